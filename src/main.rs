@@ -2,18 +2,26 @@ use anyhow::Result;
 use env_logger::Env;
 use log::info;
 use std::env;
+use std::path::{Path, PathBuf};
 use std::time::Instant;
 
-use tiny_crawler::spider::{Loader, Spider, SpiderConfig};
+use tiny_crawler::spider::{CrawlResult, Loader, Spider, SpiderConfig};
 use tiny_crawler::spider::config::defaults;
+use tiny_crawler::spider::graph::GraphFormat;
+use tiny_crawler::spider::journal::JOURNAL_FILE;
+use tiny_crawler::spider::loader::BatchMode;
+use tiny_crawler::spider::report;
+use tiny_crawler::spider::sitemap::Sitemap;
+use tiny_crawler::spider::summary::{BatchSummary, SUMMARY_JSON};
 
 #[tokio::main]
 async fn main() -> Result<()> {
     // Initialize logger
     env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
 
-    // Get command line arguments
-    let args: Vec<String> = env::args().collect();
+    // Get command line arguments, with `--` flags apart from positional arguments
+    let (flags, args): (Vec<String>, Vec<String>) =
+        env::args().partition(|arg| arg.starts_with("--"));
 
     if args.len() < 2 {
         println!("Usage:");
@@ -22,6 +30,19 @@ async fn main() -> Result<()> {
             args[0]
         );
         println!("  Multiple URLs: {} batch [url_file] [max_depth] [max_loops] [max_concurrent] [max_concurrent_sites] [min_delay_ms] [max_delay_ms]", args[0]);
+        println!("                 [--resume | --retry-failed[=batch_summary.json]]");
+        println!(
+            "  Link graph:  {} graph <result_json> [csv|graphml|dot] [output_file]",
+            args[0]
+        );
+        println!(
+            "  Sitemap:     {} sitemap <result_json> [output_dir] [base_url]",
+            args[0]
+        );
+        println!(
+            "  Report:      {} report [result_json_or_dir] [output_html]",
+            args[0]
+        );
         println!(
             "  - min_delay_ms: Minimum delay between requests in milliseconds (default: {})",
             defaults::MIN_REQUEST_DELAY_MS
@@ -104,8 +125,23 @@ async fn main() -> Result<()> {
                 .max_request_delay_ms(max_delay)
                 .build();
 
+            // Rerun only what an earlier run left unfinished, if asked to
+            let mode = if flags.iter().any(|flag| flag == "--resume") {
+                BatchMode::Resume
+            } else if let Some(flag) = flags.iter().find(|flag| flag.starts_with("--retry-failed")) {
+                let summary = flag
+                    .strip_prefix("--retry-failed=")
+                    .map(PathBuf::from)
+                    .unwrap_or_else(|| Path::new("output").join(SUMMARY_JSON));
+                BatchMode::RetryFailed(summary)
+            } else {
+                BatchMode::Fresh
+            };
+
             // Create loader
-            let loader = Loader::new(config, max_concurrent_sites, url_file);
+            let loader = Loader::new(config, max_concurrent_sites, url_file)
+                .with_journal(Path::new("output").join(JOURNAL_FILE))
+                .with_mode(mode);
 
             // Start crawling
             info!("Starting batch crawl from file: {}", url_file);
@@ -115,22 +151,116 @@ async fn main() -> Result<()> {
             );
             let start = Instant::now();
 
-            let results = loader.crawl_all().await?;
+            let batch = loader.crawl_all().await?;
 
             let duration = start.elapsed();
             info!("Batch crawl completed in {:?}", duration);
 
-            // Print results
-            for result in results {
-                match result {
-                    Ok(msg) => info!("{}", msg),
-                    Err(err) => info!("Error: {}", err),
+            // Write the per-site summary
+            let summary = BatchSummary::from_batch(&batch, duration);
+            for path in summary.write(Path::new("output"))? {
+                info!("Saved batch summary to {}", path.display());
+            }
+
+            let totals = &summary.totals;
+            info!(
+                "{} sites completed, {} failed, {} URLs found, {} input lines left out",
+                totals.completed,
+                totals.failed,
+                totals.found_urls,
+                summary.warnings.len()
+            );
+            for site in summary.sites.iter().filter(|site| site.error.is_some()) {
+                info!("Failed: {}: {}", site.url, site.error.as_deref().unwrap_or(""));
+            }
+            if totals.failed > 0 {
+                anyhow::bail!("{} of {} sites failed", totals.failed, totals.sites);
+            }
+        }
+        "graph" => {
+            if args.len() < 3 {
+                println!("Result file is required for graph command");
+                return Ok(());
+            }
+
+            let result_file = &args[2];
+            let format_name = args.get(3).map(|s| s.as_str()).unwrap_or("csv");
+            let Some(format) = GraphFormat::from_name(format_name) else {
+                println!("Unknown graph format: {}", format_name);
+                println!("Use 'csv', 'graphml' or 'dot'");
+                return Ok(());
+            };
+
+            let result: CrawlResult =
+                serde_json::from_reader(std::fs::File::open(result_file)?)?;
+
+            match args.get(4) {
+                Some(output_file) => {
+                    let mut file = std::io::BufWriter::new(std::fs::File::create(output_file)?);
+                    result.link_graph.export(format, &mut file)?;
+                    info!(
+                        "Wrote {} links to {}",
+                        result.link_graph.edges.len(),
+                        output_file
+                    );
+                }
+                None => {
+                    result.link_graph.export(format, &mut std::io::stdout().lock())?;
+                }
+            }
+        }
+        "sitemap" => {
+            if args.len() < 3 {
+                println!("Result file is required for sitemap command");
+                return Ok(());
+            }
+
+            let result: CrawlResult =
+                serde_json::from_reader(std::fs::File::open(&args[2])?)?;
+
+            // Default to a directory per domain
+            let output_dir = args.get(3).cloned().unwrap_or_else(|| {
+                format!(
+                    "output/sitemap/{}",
+                    result.base_domain.replace(['.', ':'], "_")
+                )
+            });
+
+            // Default to the root of the site the pages are listed for, after redirects
+            let sitemap = Sitemap::from_result(&result);
+            let base_url = match (args.get(4), &sitemap.origin) {
+                (Some(base_url), _) => base_url.clone(),
+                (None, Some(origin)) => format!("{}/", origin),
+                (None, None) => {
+                    println!("Base URL of {} is not valid, pass base_url", args[2]);
+                    return Ok(());
                 }
+            };
+
+            let paths = sitemap.write(Path::new(&output_dir), &base_url)?;
+            info!(
+                "Wrote {} URLs to {} sitemap file(s) in {}",
+                sitemap.entries.len(),
+                paths.len(),
+                output_dir
+            );
+        }
+        "report" => {
+            let input = args.get(2).map(|s| s.as_str()).unwrap_or("output/crawler");
+            let output = args.get(3).map(|s| s.as_str()).unwrap_or("output/report.html");
+
+            let results = report::load_results(Path::new(input))?;
+            if results.is_empty() {
+                println!("No crawl results found in {}", input);
+                return Ok(());
             }
+
+            report::write_report(&results, Path::new(output))?;
+            info!("Wrote report of {} site(s) to {}", results.len(), output);
         }
         _ => {
             println!("Unknown command: {}", command);
-            println!("Use 'crawl' or 'batch' commands");
+            println!("Use 'crawl', 'batch', 'graph', 'sitemap' or 'report' commands");
         }
     }
 
@@ -0,0 +1,311 @@
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252, X_USER_DEFINED};
+
+/// Number of bytes scanned for a `<meta>` charset declaration
+const META_PRESCAN_LIMIT: usize = 1024;
+
+/// An HTML document decoded to UTF-8
+#[derive(Debug, Clone)]
+pub struct DecodedHtml {
+    /// The decoded HTML
+    pub html: String,
+
+    /// The encoding the document was decoded from
+    pub encoding: &'static Encoding,
+}
+
+/// Decode an HTML document following the encoding sniffing steps of the HTML spec
+///
+/// The encoding is taken from, in order: a byte order mark, the charset in the
+/// Content-Type header, a `<meta>` declaration in the first 1024 bytes, and finally
+/// UTF-8 if the bytes are valid UTF-8 or windows-1252 otherwise.
+pub fn decode_html(bytes: &[u8], content_type: &str) -> DecodedHtml {
+    let encoding = sniff_encoding(bytes, content_type);
+    let (html, _, _) = encoding.decode(bytes);
+
+    DecodedHtml {
+        html: html.into_owned(),
+        encoding,
+    }
+}
+
+/// Determine the encoding of an HTML document
+pub fn sniff_encoding(bytes: &[u8], content_type: &str) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return encoding;
+    }
+
+    if let Some(encoding) = charset_from_content_type(content_type) {
+        return encoding;
+    }
+
+    if let Some(encoding) = prescan_meta_charset(bytes) {
+        return encoding;
+    }
+
+    if std::str::from_utf8(bytes).is_ok() {
+        UTF_8
+    } else {
+        WINDOWS_1252
+    }
+}
+
+/// Decode a non-HTML text document such as XML or plain text
+///
+/// The encoding is taken from a byte order mark, the Content-Type charset or an
+/// XML declaration, falling back to UTF-8 or windows-1252.
+pub fn decode_text(bytes: &[u8], content_type: &str) -> String {
+    let encoding = Encoding::for_bom(bytes)
+        .map(|(encoding, _)| encoding)
+        .or_else(|| charset_from_content_type(content_type))
+        .or_else(|| xml_declared_encoding(bytes))
+        .unwrap_or_else(|| {
+            if std::str::from_utf8(bytes).is_ok() {
+                UTF_8
+            } else {
+                WINDOWS_1252
+            }
+        });
+
+    encoding.decode(bytes).0.into_owned()
+}
+
+/// Extract the encoding from an `<?xml encoding="..."?>` declaration
+fn xml_declared_encoding(bytes: &[u8]) -> Option<&'static Encoding> {
+    let head = &bytes[..bytes.len().min(META_PRESCAN_LIMIT)];
+    let text = String::from_utf8_lossy(head);
+    let declaration = text.trim_start().strip_prefix("<?xml")?;
+    let declaration = &declaration[..declaration.find("?>")?];
+    let (_, value) = parse_attributes(declaration)
+        .into_iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("encoding"))?;
+    Encoding::for_label(value.as_bytes())
+}
+
+/// Extract the encoding from the charset parameter of a Content-Type header
+pub fn charset_from_content_type(content_type: &str) -> Option<&'static Encoding> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        if !name.trim().eq_ignore_ascii_case("charset") {
+            return None;
+        }
+        let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+        Encoding::for_label(value.as_bytes())
+    })
+}
+
+/// Look for a `<meta charset>` or `<meta http-equiv="content-type">` declaration
+///
+/// This is a simplified version of the spec's prescan algorithm: comments are
+/// skipped and only `<meta>` tags are inspected.
+pub fn prescan_meta_charset(bytes: &[u8]) -> Option<&'static Encoding> {
+    let head = &bytes[..bytes.len().min(META_PRESCAN_LIMIT)];
+    let text = String::from_utf8_lossy(head).to_ascii_lowercase();
+
+    let mut pos = 0;
+    while let Some(offset) = text[pos..].find('<') {
+        let start = pos + offset;
+
+        if text[start..].starts_with("<!--") {
+            match text[start + 4..].find("-->") {
+                Some(end) => {
+                    pos = start + 4 + end + 3;
+                    continue;
+                }
+                None => return None,
+            }
+        }
+
+        let end = match text[start..].find('>') {
+            Some(end) => start + end,
+            None => return None,
+        };
+        let tag = &text[start + 1..end];
+        pos = end + 1;
+
+        let is_meta = tag.starts_with("meta")
+            && tag[4..].starts_with(|c: char| c.is_ascii_whitespace() || c == '/');
+        if !is_meta {
+            continue;
+        }
+
+        let attributes = parse_attributes(&tag[4..]);
+        let get = |name: &str| {
+            attributes
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_str())
+        };
+
+        let label = if let Some(charset) = get("charset") {
+            Some(charset.to_string())
+        } else if get("http-equiv") == Some("content-type") {
+            get("content").and_then(charset_from_meta_content)
+        } else {
+            None
+        };
+
+        if let Some(encoding) = label.and_then(|l| Encoding::for_label(l.trim().as_bytes())) {
+            return Some(meta_encoding_override(encoding));
+        }
+    }
+
+    None
+}
+
+/// Extract the charset value from the content attribute of a `<meta http-equiv>` tag
+fn charset_from_meta_content(content: &str) -> Option<String> {
+    let index = content.find("charset")?;
+    let rest = content[index + "charset".len()..].trim_start();
+    let rest = rest.strip_prefix('=')?.trim_start();
+
+    let value = if let Some(quoted) = rest.strip_prefix(['"', '\'']) {
+        quoted.split(['"', '\'']).next()?
+    } else {
+        rest.split(|c: char| c == ';' || c.is_ascii_whitespace())
+            .next()?
+    };
+
+    if value.is_empty() {
+        None
+    } else {
+        Some(value.to_string())
+    }
+}
+
+/// Parse `name="value"` attribute pairs from the inside of a tag
+fn parse_attributes(input: &str) -> Vec<(String, String)> {
+    let mut attributes = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    loop {
+        while chars
+            .peek()
+            .is_some_and(|c| c.is_ascii_whitespace() || *c == '/')
+        {
+            chars.next();
+        }
+
+        let mut name = String::new();
+        while let Some(&c) = chars.peek() {
+            if c == '=' || c.is_ascii_whitespace() || c == '/' {
+                break;
+            }
+            name.push(c);
+            chars.next();
+        }
+        if name.is_empty() {
+            break;
+        }
+
+        while chars.peek().is_some_and(|c| c.is_ascii_whitespace()) {
+            chars.next();
+        }
+
+        let mut value = String::new();
+        if chars.peek() == Some(&'=') {
+            chars.next();
+            while chars.peek().is_some_and(|c| c.is_ascii_whitespace()) {
+                chars.next();
+            }
+            match chars.peek() {
+                Some(&quote) if quote == '"' || quote == '\'' => {
+                    chars.next();
+                    for c in chars.by_ref() {
+                        if c == quote {
+                            break;
+                        }
+                        value.push(c);
+                    }
+                }
+                _ => {
+                    while let Some(&c) = chars.peek() {
+                        if c.is_ascii_whitespace() {
+                            break;
+                        }
+                        value.push(c);
+                        chars.next();
+                    }
+                }
+            }
+        }
+
+        attributes.push((name, value));
+    }
+
+    attributes
+}
+
+/// Apply the spec's overrides for encodings declared in `<meta>`
+fn meta_encoding_override(encoding: &'static Encoding) -> &'static Encoding {
+    if encoding == UTF_16LE || encoding == UTF_16BE {
+        UTF_8
+    } else if encoding == X_USER_DEFINED {
+        WINDOWS_1252
+    } else {
+        encoding
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::{SHIFT_JIS, WINDOWS_1251};
+
+    #[test]
+    fn test_bom_wins() {
+        let bytes = b"\xEF\xBB\xBF<meta charset=\"shift_jis\">";
+        assert_eq!(sniff_encoding(bytes, "text/html; charset=gbk"), UTF_8);
+    }
+
+    #[test]
+    fn test_header_charset() {
+        let bytes = b"<meta charset=\"shift_jis\">";
+        assert_eq!(
+            sniff_encoding(bytes, "text/html; charset=\"windows-1251\""),
+            WINDOWS_1251
+        );
+    }
+
+    #[test]
+    fn test_meta_charset() {
+        let bytes = b"<html><head><meta charset=Shift_JIS></head>";
+        assert_eq!(sniff_encoding(bytes, "text/html"), SHIFT_JIS);
+    }
+
+    #[test]
+    fn test_meta_http_equiv() {
+        let bytes = b"<!-- <meta charset=\"gbk\"> --><meta http-equiv=\"Content-Type\" content=\"text/html; charset=windows-1251\">";
+        assert_eq!(sniff_encoding(bytes, "text/html"), WINDOWS_1251);
+    }
+
+    #[test]
+    fn test_meta_utf16_becomes_utf8() {
+        let bytes = b"<meta charset=\"utf-16\">";
+        assert_eq!(sniff_encoding(bytes, ""), UTF_8);
+    }
+
+    #[test]
+    fn test_fallback() {
+        assert_eq!(sniff_encoding("<p>caf\u{e9}</p>".as_bytes(), ""), UTF_8);
+        assert_eq!(sniff_encoding(b"<p>caf\xE9</p>", ""), WINDOWS_1252);
+    }
+
+    #[test]
+    fn test_decode_text_xml_declaration() {
+        let (bytes, _, _) = WINDOWS_1251
+            .encode("<?xml version=\"1.0\" encoding=\"windows-1251\"?><loc>\u{0434}</loc>");
+        assert_eq!(
+            decode_text(&bytes, "application/xml"),
+            "<?xml version=\"1.0\" encoding=\"windows-1251\"?><loc>\u{0434}</loc>"
+        );
+    }
+
+    #[test]
+    fn test_decode_shift_jis() {
+        let (bytes, _, _) =
+            SHIFT_JIS.encode("<meta charset=\"shift_jis\"><a href=\"/\u{65e5}\u{672c}\">");
+        let decoded = decode_html(&bytes, "text/html");
+        assert_eq!(decoded.encoding, SHIFT_JIS);
+        assert!(decoded.html.contains("/\u{65e5}\u{672c}"));
+    }
+}
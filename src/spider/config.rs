@@ -1,3 +1,14 @@
+use crate::spider::error::SpiderError;
+use crate::spider::store::ContentStoreConfig;
+use crate::spider::strategy::{CrawlStrategy, LinkLocation, LinkScoring};
+use crate::spider::templates::TemplateQuota;
+use crate::spider::traps::TrapConfig;
+use crate::spider::utils::{DomainScope, ScopeAction, ScopePattern, ScopeRule, ScopeTarget};
+use crate::spider::warc::WarcConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
 /// Default configuration constants
 pub mod defaults {
     /// Default maximum depth for recursive crawling
@@ -12,8 +23,17 @@ pub mod defaults {
     /// Default maximum number of concurrent sites to crawl
     pub const MAX_CONCURRENT_SITES: usize = 5;
 
-    /// Default threshold for detecting massive link patterns
-    pub const PATTERN_THRESHOLD: usize = 500;
+    /// Default maximum number of URLs crawled per URL template
+    pub const MAX_URLS_PER_TEMPLATE: usize = 500;
+
+    /// Default maximum size of the stored main content text per page, in bytes
+    pub const MAX_TEXT_BYTES: usize = 64 * 1024;
+
+    /// Default size at which a new WARC file is started, in bytes
+    pub const MAX_WARC_FILE_SIZE: u64 = 1024 * 1024 * 1024;
+
+    /// Default maximum SimHash distance for pages to count as near-duplicates
+    pub const NEAR_DUPLICATE_DISTANCE: u32 = 3;
 
     /// Default minimum delay between requests in milliseconds
     pub const MIN_REQUEST_DELAY_MS: u64 = 100;
@@ -62,6 +82,27 @@ pub mod defaults {
     /// Default priority paths
     pub const PRIORITY_PATHS: &[&str] = &["/contact", "/about", "/faq", "/help", "/support"];
 
+    /// Default keywords raising the score of links whose text mentions them
+    pub const PRIORITY_KEYWORDS: &[&str] = &["contact", "about", "faq", "help", "support"];
+
+    /// Default score added for each keyword found in a link's text
+    pub const PRIORITY_KEYWORD_WEIGHT: i64 = 40;
+
+    /// Default user agent used in identify mode
+    pub const BOT_USER_AGENT: &str =
+        "TinyCrawler/0.1 (+https://github.com/shuhaodo/tiny-crawler)";
+
+    /// Default headers sent with every request
+    pub const DEFAULT_HEADERS: &[(&str, &str)] = &[
+        (
+            "Accept",
+            "text/html,application/xhtml+xml,application/xml;q=0.9,image/webp,*/*;q=0.8",
+        ),
+        ("Accept-Language", "en-US,en;q=0.5"),
+        ("Accept-Encoding", "gzip, deflate, br"),
+        ("DNT", "1"),
+    ];
+
     /// Default user agents
     pub const USER_AGENTS: &[&str] = &[
         "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36",
@@ -73,6 +114,43 @@ pub mod defaults {
     ];
 }
 
+/// How the User-Agent header is chosen for each request
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum UserAgentMode {
+    /// Pick a random user agent from `user_agents` for every request
+    #[default]
+    Rotate,
+
+    /// Always send the given bot user agent and obey robots.txt rules for it
+    Identify(String),
+}
+
+/// What to send in the Referer header
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RefererPolicy {
+    /// Never send a Referer header
+    None,
+
+    /// Send the origin of the page the link was found on
+    #[default]
+    Origin,
+
+    /// Send the URL of the page the link was found on
+    LinkingPage,
+}
+
+impl RefererPolicy {
+    /// Parse a policy name such as `linking_page`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "none" => Some(RefererPolicy::None),
+            "origin" => Some(RefererPolicy::Origin),
+            "linking_page" => Some(RefererPolicy::LinkingPage),
+            _ => None,
+        }
+    }
+}
+
 /// Configuration for the spider
 #[derive(Debug, Clone)]
 pub struct SpiderConfig {
@@ -85,8 +163,11 @@ pub struct SpiderConfig {
     /// Maximum number of concurrent requests
     pub max_concurrent: usize,
 
-    /// Threshold for detecting massive link patterns
-    pub pattern_threshold: usize,
+    /// Maximum number of URLs crawled per URL template before it is treated as a trap
+    pub max_urls_per_template: usize,
+
+    /// Crawler trap detection settings
+    pub traps: TrapConfig,
 
     /// List of path patterns to skip
     pub skip_patterns: Vec<String>,
@@ -106,6 +187,71 @@ pub struct SpiderConfig {
     /// List of user agents to rotate through for requests
     pub user_agents: Vec<String>,
 
+    /// How the User-Agent header is chosen
+    pub user_agent_mode: UserAgentMode,
+
+    /// Headers sent with every request
+    pub default_headers: Vec<(String, String)>,
+
+    /// Extra headers sent to specific hosts, keyed by host name
+    pub host_headers: HashMap<String, Vec<(String, String)>>,
+
+    /// What to send in the Referer header
+    pub referer_policy: RefererPolicy,
+
+    /// Check whether each unique external link is alive after the crawl
+    pub validate_external_links: bool,
+
+    /// Include and exclude rules, evaluated in order with the first match winning
+    pub scope_rules: Vec<ScopeRule>,
+
+    /// Action for URLs that match no scope rule
+    pub scope_default: ScopeAction,
+
+    /// Which hosts count as the same site as the start URL
+    pub domain_scope: DomainScope,
+
+    /// Extra hosts crawled as part of the site, e.g. brand domains
+    pub allowed_hosts: Vec<String>,
+
+    /// Limits on how many URLs matching a template are crawled
+    pub template_quotas: Vec<TemplateQuota>,
+
+    /// Maximum SimHash distance for pages to count as near-duplicates
+    pub near_duplicate_distance: u32,
+
+    /// Do not follow links on pages that duplicate an already crawled page
+    pub skip_duplicate_links: bool,
+
+    /// Order in which queued URLs are crawled
+    pub strategy: CrawlStrategy,
+
+    /// Keyword and location weights used by the default strategy
+    pub link_scoring: LinkScoring,
+
+    /// Collect email addresses, phone numbers, postal addresses and social profiles
+    pub extract_contacts: bool,
+
+    /// Extract JSON-LD, microdata, RDFa, OpenGraph and Twitter card data from pages
+    pub extract_structured_data: bool,
+
+    /// Store the main content text, headings and language of each page
+    pub extract_text: bool,
+
+    /// Maximum size of the stored main content text per page, in bytes
+    pub max_text_bytes: usize,
+
+    /// Write every fetch to WARC files, if set
+    pub warc: Option<WarcConfig>,
+
+    /// Save every fetched body to a content store, if set
+    pub content_store: Option<ContentStoreConfig>,
+
+    /// Write results to a SQLite database at this path, if set
+    pub sqlite: Option<PathBuf>,
+
+    /// Labels copied to the crawl result, e.g. from the batch input
+    pub tags: Vec<String>,
 }
 
 impl Default for SpiderConfig {
@@ -116,7 +262,8 @@ impl Default for SpiderConfig {
             max_depth: MAX_DEPTH,
             max_loops: MAX_LOOPS,
             max_concurrent: MAX_CONCURRENT,
-            pattern_threshold: PATTERN_THRESHOLD,
+            max_urls_per_template: MAX_URLS_PER_TEMPLATE,
+            traps: TrapConfig::default(),
             skip_patterns: SKIP_PATTERNS.iter().map(|s| s.to_string()).collect(),
             skip_subdomain_patterns: SKIP_SUBDOMAIN_PATTERNS
                 .iter()
@@ -126,6 +273,31 @@ impl Default for SpiderConfig {
             min_request_delay_ms: MIN_REQUEST_DELAY_MS,
             max_request_delay_ms: MAX_REQUEST_DELAY_MS,
             user_agents: USER_AGENTS.iter().map(|s| s.to_string()).collect(),
+            user_agent_mode: UserAgentMode::default(),
+            default_headers: DEFAULT_HEADERS
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            host_headers: HashMap::new(),
+            referer_policy: RefererPolicy::default(),
+            validate_external_links: false,
+            scope_rules: Vec::new(),
+            scope_default: ScopeAction::Include,
+            domain_scope: DomainScope::default(),
+            allowed_hosts: Vec::new(),
+            template_quotas: Vec::new(),
+            near_duplicate_distance: NEAR_DUPLICATE_DISTANCE,
+            skip_duplicate_links: false,
+            strategy: CrawlStrategy::default(),
+            link_scoring: LinkScoring::default(),
+            extract_contacts: false,
+            extract_structured_data: false,
+            extract_text: false,
+            max_text_bytes: MAX_TEXT_BYTES,
+            warc: None,
+            content_store: None,
+            sqlite: None,
+            tags: Vec::new(),
         }
     }
 }
@@ -145,21 +317,43 @@ impl SpiderConfig {
     pub fn builder() -> SpiderConfigBuilder {
         SpiderConfigBuilder::default()
     }
+
+    /// The user agent to match against robots.txt rules, if robots.txt should be obeyed
+    pub fn robots_user_agent(&self) -> Option<&str> {
+        match &self.user_agent_mode {
+            UserAgentMode::Rotate => None,
+            UserAgentMode::Identify(user_agent) => Some(user_agent),
+        }
+    }
+
+    /// Check that the settings can be crawled with
+    pub fn validate(&self) -> Result<(), SpiderError> {
+        if self.min_request_delay_ms > self.max_request_delay_ms {
+            return Err(SpiderError::InvalidInput(format!(
+                "min_request_delay_ms ({}) is greater than max_request_delay_ms ({})",
+                self.min_request_delay_ms, self.max_request_delay_ms
+            )));
+        }
+        if self.user_agents.is_empty() {
+            return Err(SpiderError::InvalidInput(
+                "user_agents is empty".to_string(),
+            ));
+        }
+        if self.max_concurrent == 0 {
+            return Err(SpiderError::InvalidInput(
+                "max_concurrent must be at least 1".to_string(),
+            ));
+        }
+        Ok(())
+    }
 }
 
 /// Builder for SpiderConfig to allow for more granular configuration
+#[derive(Default)]
 pub struct SpiderConfigBuilder {
     config: SpiderConfig,
 }
 
-impl Default for SpiderConfigBuilder {
-    fn default() -> Self {
-        Self {
-            config: SpiderConfig::default(),
-        }
-    }
-}
-
 impl SpiderConfigBuilder {
     /// Set the maximum crawl depth
     pub fn max_depth(mut self, max_depth: usize) -> Self {
@@ -179,9 +373,15 @@ impl SpiderConfigBuilder {
         self
     }
 
-    /// Set the pattern threshold for detecting massive link patterns
-    pub fn pattern_threshold(mut self, threshold: usize) -> Self {
-        self.config.pattern_threshold = threshold;
+    /// Set the maximum number of URLs crawled per URL template
+    pub fn max_urls_per_template(mut self, max_urls: usize) -> Self {
+        self.config.max_urls_per_template = max_urls;
+        self
+    }
+
+    /// Set the crawler trap detection settings
+    pub fn traps(mut self, traps: TrapConfig) -> Self {
+        self.config.traps = traps;
         self
     }
 
@@ -254,8 +454,635 @@ impl SpiderConfigBuilder {
         self
     }
 
+    /// Identify as a bot: disable user agent rotation and obey robots.txt
+    pub fn identify(mut self, user_agent: &str) -> Self {
+        self.config.user_agent_mode = UserAgentMode::Identify(user_agent.to_string());
+        self
+    }
+
+    /// Add a header sent with every request
+    pub fn add_default_header(mut self, name: &str, value: &str) -> Self {
+        self.config
+            .default_headers
+            .push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Replace all default headers
+    pub fn default_headers(mut self, headers: &[(&str, &str)]) -> Self {
+        self.config.default_headers = headers
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        self
+    }
+
+    /// Add a header sent only to the given host
+    pub fn add_host_header(mut self, host: &str, name: &str, value: &str) -> Self {
+        self.config
+            .host_headers
+            .entry(host.to_string())
+            .or_default()
+            .push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Set the referer policy
+    pub fn referer_policy(mut self, policy: RefererPolicy) -> Self {
+        self.config.referer_policy = policy;
+        self
+    }
+
+    /// Enable or disable checking external links after the crawl
+    pub fn validate_external_links(mut self, validate: bool) -> Self {
+        self.config.validate_external_links = validate;
+        self
+    }
+
+    /// Set the maximum SimHash distance for pages to count as near-duplicates
+    pub fn near_duplicate_distance(mut self, distance: u32) -> Self {
+        self.config.near_duplicate_distance = distance;
+        self
+    }
+
+    /// Enable or disable following links on duplicate pages
+    pub fn skip_duplicate_links(mut self, skip: bool) -> Self {
+        self.config.skip_duplicate_links = skip;
+        self
+    }
+
+    /// Set the order in which queued URLs are crawled
+    pub fn strategy(mut self, strategy: CrawlStrategy) -> Self {
+        self.config.strategy = strategy;
+        self
+    }
+
+    /// Set the keyword and location weights used by the default strategy
+    pub fn link_scoring(mut self, scoring: LinkScoring) -> Self {
+        self.config.link_scoring = scoring;
+        self
+    }
+
+    /// Add a keyword raising the score of links whose anchor text or title contains it
+    pub fn keyword_weight(mut self, keyword: &str, weight: i64) -> Self {
+        self.config
+            .link_scoring
+            .keyword_weights
+            .push((keyword.to_string(), weight));
+        self
+    }
+
+    /// Set the score added for links found in the given part of a page
+    pub fn location_weight(mut self, location: LinkLocation, weight: i64) -> Self {
+        self.config
+            .link_scoring
+            .location_weights
+            .insert(location, weight);
+        self
+    }
+
+    /// Enable or disable collecting contact details from crawled pages
+    pub fn extract_contacts(mut self, extract: bool) -> Self {
+        self.config.extract_contacts = extract;
+        self
+    }
+
+    /// Enable or disable extracting structured data from crawled pages
+    pub fn extract_structured_data(mut self, extract: bool) -> Self {
+        self.config.extract_structured_data = extract;
+        self
+    }
+
+    /// Enable or disable storing the main content text of crawled pages
+    pub fn extract_text(mut self, extract: bool) -> Self {
+        self.config.extract_text = extract;
+        self
+    }
+
+    /// Set the maximum size of the stored main content text per page, in bytes
+    pub fn max_text_bytes(mut self, max_bytes: usize) -> Self {
+        self.config.max_text_bytes = max_bytes;
+        self
+    }
+
+    /// Write every fetch to WARC files with the given settings
+    pub fn warc(mut self, warc: WarcConfig) -> Self {
+        self.config.warc = Some(warc);
+        self
+    }
+
+    /// Save every fetched body to a content store with the given settings
+    pub fn content_store(mut self, content_store: ContentStoreConfig) -> Self {
+        self.config.content_store = Some(content_store);
+        self
+    }
+
+    /// Write results to a SQLite database at the given path as the crawl runs
+    pub fn sqlite(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.sqlite = Some(path.into());
+        self
+    }
+
+    /// Add labels copied to the crawl result
+    pub fn add_tags(mut self, tags: &[&str]) -> Self {
+        self.config.tags.extend(tags.iter().map(|s| s.to_string()));
+        self
+    }
+
+    /// Append a scope rule
+    pub fn add_scope_rule(mut self, rule: ScopeRule) -> Self {
+        self.config.scope_rules.push(rule);
+        self
+    }
+
+    /// Append a rule including URLs whose target matches the pattern
+    pub fn include(
+        self,
+        name: &str,
+        target: ScopeTarget,
+        pattern: ScopePattern,
+    ) -> Result<Self, SpiderError> {
+        let rule = ScopeRule::new(name, ScopeAction::Include, target, pattern)?;
+        Ok(self.add_scope_rule(rule))
+    }
+
+    /// Append a rule excluding URLs whose target matches the pattern
+    pub fn exclude(
+        self,
+        name: &str,
+        target: ScopeTarget,
+        pattern: ScopePattern,
+    ) -> Result<Self, SpiderError> {
+        let rule = ScopeRule::new(name, ScopeAction::Exclude, target, pattern)?;
+        Ok(self.add_scope_rule(rule))
+    }
+
+    /// Set the action for URLs that match no scope rule
+    pub fn scope_default(mut self, action: ScopeAction) -> Self {
+        self.config.scope_default = action;
+        self
+    }
+
+    /// Set which hosts count as the same site as the start URL
+    pub fn domain_scope(mut self, scope: DomainScope) -> Self {
+        self.config.domain_scope = scope;
+        self
+    }
+
+    /// Add hosts crawled as part of the site
+    pub fn add_allowed_hosts(mut self, hosts: &[&str]) -> Self {
+        self.config
+            .allowed_hosts
+            .extend(hosts.iter().map(|s| s.to_string()));
+        self
+    }
+
+    /// Add a limit on how many URLs matching a template are crawled
+    pub fn add_template_quota(mut self, quota: TemplateQuota) -> Self {
+        self.config.template_quotas.push(quota);
+        self
+    }
+
+    /// Crawl at most `max_urls` URLs matching a path template such as `/product/{id}`
+    pub fn template_quota(self, pattern: &str, max_urls: usize) -> Result<Self, SpiderError> {
+        let quota = TemplateQuota::new(pattern, max_urls)?;
+        Ok(self.add_template_quota(quota))
+    }
+
     /// Build the final SpiderConfig
     pub fn build(self) -> SpiderConfig {
         self.config
     }
 }
+
+/// `SpiderConfig` fields that only the batch config can set, not `ConfigOverrides`
+pub const BATCH_ONLY_SETTINGS: &[&str] = &["warc", "content_store", "sqlite"];
+
+/// Settings overriding parts of a `SpiderConfig` for a single site
+///
+/// Used for the rows of a batch input file. Every field left as `None` keeps
+/// the value of the batch config. Enum settings are given by name, e.g.
+/// `"strategy": "breadth_first"`, and template quotas as a map from path
+/// template to maximum URL count.
+///
+/// The settings listed in `BATCH_ONLY_SETTINGS` cannot be overridden: the WARC,
+/// content store and SQLite outputs are shared by the whole batch.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigOverrides {
+    /// Maximum crawl depth
+    pub max_depth: Option<usize>,
+
+    /// Maximum number of loops
+    pub max_loops: Option<usize>,
+
+    /// Maximum number of concurrent requests
+    pub max_concurrent: Option<usize>,
+
+    /// Maximum number of URLs crawled per URL template
+    pub max_urls_per_template: Option<usize>,
+
+    /// Trap detection settings
+    pub traps: Option<TrapOverrides>,
+
+    /// Minimum request delay in milliseconds
+    pub min_request_delay_ms: Option<u64>,
+
+    /// Maximum request delay in milliseconds
+    pub max_request_delay_ms: Option<u64>,
+
+    /// Replaces the skip patterns
+    pub skip_patterns: Option<Vec<String>>,
+
+    /// Added to the skip patterns
+    pub add_skip_patterns: Option<Vec<String>>,
+
+    /// Replaces the subdomain skip patterns
+    pub skip_subdomain_patterns: Option<Vec<String>>,
+
+    /// Replaces the priority paths
+    pub priority_paths: Option<Vec<String>>,
+
+    /// Replaces the rotated user agents
+    pub user_agents: Option<Vec<String>>,
+
+    /// Identify with this user agent and obey robots.txt
+    pub identify: Option<String>,
+
+    /// Headers added to the default headers
+    pub headers: Option<BTreeMap<String, String>>,
+
+    /// Headers added to the per-host headers, keyed by host name
+    pub host_headers: Option<BTreeMap<String, BTreeMap<String, String>>>,
+
+    /// Referer policy: `none`, `origin` or `linking_page`
+    pub referer_policy: Option<String>,
+
+    /// Check external links after the crawl
+    pub validate_external_links: Option<bool>,
+
+    /// Domain scope: `exact_host`, `all_subdomains` or `registrable_domain`
+    pub domain_scope: Option<String>,
+
+    /// Replaces the hosts crawled as part of the site
+    pub allowed_hosts: Option<Vec<String>>,
+
+    /// Replaces the scope rules
+    pub scope_rules: Option<Vec<ScopeRuleOverride>>,
+
+    /// Action for URLs that match no scope rule: `include` or `exclude`
+    pub scope_default: Option<String>,
+
+    /// Template quotas added to the batch ones
+    pub template_quotas: Option<BTreeMap<String, usize>>,
+
+    /// Maximum SimHash distance for near-duplicates
+    pub near_duplicate_distance: Option<u32>,
+
+    /// Do not follow links on duplicate pages
+    pub skip_duplicate_links: Option<bool>,
+
+    /// Crawl strategy: `priority_paths`, `breadth_first`, `depth_first` or `round_robin_hosts`
+    pub strategy: Option<String>,
+
+    /// Link scoring weights
+    pub link_scoring: Option<LinkScoringOverrides>,
+
+    /// Collect contact details
+    pub extract_contacts: Option<bool>,
+
+    /// Extract structured data
+    pub extract_structured_data: Option<bool>,
+
+    /// Store the main content text
+    pub extract_text: Option<bool>,
+
+    /// Maximum size of the stored main content text per page, in bytes
+    pub max_text_bytes: Option<usize>,
+}
+
+impl ConfigOverrides {
+    /// Apply the overrides to a copy of `config`, checking the merged settings
+    pub fn apply(&self, config: &SpiderConfig) -> Result<SpiderConfig, SpiderError> {
+        let mut config = config.clone();
+
+        macro_rules! set {
+            ($($field:ident),*) => {
+                $(if let Some(value) = &self.$field {
+                    config.$field = value.clone();
+                })*
+            };
+        }
+        set!(
+            max_depth,
+            max_loops,
+            max_concurrent,
+            max_urls_per_template,
+            min_request_delay_ms,
+            max_request_delay_ms,
+            skip_patterns,
+            skip_subdomain_patterns,
+            priority_paths,
+            user_agents,
+            validate_external_links,
+            allowed_hosts,
+            near_duplicate_distance,
+            skip_duplicate_links,
+            extract_contacts,
+            extract_structured_data,
+            extract_text,
+            max_text_bytes
+        );
+
+        if let Some(patterns) = &self.add_skip_patterns {
+            config.skip_patterns.extend(patterns.iter().cloned());
+        }
+        if let Some(user_agent) = &self.identify {
+            config.user_agent_mode = UserAgentMode::Identify(user_agent.clone());
+        }
+        if let Some(headers) = &self.headers {
+            config.default_headers.extend(
+                headers
+                    .iter()
+                    .map(|(name, value)| (name.clone(), value.clone())),
+            );
+        }
+        if let Some(host_headers) = &self.host_headers {
+            for (host, headers) in host_headers {
+                config.host_headers.entry(host.clone()).or_default().extend(
+                    headers
+                        .iter()
+                        .map(|(name, value)| (name.clone(), value.clone())),
+                );
+            }
+        }
+        if let Some(name) = &self.referer_policy {
+            config.referer_policy = RefererPolicy::from_name(name)
+                .ok_or_else(|| unknown_value("referer_policy", name))?;
+        }
+        if let Some(name) = &self.domain_scope {
+            config.domain_scope =
+                DomainScope::from_name(name).ok_or_else(|| unknown_value("domain_scope", name))?;
+        }
+        if let Some(rules) = &self.scope_rules {
+            config.scope_rules = rules
+                .iter()
+                .map(ScopeRuleOverride::rule)
+                .collect::<Result<_, _>>()?;
+        }
+        if let Some(name) = &self.scope_default {
+            config.scope_default =
+                ScopeAction::from_name(name).ok_or_else(|| unknown_value("scope_default", name))?;
+        }
+        if let Some(name) = &self.strategy {
+            config.strategy =
+                CrawlStrategy::from_name(name).ok_or_else(|| unknown_value("strategy", name))?;
+        }
+        if let Some(traps) = &self.traps {
+            traps.apply(&mut config.traps);
+        }
+        if let Some(scoring) = &self.link_scoring {
+            scoring.apply(&mut config.link_scoring)?;
+        }
+        if let Some(quotas) = &self.template_quotas {
+            for (pattern, max_urls) in quotas {
+                config
+                    .template_quotas
+                    .push(TemplateQuota::new(pattern, *max_urls)?);
+            }
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+/// A scope rule in `ConfigOverrides`, matching with one of `prefix`, `glob` or `regex`
+///
+/// For example `{"name": "blog", "action": "exclude", "target": "path", "glob": "/blog/**"}`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScopeRuleOverride {
+    /// Name recorded as the skip reason when the rule excludes a URL
+    pub name: String,
+
+    /// `include` or `exclude`
+    pub action: String,
+
+    /// `host`, `path` or `query`
+    pub target: String,
+
+    /// Match targets starting with this string
+    pub prefix: Option<String>,
+
+    /// Match targets with this glob
+    pub glob: Option<String>,
+
+    /// Match targets with this regular expression
+    pub regex: Option<String>,
+}
+
+impl ScopeRuleOverride {
+    /// Compile the rule
+    pub fn rule(&self) -> Result<ScopeRule, SpiderError> {
+        let action = ScopeAction::from_name(&self.action)
+            .ok_or_else(|| unknown_value("scope rule action", &self.action))?;
+        let target = ScopeTarget::from_name(&self.target)
+            .ok_or_else(|| unknown_value("scope rule target", &self.target))?;
+        let pattern = match (&self.prefix, &self.glob, &self.regex) {
+            (Some(prefix), None, None) => ScopePattern::Prefix(prefix.clone()),
+            (None, Some(glob), None) => ScopePattern::Glob(glob.clone()),
+            (None, None, Some(regex)) => ScopePattern::Regex(regex.clone()),
+            _ => {
+                return Err(SpiderError::InvalidInput(format!(
+                    "scope rule {} needs one of prefix, glob or regex",
+                    self.name
+                )))
+            }
+        };
+        ScopeRule::new(&self.name, action, target, pattern)
+    }
+}
+
+/// Trap detection settings in `ConfigOverrides`; fields left as `None` keep the batch value
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TrapOverrides {
+    /// URLs longer than this are treated as traps
+    pub max_url_length: Option<usize>,
+
+    /// URLs with more query parameters than this are treated as traps
+    pub max_query_params: Option<usize>,
+
+    /// Maximum number of distinct query parameter combinations per path
+    pub max_query_combinations: Option<usize>,
+
+    /// How many times a sequence of path segments may repeat back to back
+    pub max_path_repeats: Option<usize>,
+
+    /// Calendar pages more than this many years in the past are traps
+    pub calendar_years_back: Option<i32>,
+
+    /// Calendar pages more than this many years in the future are traps
+    pub calendar_years_ahead: Option<i32>,
+}
+
+impl TrapOverrides {
+    /// Apply the overrides to the batch trap settings
+    fn apply(&self, traps: &mut TrapConfig) {
+        macro_rules! set {
+            ($($field:ident),*) => {
+                $(if let Some(value) = self.$field {
+                    traps.$field = value;
+                })*
+            };
+        }
+        set!(
+            max_url_length,
+            max_query_params,
+            max_query_combinations,
+            max_path_repeats,
+            calendar_years_back,
+            calendar_years_ahead
+        );
+    }
+}
+
+/// Link scoring weights in `ConfigOverrides`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LinkScoringOverrides {
+    /// Replaces the keyword weights
+    pub keyword_weights: Option<BTreeMap<String, i64>>,
+
+    /// Replaces the location weights, keyed by location name such as `nav` or `footer`
+    pub location_weights: Option<BTreeMap<String, i64>>,
+}
+
+impl LinkScoringOverrides {
+    /// Apply the overrides to the batch link scoring
+    fn apply(&self, scoring: &mut LinkScoring) -> Result<(), SpiderError> {
+        if let Some(weights) = &self.keyword_weights {
+            scoring.keyword_weights = weights
+                .iter()
+                .map(|(keyword, weight)| (keyword.clone(), *weight))
+                .collect();
+        }
+        if let Some(weights) = &self.location_weights {
+            scoring.location_weights = weights
+                .iter()
+                .map(|(name, weight)| {
+                    LinkLocation::from_name(name)
+                        .map(|location| (location, *weight))
+                        .ok_or_else(|| unknown_value("link location", name))
+                })
+                .collect::<Result<_, _>>()?;
+        }
+        Ok(())
+    }
+}
+
+/// Error for an unrecognized setting value
+fn unknown_value(setting: &str, value: &str) -> SpiderError {
+    SpiderError::InvalidInput(format!("unknown {}: {}", setting, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overrides_apply() {
+        let base = SpiderConfig::builder().max_depth(5).build();
+        let overrides: ConfigOverrides = serde_json::from_str(
+            r#"{"max_depth": 2, "add_skip_patterns": ["/shop/"], "identify": "Bot/1.0",
+                "strategy": "breadth_first", "template_quotas": {"/p/{id}": 3}}"#,
+        )
+        .unwrap();
+
+        let config = overrides.apply(&base).unwrap();
+        assert_eq!(config.max_depth, 2);
+        assert_eq!(config.max_loops, base.max_loops);
+        assert_eq!(config.skip_patterns.last().unwrap(), "/shop/");
+        assert_eq!(config.skip_patterns.len(), base.skip_patterns.len() + 1);
+        assert_eq!(config.robots_user_agent(), Some("Bot/1.0"));
+        assert!(matches!(config.strategy, CrawlStrategy::BreadthFirst));
+        assert_eq!(config.template_quotas.len(), 1);
+
+        let unknown = ConfigOverrides {
+            domain_scope: Some("everything".to_string()),
+            ..Default::default()
+        };
+        assert!(unknown.apply(&base).is_err());
+        assert!(serde_json::from_str::<ConfigOverrides>(r#"{"max_dpth": 2}"#).is_err());
+    }
+
+    #[test]
+    fn test_overrides_per_site_settings() {
+        let base = SpiderConfig::builder()
+            .add_host_header("a.com", "X-Batch", "1")
+            .keyword_weight("pricing", 5)
+            .build();
+        let overrides: ConfigOverrides = serde_json::from_str(
+            r#"{"scope_rules": [{"name": "blog", "action": "exclude", "target": "path",
+                                 "glob": "/blog/**"}],
+                "scope_default": "exclude",
+                "host_headers": {"a.com": {"X-Site": "2"}},
+                "traps": {"max_url_length": 200},
+                "link_scoring": {"keyword_weights": {"docs": 3}, "location_weights": {"nav": -2}}}"#,
+        )
+        .unwrap();
+
+        let config = overrides.apply(&base).unwrap();
+        assert_eq!(config.scope_rules.len(), 1);
+        assert_eq!(config.scope_rules[0].action, ScopeAction::Exclude);
+        assert_eq!(config.scope_default, ScopeAction::Exclude);
+        assert_eq!(config.host_headers["a.com"].len(), 2);
+        assert_eq!(config.traps.max_url_length, 200);
+        assert_eq!(config.traps.max_query_params, base.traps.max_query_params);
+        assert_eq!(
+            config.link_scoring.keyword_weights,
+            vec![("docs".to_string(), 3)]
+        );
+        assert_eq!(config.link_scoring.location_weights[&LinkLocation::Nav], -2);
+
+        let invalid = |json: &str| {
+            let overrides: ConfigOverrides = serde_json::from_str(json).unwrap();
+            overrides.apply(&base).is_err()
+        };
+        assert!(invalid(
+            r#"{"scope_rules": [{"name": "x", "action": "exclude", "target": "path"}]}"#
+        ));
+        assert!(invalid(
+            r#"{"link_scoring": {"location_weights": {"sidebar": 1}}}"#
+        ));
+    }
+
+    #[test]
+    fn test_overrides_validate_merged_config() {
+        let base = SpiderConfig::builder()
+            .min_request_delay_ms(100)
+            .max_request_delay_ms(1000)
+            .build();
+        let invalid = |json: &str| {
+            let overrides: ConfigOverrides = serde_json::from_str(json).unwrap();
+            match overrides.apply(&base) {
+                Err(SpiderError::InvalidInput(message)) => message,
+                other => panic!(
+                    "expected an invalid input error, got {:?}",
+                    other.map(|_| ())
+                ),
+            }
+        };
+
+        // Checked against the batch config, not just the row
+        assert!(invalid(r#"{"min_request_delay_ms": 5000}"#).contains("min_request_delay_ms"));
+        assert!(invalid(r#"{"user_agents": []}"#).contains("user_agents"));
+        assert!(invalid(r#"{"max_concurrent": 0}"#).contains("max_concurrent"));
+
+        let overrides: ConfigOverrides =
+            serde_json::from_str(r#"{"min_request_delay_ms": 5000, "max_request_delay_ms": 6000}"#)
+                .unwrap();
+        assert!(overrides.apply(&base).is_ok());
+    }
+}
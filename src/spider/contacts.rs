@@ -0,0 +1,374 @@
+use crate::spider::dedup::visible_text;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use scraper::{Html, Selector};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use url::Url;
+
+/// Social networks recognized in links, by host
+const SOCIAL_NETWORKS: &[(&str, &str)] = &[
+    ("facebook.com", "facebook"),
+    ("twitter.com", "twitter"),
+    ("x.com", "twitter"),
+    ("linkedin.com", "linkedin"),
+    ("instagram.com", "instagram"),
+    ("youtube.com", "youtube"),
+    ("tiktok.com", "tiktok"),
+    ("pinterest.com", "pinterest"),
+    ("github.com", "github"),
+    ("t.me", "telegram"),
+];
+
+/// Path fragments of share and intent links, which are not profiles
+const SOCIAL_SHARE_PATHS: &[&str] = &[
+    "/sharer",
+    "/share",
+    "/intent/",
+    "/dialog/",
+    "/plugins/",
+    "/shareArticle",
+];
+
+/// Minimum and maximum number of digits in a phone number
+const PHONE_DIGITS: std::ops::RangeInclusive<usize> = 9..=15;
+
+static EMAIL_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?i)\b[a-z0-9._%+-]+@[a-z0-9.-]+\.[a-z]{2,}\b").unwrap());
+static PHONE_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?:\+\d{1,3}[\s.-]?)?(?:\(\d{1,4}\)[\s.-]?|\b)\d{2,4}(?:[\s.-]\d{2,4}){1,4}\b")
+        .unwrap()
+});
+static DATE_LIKE_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^\d{4}\s*[-/.]\s*\d{1,4}(\s*[-/.]\s*\d{1,4})?$").unwrap());
+
+/// Contact details found on one page
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PageContacts {
+    /// Email addresses, lowercased
+    pub emails: BTreeSet<String>,
+
+    /// Phone numbers as digits with an optional leading `+`
+    pub phones: BTreeSet<String>,
+
+    /// Postal addresses as single-line text
+    pub addresses: BTreeSet<String>,
+
+    /// Social profile links with their network name
+    pub social_profiles: BTreeSet<(String, String)>,
+}
+
+/// A contact detail and the pages it was found on
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContactEntry {
+    /// The email address, phone number or postal address
+    pub value: String,
+
+    /// Pages the value was found on
+    pub source_pages: Vec<String>,
+}
+
+/// A social network profile linked from the site
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SocialProfile {
+    /// Name of the network, e.g. `linkedin`
+    pub network: String,
+
+    /// The profile URL
+    pub url: String,
+
+    /// Pages linking to the profile
+    pub source_pages: Vec<String>,
+}
+
+/// Contact details found across a site, without duplicates
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SiteContacts {
+    /// Email addresses from `mailto:` links and page text
+    pub emails: Vec<ContactEntry>,
+
+    /// Phone numbers from `tel:` links and page text
+    pub phones: Vec<ContactEntry>,
+
+    /// Postal addresses from `<address>` elements and schema.org markup
+    pub addresses: Vec<ContactEntry>,
+
+    /// Links to social network profiles
+    pub social_profiles: Vec<SocialProfile>,
+}
+
+/// Collects contact details from the pages of a site
+#[derive(Debug, Default)]
+pub struct ContactCollector {
+    /// Source pages per email address
+    emails: BTreeMap<String, BTreeSet<String>>,
+
+    /// Source pages per phone number
+    phones: BTreeMap<String, BTreeSet<String>>,
+
+    /// Source pages per postal address
+    addresses: BTreeMap<String, BTreeSet<String>>,
+
+    /// Source pages per social profile
+    social_profiles: BTreeMap<(String, String), BTreeSet<String>>,
+}
+
+impl ContactCollector {
+    /// Record the contact details found on a page
+    pub fn add(&mut self, page_url: &str, contacts: PageContacts) {
+        for (values, collected) in [
+            (contacts.emails, &mut self.emails),
+            (contacts.phones, &mut self.phones),
+            (contacts.addresses, &mut self.addresses),
+        ] {
+            for value in values {
+                collected
+                    .entry(value)
+                    .or_default()
+                    .insert(page_url.to_string());
+            }
+        }
+
+        for profile in contacts.social_profiles {
+            self.social_profiles
+                .entry(profile)
+                .or_default()
+                .insert(page_url.to_string());
+        }
+    }
+
+    /// Build the site-wide contact list
+    pub fn finish(&self) -> SiteContacts {
+        let entries = |collected: &BTreeMap<String, BTreeSet<String>>| {
+            collected
+                .iter()
+                .map(|(value, pages)| ContactEntry {
+                    value: value.clone(),
+                    source_pages: pages.iter().cloned().collect(),
+                })
+                .collect()
+        };
+
+        SiteContacts {
+            emails: entries(&self.emails),
+            phones: entries(&self.phones),
+            addresses: entries(&self.addresses),
+            social_profiles: self
+                .social_profiles
+                .iter()
+                .map(|((network, url), pages)| SocialProfile {
+                    network: network.clone(),
+                    url: url.clone(),
+                    source_pages: pages.iter().cloned().collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Extract contact details from a parsed page
+///
+/// `base_url` is used to resolve relative links; `text` is the page's visible text
+/// if already computed.
+pub fn extract_contacts(document: &Html, base_url: &str, text: Option<&str>) -> PageContacts {
+    let mut contacts = PageContacts::default();
+    let base = Url::parse(base_url).ok();
+
+    let links = Selector::parse("a[href]").unwrap();
+    for link in document.select(&links) {
+        let href = link.value().attr("href").unwrap_or("").trim();
+        let lower = href.to_ascii_lowercase();
+
+        if lower.starts_with("mailto:") {
+            let address = href[7..].split('?').next().unwrap_or("");
+            for email in percent_decode(address).split(',') {
+                if let Some(email) = normalize_email(email) {
+                    contacts.emails.insert(email);
+                }
+            }
+        } else if lower.starts_with("tel:") {
+            if let Some(phone) = normalize_phone(&percent_decode(&href[4..]), true) {
+                contacts.phones.insert(phone);
+            }
+        } else if let Some(url) = base.as_ref().and_then(|base| base.join(href).ok()) {
+            if let Some(network) = social_network(&url) {
+                contacts
+                    .social_profiles
+                    .insert((network.to_string(), url.to_string()));
+            }
+        }
+    }
+
+    let owned_text;
+    let text = match text {
+        Some(text) => text,
+        None => {
+            owned_text = visible_text(document);
+            &owned_text
+        }
+    };
+    for email in EMAIL_RE.find_iter(text) {
+        if let Some(email) = normalize_email(email.as_str()) {
+            contacts.emails.insert(email);
+        }
+    }
+    for phone in PHONE_RE.find_iter(text) {
+        if let Some(phone) = normalize_phone(phone.as_str(), false) {
+            contacts.phones.insert(phone);
+        }
+    }
+
+    let addresses = Selector::parse(r#"address, [itemtype*="PostalAddress"]"#).unwrap();
+    for element in document.select(&addresses) {
+        let address = element
+            .text()
+            .flat_map(|t| t.split_whitespace())
+            .collect::<Vec<_>>()
+            .join(" ");
+        if !address.is_empty() {
+            contacts.addresses.insert(address);
+        }
+    }
+
+    contacts
+}
+
+/// Lowercase an email address, rejecting file names like `logo@2x.png`
+fn normalize_email(email: &str) -> Option<String> {
+    let email = email.trim().to_lowercase();
+    let valid = EMAIL_RE
+        .find(&email)
+        .is_some_and(|m| m.start() == 0 && m.end() == email.len());
+    let is_file = [".png", ".jpg", ".jpeg", ".gif", ".svg", ".webp"]
+        .iter()
+        .any(|ext| email.ends_with(ext));
+    (valid && !is_file).then_some(email)
+}
+
+/// Reduce a phone number to digits with an optional leading `+`
+///
+/// Numbers from text must contain a separator or a leading `+`, and must not look
+/// like a date or year range, to avoid matching IDs and prices.
+fn normalize_phone(phone: &str, from_link: bool) -> Option<String> {
+    let phone = phone.trim();
+    let digits: String = phone.chars().filter(|c| c.is_ascii_digit()).collect();
+    if !PHONE_DIGITS.contains(&digits.len()) {
+        return None;
+    }
+
+    if !from_link {
+        let has_separator = phone.contains([' ', '-', '.', '(']);
+        if !(phone.starts_with('+') || has_separator) || DATE_LIKE_RE.is_match(phone) {
+            return None;
+        }
+    }
+
+    if phone.starts_with('+') {
+        Some(format!("+{}", digits))
+    } else {
+        Some(digits)
+    }
+}
+
+/// Name the social network a profile link points to, ignoring share links
+fn social_network(url: &Url) -> Option<&'static str> {
+    let host = url.host_str()?.to_lowercase();
+    let host = host.strip_prefix("www.").unwrap_or(&host);
+    let (_, network) = SOCIAL_NETWORKS
+        .iter()
+        .find(|(domain, _)| host == *domain || host.ends_with(&format!(".{}", domain)))?;
+
+    let path = url.path();
+    let is_share = SOCIAL_SHARE_PATHS.iter().any(|p| path.starts_with(p));
+    (path.len() > 1 && !is_share).then_some(*network)
+}
+
+/// Decode `%XX` escapes, leaving invalid ones as they are
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escape = if bytes[i] == b'%' {
+            value
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        } else {
+            None
+        };
+        match escape {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_contacts() {
+        let document = Html::parse_document(
+            r#"<body>
+            <a href="mailto:Sales@Example.com?subject=Hi">Email</a>
+            <a href="tel:+1-555-010-9999">Call</a>
+            <p>Write to support@example.com or call (555) 010-1234. Since 2019-2024. Order 123456789.</p>
+            <img src="logo@2x.png">
+            <address>1 Main St,<br> Springfield</address>
+            <a href="https://www.linkedin.com/company/example">LinkedIn</a>
+            <a href="https://www.facebook.com/sharer/sharer.php?u=x">Share</a>
+            <a href="https://x.com/example">X</a>
+            </body>"#,
+        );
+        let contacts = extract_contacts(&document, "https://example.com/contact", None);
+
+        assert_eq!(
+            contacts.emails.into_iter().collect::<Vec<_>>(),
+            vec!["sales@example.com", "support@example.com"]
+        );
+        assert_eq!(
+            contacts.phones.into_iter().collect::<Vec<_>>(),
+            vec!["+15550109999", "5550101234"]
+        );
+        assert_eq!(
+            contacts.addresses.into_iter().collect::<Vec<_>>(),
+            vec!["1 Main St, Springfield"]
+        );
+        assert_eq!(
+            contacts.social_profiles.into_iter().collect::<Vec<_>>(),
+            vec![
+                (
+                    "linkedin".to_string(),
+                    "https://www.linkedin.com/company/example".to_string()
+                ),
+                ("twitter".to_string(), "https://x.com/example".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_collector_dedups_with_sources() {
+        let mut collector = ContactCollector::default();
+        let page = |email: &str| PageContacts {
+            emails: BTreeSet::from([email.to_string()]),
+            ..Default::default()
+        };
+        collector.add("https://a.com/contact", page("info@a.com"));
+        collector.add("https://a.com/about", page("info@a.com"));
+
+        let contacts = collector.finish();
+        assert_eq!(contacts.emails.len(), 1);
+        assert_eq!(
+            contacts.emails[0].source_pages,
+            vec!["https://a.com/about", "https://a.com/contact"]
+        );
+    }
+}
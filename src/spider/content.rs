@@ -0,0 +1,259 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// Kind of document, used to pick a link extraction handler
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentKind {
+    /// HTML or XHTML page
+    Html,
+
+    /// RSS or Atom feed
+    Feed,
+
+    /// Any other XML document, e.g. a sitemap
+    Xml,
+
+    /// Plain text
+    PlainText,
+
+    /// Anything we cannot extract links from, e.g. PDFs and images
+    Other,
+}
+
+impl ContentKind {
+    /// Classify a document from its Content-Type header and the start of its body
+    ///
+    /// XML served with a generic content type is recognized as a feed when its root
+    /// element is `<rss>`, `<feed>` or `<rdf:RDF>`.
+    pub fn detect(content_type: &str, body: &[u8]) -> Self {
+        let mime = content_type
+            .split(';')
+            .next()
+            .unwrap_or("")
+            .trim()
+            .to_lowercase();
+
+        match mime.as_str() {
+            "text/html" | "application/xhtml+xml" => ContentKind::Html,
+            "application/rss+xml" | "application/atom+xml" | "application/rdf+xml" => {
+                ContentKind::Feed
+            }
+            "text/plain" => ContentKind::PlainText,
+            _ if mime == "text/xml" || mime == "application/xml" || mime.ends_with("+xml") => {
+                if looks_like_feed(body) {
+                    ContentKind::Feed
+                } else {
+                    ContentKind::Xml
+                }
+            }
+            _ => ContentKind::Other,
+        }
+    }
+
+    /// Short lowercase name of the kind
+    pub fn name(&self) -> &'static str {
+        match self {
+            ContentKind::Html => "html",
+            ContentKind::Feed => "feed",
+            ContentKind::Xml => "xml",
+            ContentKind::PlainText => "plain_text",
+            ContentKind::Other => "other",
+        }
+    }
+
+    /// Whether links can be extracted from this kind of document
+    pub fn has_links(&self) -> bool {
+        !matches!(self, ContentKind::Other)
+    }
+}
+
+/// Check whether an XML document's root element is a feed
+fn looks_like_feed(body: &[u8]) -> bool {
+    let head = String::from_utf8_lossy(&body[..body.len().min(1024)]).to_lowercase();
+    head.contains("<rss") || head.contains("<feed") || head.contains("<rdf:rdf")
+}
+
+static FEED_LINK_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?is)<link\b[^>]*>\s*(<!\[CDATA\[.*?\]\]>|[^<\s][^<]*?)\s*</link>").unwrap()
+});
+static HREF_ATTR_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(?i)\b(?:href|url|src|xlink:href)\s*=\s*["']([^"']+)["']"#).unwrap()
+});
+static LOC_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?is)<(?:loc|guid|id)\b[^>]*>\s*([^<]+?)\s*</(?:loc|guid|id)>").unwrap()
+});
+static TEXT_URL_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"https?://[^\s<>"'()\[\]{}]+"#).unwrap());
+
+/// Extract links from a non-HTML document
+///
+/// Returns the raw link values in document order, without duplicates. They may
+/// still be relative and need resolving against the document URL.
+pub fn extract_links(kind: ContentKind, text: &str) -> Vec<String> {
+    let links = match kind {
+        ContentKind::Feed => extract_feed_links(text),
+        ContentKind::Xml => extract_xml_links(text),
+        ContentKind::PlainText => extract_text_links(text),
+        ContentKind::Html | ContentKind::Other => Vec::new(),
+    };
+
+    let mut seen = std::collections::HashSet::new();
+    links
+        .into_iter()
+        .filter(|link| seen.insert(link.clone()))
+        .collect()
+}
+
+/// Extract item and channel links from an RSS or Atom feed
+pub fn extract_feed_links(xml: &str) -> Vec<String> {
+    let mut links: Vec<String> = FEED_LINK_RE
+        .captures_iter(xml)
+        .map(|c| unescape_xml(&c[1]))
+        .collect();
+
+    // Atom links and RSS enclosures keep the URL in an attribute
+    links.extend(HREF_ATTR_RE.captures_iter(xml).map(|c| unescape_xml(&c[1])));
+
+    links.retain(|link| is_link_like(link));
+    links
+}
+
+/// Extract links from a generic XML document such as a sitemap
+pub fn extract_xml_links(xml: &str) -> Vec<String> {
+    let mut links: Vec<String> = LOC_RE
+        .captures_iter(xml)
+        .map(|c| unescape_xml(&c[1]))
+        .filter(|link| is_absolute_http(link))
+        .collect();
+
+    links.extend(
+        HREF_ATTR_RE
+            .captures_iter(xml)
+            .map(|c| unescape_xml(&c[1]))
+            .filter(|link| is_link_like(link)),
+    );
+
+    links
+}
+
+/// Extract absolute http(s) URLs from plain text
+pub fn extract_text_links(text: &str) -> Vec<String> {
+    TEXT_URL_RE
+        .find_iter(text)
+        .map(|m| {
+            m.as_str()
+                .trim_end_matches(['.', ',', ';', ':', '!', '?'])
+                .to_string()
+        })
+        .collect()
+}
+
+/// Check whether a value looks like a URL rather than text or a namespace
+fn is_link_like(value: &str) -> bool {
+    !value.is_empty()
+        && !value.contains(char::is_whitespace)
+        && !value.starts_with('#')
+        && !value.starts_with("urn:")
+        && !value.starts_with("tag:")
+}
+
+/// Check whether a value is an absolute http(s) URL
+fn is_absolute_http(value: &str) -> bool {
+    value.starts_with("http://") || value.starts_with("https://")
+}
+
+/// Resolve the predefined XML entities and CDATA wrappers
+fn unescape_xml(value: &str) -> String {
+    let value = value
+        .trim()
+        .trim_start_matches("<![CDATA[")
+        .trim_end_matches("]]>")
+        .trim();
+
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_content_kind() {
+        assert_eq!(
+            ContentKind::detect("text/html; charset=utf-8", b""),
+            ContentKind::Html
+        );
+        assert_eq!(
+            ContentKind::detect("application/rss+xml", b""),
+            ContentKind::Feed
+        );
+        assert_eq!(
+            ContentKind::detect("text/xml", b"<?xml version=\"1.0\"?><rss version=\"2.0\">"),
+            ContentKind::Feed
+        );
+        assert_eq!(
+            ContentKind::detect("application/xml", b"<urlset>"),
+            ContentKind::Xml
+        );
+        assert_eq!(
+            ContentKind::detect("text/plain", b""),
+            ContentKind::PlainText
+        );
+        assert_eq!(
+            ContentKind::detect("application/pdf", b""),
+            ContentKind::Other
+        );
+    }
+
+    #[test]
+    fn test_extract_rss_links() {
+        let rss = r#"<rss><channel><link>https://example.com/</link>
+            <item><link><![CDATA[https://example.com/post?a=1&amp;b=2]]></link>
+            <enclosure url="https://example.com/ep.mp3" type="audio/mpeg"/></item></channel></rss>"#;
+        let links = extract_links(ContentKind::Feed, rss);
+        assert_eq!(
+            links,
+            vec![
+                "https://example.com/",
+                "https://example.com/post?a=1&b=2",
+                "https://example.com/ep.mp3",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extract_atom_links() {
+        let atom = r#"<feed xmlns="http://www.w3.org/2005/Atom"><id>urn:uuid:1</id>
+            <entry><link rel="alternate" href="/posts/1"/></entry></feed>"#;
+        let links = extract_links(ContentKind::Feed, atom);
+        assert_eq!(links, vec!["/posts/1"]);
+    }
+
+    #[test]
+    fn test_extract_sitemap_links() {
+        let sitemap = r#"<urlset><url><loc>https://example.com/a</loc></url>
+            <url><loc> https://example.com/b </loc></url></urlset>"#;
+        let links = extract_links(ContentKind::Xml, sitemap);
+        assert_eq!(
+            links,
+            vec!["https://example.com/a", "https://example.com/b"]
+        );
+    }
+
+    #[test]
+    fn test_extract_text_links() {
+        let text = "See https://example.com/docs. Or (http://example.com/faq)";
+        let links = extract_links(ContentKind::PlainText, text);
+        assert_eq!(
+            links,
+            vec!["https://example.com/docs", "http://example.com/faq"]
+        );
+    }
+}
@@ -1,36 +1,286 @@
+use crate::spider::charset::{decode_html, decode_text, DecodedHtml};
 use crate::spider::config::SpiderConfig;
+use crate::spider::contacts::{extract_contacts, ContactCollector, SiteContacts};
+use crate::spider::content::{extract_links, ContentKind};
 use crate::spider::error::SpiderError;
+use crate::spider::external::{
+    count_dead_links, group_external_links, validate_external_links, ExternalLink,
+};
+use crate::spider::graph::{LinkEdge, LinkGraph};
 use crate::spider::network::NetworkClient;
+use crate::spider::dedup::{
+    cluster_duplicates, hash_hex, visible_text, DuplicateCluster, DuplicateDetector,
+    DuplicateMatch, Fingerprint,
+};
+use crate::spider::sqlite::SqliteSink;
+use crate::spider::store::ContentStore;
+use crate::spider::strategy::{LinkCandidate, LinkLocation, UrlEntry, START_URL_SCORE};
+use crate::spider::structured::{
+    extract_structured_data, group_by_type, PageStructuredData, StructuredItem,
+};
+use crate::spider::templates::{TemplateReport, TemplateTracker};
+use crate::spider::traps::{TrapDetector, TrapKind};
+use crate::spider::text::{extract_page_text, PageText};
+use crate::spider::warc::WarcWriter;
 use crate::spider::utils::{
-    detect_massive_links_pattern, domain_to_filename, extract_base_domain, is_priority_url,
-    is_same_domain, normalize_url, resolve_url, should_skip_subdomain, should_skip_url,
+    domain_to_filename, extract_base_domain, evaluate_scope, is_in_domain_scope, normalize_url, resolve_url_with_encoding,
+    should_skip_subdomain, should_skip_url, ScopeAction, ScopeDecision,
 };
 
 use anyhow::Result;
+use encoding_rs::{Encoding, UTF_8};
 use futures::stream::{self, StreamExt};
 use futures::FutureExt;
 use log::{debug, info, warn};
-use scraper::{Html, Selector};
+use scraper::{ElementRef, Html, Selector};
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::fs::File;
 use std::sync::{Arc, Mutex};
 
-/// A URL with additional metadata
-#[derive(Debug, Clone, Eq, PartialEq)]
-struct UrlEntry {
-    /// The URL
-    url: String,
+/// Text and position of a link in its document
+#[derive(Default)]
+struct LinkContext<'a> {
+    /// The text of the link
+    anchor_text: Option<&'a str>,
 
-    /// The depth of this URL in the crawl
+    /// The `title` attribute of the link
+    title: Option<&'a str>,
+
+    /// Where on the page the link was found
+    location: LinkLocation,
+}
+
+/// The document a link was found in
+struct LinkSource<'a> {
+    /// URL of the document (after redirects)
+    url: &'a str,
+
+    /// The depth of the document in the crawl
     depth: usize,
 
-    /// Priority score for sorting
-    priority: usize,
+    /// Encoding used to percent-encode link query strings
+    encoding: &'static Encoding,
+
+    /// The kind of document
+    kind: ContentKind,
+}
+
+/// Mutable state shared by all URLs processed during a crawl
+#[derive(Default)]
+struct CrawlState {
+    /// URLs that have already been processed
+    visited_urls: Mutex<HashSet<String>>,
+
+    /// URLs waiting to be processed
+    queue: Mutex<VecDeque<UrlEntry>>,
+
+    /// URLs found so far
+    found_urls: Mutex<Vec<String>>,
+
+    /// Skipped URLs grouped by reason
+    skipped_urls: Mutex<HashMap<String, Vec<String>>>,
+
+    /// URL templates that reached the per-template cap
+    massive_link_patterns: Mutex<HashSet<String>>,
+
+    /// Detects crawler traps among newly discovered URLs
+    trap_detector: Mutex<TrapDetector>,
+
+    /// Applies template quotas and records URLs per template
+    templates: Mutex<TemplateTracker>,
+
+    /// Matches page content against the pages crawled so far
+    duplicates: Mutex<DuplicateDetector>,
+
+    /// Contact details found on the crawled pages
+    contacts: Mutex<ContactCollector>,
+
+    /// Structured data items grouped by schema type
+    structured_data: Mutex<BTreeMap<String, Vec<StructuredItem>>>,
+
+    /// Map of redirected URLs
+    redirects: Mutex<HashMap<String, String>>,
+
+    /// URLs that could not be fetched
+    unreachable_urls: Mutex<Vec<String>>,
+
+    /// Metadata of every fetched page
+    pages: Mutex<Vec<PageInfo>>,
+
+    /// Every link found, including external ones and links to visited pages
+    edges: Mutex<Vec<LinkEdge>>,
+
+    /// Store for the fetched bodies, if enabled
+    content_store: Option<ContentStore>,
+
+    /// The crawl's site in the SQLite sink, if enabled
+    sink: Option<SinkSite>,
+}
+
+/// A crawl's site in a SQLite sink
+///
+/// The site is marked failed if it is dropped before being finished, so a crawl
+/// that ends with an error, or is cancelled, is not left running.
+struct SinkSite {
+    /// The sink the site is written to
+    sink: Arc<SqliteSink>,
+
+    /// ID of the site in the sink
+    site_id: i64,
+
+    /// Whether the site was finished
+    finished: bool,
+}
+
+impl SinkSite {
+    /// Record the end of the crawl with its statistics
+    async fn finish(mut self, result: &CrawlResult) {
+        self.finished = true;
+        if let Err(e) = self.sink.finish_site(self.site_id, result).await {
+            warn!("Failed to write to SQLite: {}", e);
+        }
+    }
+}
+
+impl Drop for SinkSite {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.sink.fail_site(self.site_id);
+        }
+    }
+}
+
+impl CrawlState {
+    /// Create the state for a crawl with the given configuration
+    fn new(config: &SpiderConfig) -> Self {
+        Self {
+            trap_detector: Mutex::new(TrapDetector::new(
+                config.traps.clone(),
+                config.max_urls_per_template,
+            )),
+            templates: Mutex::new(TemplateTracker::new(config.template_quotas.clone())),
+            duplicates: Mutex::new(DuplicateDetector::new(config.near_duplicate_distance)),
+            ..Default::default()
+        }
+    }
+
+    /// Record a skipped URL under the given reason
+    fn skip(&self, reason: &str, url: &str) {
+        self.skipped_urls
+            .lock()
+            .unwrap()
+            .entry(reason.to_string())
+            .or_default()
+            .push(url.to_string());
+        self.write_sink(|sink, site_id| sink.add_skip(site_id, url, reason));
+    }
+
+    /// Record a URL skipped before it was fetched, so finding it again does not check it again
+    fn reject(&self, reason: &str, url: &str) {
+        self.visited_urls.lock().unwrap().insert(url.to_string());
+        self.skip(reason, url);
+    }
+
+    /// Record a fetched page
+    fn add_page(&self, page: PageInfo) {
+        self.write_sink(|sink, site_id| sink.add_page(site_id, &page));
+        self.pages.lock().unwrap().push(page);
+    }
+
+    /// Record a link
+    fn add_edge(&self, edge: LinkEdge) {
+        self.write_sink(|sink, site_id| sink.add_link(site_id, &edge));
+        self.edges.lock().unwrap().push(edge);
+    }
+
+    /// Record a redirect
+    fn add_redirect(&self, source: &str, target: &str) {
+        self.write_sink(|sink, site_id| sink.add_redirect(site_id, source, target));
+        self.redirects
+            .lock()
+            .unwrap()
+            .insert(source.to_string(), target.to_string());
+    }
+
+    /// Record a URL that could not be fetched
+    fn add_unreachable(&self, url: &str, status: Option<u16>, message: &str) {
+        self.write_sink(|sink, site_id| sink.add_error(site_id, url, status, message));
+        self.unreachable_urls.lock().unwrap().push(url.to_string());
+    }
+
+    /// Write to the SQLite sink, if enabled
+    fn write_sink(&self, write: impl FnOnce(&SqliteSink, i64)) {
+        if let Some(site) = &self.sink {
+            write(&site.sink, site.site_id);
+        }
+    }
+}
+
+/// Metadata about a fetched URL
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PageInfo {
+    /// The requested URL
+    pub url: String,
+
+    /// The URL after redirects
+    pub final_url: String,
+
+    /// The HTTP status code
+    pub status: u16,
+
+    /// The Content-Type header of the response
+    pub content_type: String,
+
+    /// Size of the body in bytes
+    pub content_length: usize,
+
+    /// Time taken to fetch the page in milliseconds
+    pub response_time_ms: u64,
+
+    /// The depth of this URL in the crawl
+    pub depth: usize,
+
+    /// The page this URL was found on
+    pub referrer: Option<String>,
+
+    /// Detected character encoding, for HTML pages
+    pub encoding: Option<String>,
+
+    /// Contents of the `<title>` element
+    pub title: Option<String>,
+
+    /// Contents of the meta description
+    pub meta_description: Option<String>,
+
+    /// Number of links found on the page
+    pub outgoing_links: usize,
+
+    /// Hash of the visible text, for HTML pages
+    pub content_hash: Option<String>,
+
+    /// SimHash of the visible text, for HTML pages
+    pub simhash: Option<String>,
+
+    /// The earlier page this one duplicates, if any
+    pub duplicate: Option<DuplicateMatch>,
+
+    /// Structured data found on the page, if enabled
+    pub structured_data: Option<PageStructuredData>,
+
+    /// Main content text, headings and language, if enabled
+    pub content: Option<PageText>,
+
+    /// The Last-Modified header of the response
+    pub last_modified: Option<String>,
+
+    /// Whether the page asks not to be indexed, by header or robots meta tag
+    #[serde(default)]
+    pub noindex: bool,
 }
 
 /// Spider crawl result
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct CrawlResult {
     /// The original URL
     pub base_url: String,
@@ -44,7 +294,7 @@ pub struct CrawlResult {
     /// Map of skipped URLs grouped by reason
     pub skipped_urls: HashMap<String, Vec<String>>,
 
-    /// List of patterns detected for massive link sets
+    /// URL templates that reached the per-template cap
     pub massive_link_patterns: Vec<String>,
 
     /// Map of redirected URLs
@@ -56,10 +306,100 @@ pub struct CrawlResult {
     /// URLs remaining in the queue
     pub remaining_queue: Vec<String>,
 
+    /// Metadata of every fetched URL, including non-HTML documents
+    #[serde(default)]
+    pub pages: Vec<PageInfo>,
+
+    /// Link graph between the crawled pages
+    #[serde(default)]
+    pub link_graph: LinkGraph,
+
+    /// External links grouped by domain
+    #[serde(default)]
+    pub external_links: BTreeMap<String, Vec<ExternalLink>>,
+
+    /// Learned URL templates with counts and examples, most common first
+    #[serde(default)]
+    pub url_templates: Vec<TemplateReport>,
+
+    /// Pages with the same or nearly the same content
+    #[serde(default)]
+    pub duplicate_clusters: Vec<DuplicateCluster>,
+
+    /// Contact details found on the site, if enabled
+    #[serde(default)]
+    pub contacts: SiteContacts,
+
+    /// References to the pages' JSON-LD, microdata and RDFa items by schema type, if enabled
+    #[serde(default)]
+    pub structured_data: BTreeMap<String, Vec<StructuredItem>>,
+
+    /// WARC files holding every fetch of the crawl, if enabled
+    #[serde(default)]
+    pub warc_files: Vec<String>,
+
+    /// CDX index of the WARC files, if enabled
+    #[serde(default)]
+    pub warc_cdx: Option<String>,
+
+    /// Manifest of the content store, if enabled
+    #[serde(default)]
+    pub content_manifest: Option<String>,
+
+    /// Labels from the config, such as the tags of a batch input row
+    #[serde(default)]
+    pub tags: Vec<String>,
+
     /// Stats about the crawl
     pub stats: HashMap<String, usize>,
 }
 
+impl CrawlResult {
+    /// Whether any page returned a 2xx status
+    pub fn has_successful_page(&self) -> bool {
+        self.pages
+            .iter()
+            .any(|page| (200..300).contains(&page.status))
+    }
+
+    /// Look up the data of an item listed in `structured_data`
+    pub fn structured_item(&self, item: &StructuredItem) -> Option<&serde_json::Value> {
+        self.pages
+            .iter()
+            .filter(|page| page.final_url == item.page)
+            .find_map(|page| page.structured_data.as_ref()?.item(&item.format, item.index))
+    }
+}
+
+#[cfg(test)]
+impl CrawlResult {
+    /// A crawl of `https://a.com/` with the given pages, shared by tests
+    pub(crate) fn fixture(pages: Vec<PageInfo>) -> Self {
+        Self {
+            base_url: "https://a.com/".to_string(),
+            base_domain: "a.com".to_string(),
+            pages,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+impl PageInfo {
+    /// An HTML page fetched without a redirect, shared by tests
+    pub(crate) fn fixture(url: &str, status: u16) -> Self {
+        Self {
+            url: url.to_string(),
+            final_url: url.to_string(),
+            status,
+            content_type: "text/html".to_string(),
+            content_length: 10,
+            response_time_ms: 5,
+            ..Default::default()
+        }
+    }
+}
+
 /// Spider for crawling websites
 pub struct Spider {
     /// Spider configuration
@@ -67,6 +407,9 @@ pub struct Spider {
 
     /// Network client for making requests
     network: NetworkClient,
+
+    /// Shared SQLite sink, used instead of opening `config.sqlite`
+    sink: Option<Arc<SqliteSink>>,
 }
 
 impl Spider {
@@ -78,11 +421,23 @@ impl Spider {
         Self { 
             config,
             network,
+            sink: None,
         }
     }
+
+    /// Write results to an already open SQLite sink, such as one shared by a batch
+    ///
+    /// The sink replaces `config.sqlite`, which is then not opened.
+    pub fn with_sqlite(mut self, sink: Arc<SqliteSink>) -> Self {
+        self.sink = Some(sink);
+        self
+    }
     
 
     /// Crawl a website starting from the given URL
+    ///
+    /// Fails when not a single page returned a success status, after the
+    /// result file was written, so the failure can be inspected.
     pub async fn crawl(&self, start_url: &str) -> Result<CrawlResult> {
         // Extract base domain from start URL
         let base_domain = extract_base_domain(start_url)?;
@@ -93,33 +448,70 @@ impl Spider {
         info!("  max_depth: {}", self.config.max_depth);
         info!("  max_loops: {}", self.config.max_loops);
         info!("  max_concurrent: {}", self.config.max_concurrent);
-        info!("  pattern_threshold: {}", self.config.pattern_threshold);
+        info!("  max_urls_per_template: {}", self.config.max_urls_per_template);
         info!("  skip_patterns: {:?}", self.config.skip_patterns);
         info!(
             "  skip_subdomain_patterns: {:?}",
             self.config.skip_subdomain_patterns
         );
         info!("  priority_paths: {:?}", self.config.priority_paths);
+        info!("  domain_scope: {:?}", self.config.domain_scope);
+        info!("  allowed_hosts: {:?}", self.config.allowed_hosts);
+        info!(
+            "  scope_rules: {:?} (default: {:?})",
+            self.config
+                .scope_rules
+                .iter()
+                .map(|rule| rule.name.as_str())
+                .collect::<Vec<_>>(),
+            self.config.scope_default
+        );
 
         info!(
             "Starting crawl of {} (base domain: {})",
             normalized_start_url, base_domain
         );
 
+        if let Some(warc) = &self.config.warc {
+            let writer = WarcWriter::new(warc.clone(), &base_domain)?;
+            self.network.start_warc(writer).await;
+        }
+
         // Initialize shared state
-        let visited_urls = Arc::new(Mutex::new(HashSet::new()));
-        let queue = Arc::new(Mutex::new(VecDeque::new()));
-        let found_urls = Arc::new(Mutex::new(Vec::new()));
-        let skipped_urls = Arc::new(Mutex::new(HashMap::<String, Vec<String>>::new()));
-        let massive_link_patterns = Arc::new(Mutex::new(HashSet::new()));
-        let redirects = Arc::new(Mutex::new(HashMap::new()));
-        let unreachable_urls = Arc::new(Mutex::new(Vec::new()));
+        let mut state = CrawlState::new(&self.config);
+        if let Some(store) = &self.config.content_store {
+            state.content_store = Some(ContentStore::new(store, &base_domain)?);
+        }
+        let sink = match (&self.sink, &self.config.sqlite) {
+            (Some(sink), _) => Some(sink.clone()),
+            (None, Some(path)) => Some(Arc::new(SqliteSink::open(path)?)),
+            (None, None) => None,
+        };
+        if let Some(sink) = sink {
+            let site_id = sink.start_site(&normalized_start_url, &base_domain).await?;
+            state.sink = Some(SinkSite {
+                sink,
+                site_id,
+                finished: false,
+            });
+        }
+        state
+            .trap_detector
+            .lock()
+            .unwrap()
+            .check(&normalized_start_url);
+        state
+            .templates
+            .lock()
+            .unwrap()
+            .admit(&normalized_start_url);
 
         // Add start URL to queue
-        queue.lock().unwrap().push_back(UrlEntry {
+        state.queue.lock().unwrap().push_back(UrlEntry {
             url: normalized_start_url.clone(),
             depth: 0,
-            priority: 100, // Start URL gets top priority
+            score: START_URL_SCORE,
+            referrer: None,
         });
 
         // Process URLs from the queue until empty or max_loops reached
@@ -130,88 +522,29 @@ impl Spider {
             loop_count += 1;
             // Get next batch of URLs to process
             let batch = {
-                let mut queue_lock = queue.lock().unwrap();
+                let mut queue_lock = state.queue.lock().unwrap();
 
                 if queue_lock.is_empty() {
                     info!("Queue is empty, crawl complete");
                     break;
                 }
 
-                // Sort queue by priority (higher is better)
-                let mut entries: Vec<_> = queue_lock.drain(..).collect();
-                entries.sort_by(|a, b| b.priority.cmp(&a.priority));
-
-                // Take up to max_concurrent URLs
-                let batch_size = std::cmp::min(self.config.max_concurrent, entries.len());
-                let batch: Vec<_> = entries.drain(..batch_size).collect();
-
-                // Put remaining entries back in queue
-                queue_lock.extend(entries);
-
-                batch
+                // Take up to max_concurrent URLs in the order of the crawl strategy
+                self.config
+                    .strategy
+                    .take_batch(&mut queue_lock, self.config.max_concurrent, loop_count - 1)
             };
 
-            // Detect massive link patterns
-            if let Some(pattern) = detect_massive_links_pattern(
-                &batch.iter().map(|e| e.url.clone()).collect::<Vec<_>>(),
-                self.config.pattern_threshold,
-            ) {
-                info!("Detected massive link pattern: {}", pattern);
-                massive_link_patterns.lock().unwrap().insert(pattern);
-            }
-
             // Get the batch length here before we move it
             let batch_len = batch.len();
 
             // Process batch in parallel
+            let state = &state;
+            let base_domain = &base_domain;
             let futures = batch.into_iter().map(|entry| {
                 // Skip URLs that exceed max depth
                 if entry.depth >= self.config.max_depth {
-                    {
-                        let mut skipped = skipped_urls.lock().unwrap();
-                        let reason = "max_depth_exceeded".to_string();
-                        skipped
-                            .entry(reason)
-                            .or_default()
-                            .push(entry.url.clone());
-                    }
-                    return futures::future::ready(()).boxed();
-                }
-
-                // Skip URLs that match massive link patterns
-                let patterns = massive_link_patterns.lock().unwrap().clone();
-                let matches_pattern = patterns.iter().any(|pattern| {
-                    let pattern_parts: Vec<&str> = pattern.split('*').collect();
-                    if pattern_parts.len() == 2 {
-                        entry.url.starts_with(pattern_parts[0])
-                            && entry.url.ends_with(pattern_parts[1])
-                    } else {
-                        false
-                    }
-                });
-
-                if matches_pattern {
-                    {
-                        let mut skipped = skipped_urls.lock().unwrap();
-                        let reason = "massive_link_pattern".to_string();
-                        skipped
-                            .entry(reason)
-                            .or_default()
-                            .push(entry.url.clone());
-                    }
-                    return futures::future::ready(()).boxed();
-                }
-
-                // Skip URLs that match skip patterns
-                if should_skip_url(&entry.url, &self.config.skip_patterns) {
-                    {
-                        let mut skipped = skipped_urls.lock().unwrap();
-                        let reason = "skip_pattern".to_string();
-                        skipped
-                            .entry(reason)
-                            .or_default()
-                            .push(entry.url.clone());
-                    }
+                    state.reject("max_depth_exceeded", &entry.url);
                     return futures::future::ready(()).boxed();
                 }
 
@@ -221,14 +554,7 @@ impl Spider {
                 match skip_subdomain_result {
                     Ok(should_skip) => {
                         if should_skip {
-                            {
-                                let mut skipped = skipped_urls.lock().unwrap();
-                                let reason = "subdomain_pattern".to_string();
-                                skipped
-                                    .entry(reason)
-                                    .or_default()
-                                    .push(entry.url.clone());
-                            }
+                            state.reject("subdomain_pattern", &entry.url);
                             return futures::future::ready(()).boxed();
                         }
                     }
@@ -239,31 +565,8 @@ impl Spider {
                 }
 
                 // Process URL
-                let url = entry.url.clone();
-                let depth = entry.depth;
-
-                // Clone all the shared state we need
-                let visited_urls_clone = visited_urls.clone();
-                let queue_clone = queue.clone();
-                let found_urls_clone = found_urls.clone();
-                let base_domain_clone = base_domain.clone();
-                let redirects_clone = redirects.clone();
-                let unreachable_urls_clone = unreachable_urls.clone();
-                let priority_paths_clone = self.config.priority_paths.clone();
-
                 async move {
-                    self.process_url(
-                        &url,
-                        depth,
-                        visited_urls_clone,
-                        queue_clone,
-                        found_urls_clone,
-                        &base_domain_clone,
-                        redirects_clone,
-                        unreachable_urls_clone,
-                        &priority_paths_clone,
-                    )
-                    .await;
+                    self.process_url(&entry, state, base_domain).await;
                 }
                 .boxed()
             });
@@ -277,13 +580,19 @@ impl Spider {
             processed_urls_count += batch_len;
 
             // Collect detailed statistics
-            let queue_len = queue.lock().unwrap().len();
-            let visited_count = visited_urls.lock().unwrap().len();
-            let found_count = found_urls.lock().unwrap().len();
-            let skipped_count: usize = skipped_urls.lock().unwrap().values().map(|v| v.len()).sum();
-            let patterns_count = massive_link_patterns.lock().unwrap().len();
-            let redirects_count = redirects.lock().unwrap().len();
-            let unreachable_count = unreachable_urls.lock().unwrap().len();
+            let queue_len = state.queue.lock().unwrap().len();
+            let visited_count = state.visited_urls.lock().unwrap().len();
+            let found_count = state.found_urls.lock().unwrap().len();
+            let skipped_count: usize = state
+                .skipped_urls
+                .lock()
+                .unwrap()
+                .values()
+                .map(|v| v.len())
+                .sum();
+            let patterns_count = state.massive_link_patterns.lock().unwrap().len();
+            let redirects_count = state.redirects.lock().unwrap().len();
+            let unreachable_count = state.unreachable_urls.lock().unwrap().len();
 
             info!("--- Loop stats for {} (loop #{}) ---", base_domain, loop_count);
             info!(
@@ -300,19 +609,47 @@ impl Spider {
         }
 
         // Collect results
-        let mut urls = found_urls.lock().unwrap().clone();
+        let mut urls = state.found_urls.lock().unwrap().clone();
         urls.sort();
         urls.dedup();
 
-        let skipped = skipped_urls.lock().unwrap().clone();
-        let patterns = massive_link_patterns
+        let skipped = state.skipped_urls.lock().unwrap().clone();
+        let patterns = state
+            .massive_link_patterns
             .lock()
             .unwrap()
             .iter()
             .cloned()
             .collect();
-        let redirect_map = redirects.lock().unwrap().clone();
-        let unreachable = unreachable_urls.lock().unwrap().clone();
+        let redirect_map = state.redirects.lock().unwrap().clone();
+        let unreachable = state.unreachable_urls.lock().unwrap().clone();
+        let mut pages = state.pages.lock().unwrap().clone();
+        pages.sort_by(|a, b| a.url.cmp(&b.url));
+        let duplicate_clusters = cluster_duplicates(pages.iter().filter_map(|page| {
+            page.duplicate
+                .as_ref()
+                .map(|duplicate| (page.final_url.as_str(), duplicate))
+        }));
+        let contacts = state.contacts.lock().unwrap().finish();
+        let structured_data = std::mem::take(&mut *state.structured_data.lock().unwrap());
+        let url_templates = {
+            let fetched: HashSet<&str> = pages.iter().map(|page| page.url.as_str()).collect();
+            state.templates.lock().unwrap().report(&fetched)
+        };
+        let edges = std::mem::take(&mut *state.edges.lock().unwrap());
+        let link_graph = LinkGraph::new(edges, &normalized_start_url, &redirect_map);
+
+        let mut external_links = group_external_links(&link_graph.edges);
+        if self.config.validate_external_links {
+            validate_external_links(
+                &self.network,
+                &mut external_links,
+                self.config.max_concurrent,
+            )
+            .await;
+        }
+
+        let warc = self.network.finish_warc().await?.unwrap_or_default();
 
         // Create result
         let result = CrawlResult {
@@ -324,6 +661,17 @@ impl Spider {
             redirects: redirect_map,
             unreachable_urls: unreachable,
             remaining_queue: Vec::new(), // Will be populated later
+            pages,
+            link_graph,
+            external_links,
+            url_templates,
+            duplicate_clusters,
+            contacts,
+            structured_data,
+            warc_files: warc.files,
+            warc_cdx: warc.cdx_file,
+            content_manifest: state.content_store.as_ref().map(|store| store.manifest_path()),
+            tags: self.config.tags.clone(),
             stats: HashMap::new(),       // Will be populated later
         };
 
@@ -333,7 +681,7 @@ impl Spider {
         stats.insert("processed_urls".to_string(), processed_urls_count);
         stats.insert(
             "visited_urls".to_string(),
-            visited_urls.lock().unwrap().len(),
+            state.visited_urls.lock().unwrap().len(),
         );
         stats.insert("found_urls".to_string(), result.urls.len());
         stats.insert(
@@ -349,9 +697,58 @@ impl Spider {
             "patterns_detected".to_string(),
             result.massive_link_patterns.len(),
         );
+        stats.insert("pages".to_string(), result.pages.len());
+        stats.insert("links".to_string(), result.link_graph.edges.len());
+        stats.insert(
+            "external_domains".to_string(),
+            result.external_links.len(),
+        );
+        stats.insert(
+            "external_links".to_string(),
+            result.external_links.values().map(|v| v.len()).sum(),
+        );
+        stats.insert(
+            "dead_external_links".to_string(),
+            count_dead_links(&result.external_links),
+        );
+        stats.insert(
+            "non_html_resources".to_string(),
+            result
+                .pages
+                .iter()
+                .filter(|page| (200..300).contains(&page.status))
+                .filter(|page| ContentKind::detect(&page.content_type, &[]) != ContentKind::Html)
+                .count(),
+        );
+        stats.insert("url_templates".to_string(), result.url_templates.len());
+        stats.insert(
+            "duplicate_pages".to_string(),
+            result
+                .duplicate_clusters
+                .iter()
+                .map(|cluster| cluster.duplicates.len())
+                .sum(),
+        );
+        stats.insert("emails".to_string(), result.contacts.emails.len());
+        stats.insert("phones".to_string(), result.contacts.phones.len());
+        stats.insert(
+            "words".to_string(),
+            result
+                .pages
+                .iter()
+                .filter_map(|page| page.content.as_ref())
+                .map(|content| content.word_count)
+                .sum(),
+        );
+        stats.insert(
+            "structured_data_items".to_string(),
+            result.structured_data.values().map(|items| items.len()).sum(),
+        );
+        stats.insert("warc_files".to_string(), result.warc_files.len());
 
         // Get remaining URLs in the queue
-        let remaining_urls: Vec<String> = queue
+        let remaining_urls: Vec<String> = state
+            .queue
             .lock()
             .unwrap()
             .iter()
@@ -366,6 +763,18 @@ impl Spider {
         // Save the updated result
         self.save_result(&result_with_queue)?;
 
+        // Dropping the state without finishing marks the site failed in the SQLite sink
+        if !result_with_queue.has_successful_page() {
+            return Err(SpiderError::NetworkError(format!(
+                "no page of {} returned a success status",
+                result_with_queue.base_url
+            ))
+            .into());
+        }
+        if let Some(site) = state.sink.take() {
+            site.finish(&result_with_queue).await;
+        }
+
         // Print final statistics (but not the queue contents)
         info!("=== Final crawl statistics ===");
         info!("  Base URL: {}", result_with_queue.base_url);
@@ -392,21 +801,13 @@ impl Spider {
     }
 
     /// Process a single URL
-    async fn process_url(
-        &self,
-        url: &str,
-        depth: usize,
-        visited_urls: Arc<Mutex<HashSet<String>>>,
-        queue: Arc<Mutex<VecDeque<UrlEntry>>>,
-        found_urls: Arc<Mutex<Vec<String>>>,
-        base_domain: &str,
-        redirects: Arc<Mutex<HashMap<String, String>>>,
-        unreachable_urls: Arc<Mutex<Vec<String>>>,
-        priority_paths: &[String],
-    ) {
+    async fn process_url(&self, entry: &UrlEntry, state: &CrawlState, base_domain: &str) {
+        let url = entry.url.as_str();
+        let depth = entry.depth;
+
         // Mark URL as visited
         {
-            let mut visited = visited_urls.lock().unwrap();
+            let mut visited = state.visited_urls.lock().unwrap();
             if visited.contains(url) {
                 debug!("Already visited {}", url);
                 return;
@@ -414,53 +815,132 @@ impl Spider {
             visited.insert(url.to_string());
         }
 
+        // Respect robots.txt when identifying as a bot
+        if !self.network.is_allowed_by_robots(url).await {
+            debug!("Disallowed by robots.txt: {}", url);
+            state.skip("robots_txt", url);
+            return;
+        }
+
         // Add URL to found_urls
         {
-            let mut found = found_urls.lock().unwrap();
+            let mut found = state.found_urls.lock().unwrap();
             found.push(url.to_string());
         }
 
         // Fetch the URL using our network client
-        let response = match self.network.fetch(url).await {
-            Ok(response) => response,
+        let fetched = match self.network.fetch_page(url, entry.referrer.as_deref()).await {
+            Ok(fetched) => fetched,
             Err(e) => {
                 warn!("Failed to fetch {}: {}", url, e);
-                
-                // Add to unreachable_urls
-                {
-                    let mut unreachable = unreachable_urls.lock().unwrap();
-                    unreachable.push(url.to_string());
-                }
-                
+                state.add_unreachable(url, None, &e.to_string());
                 return;
             }
         };
 
-        // Check for redirects
-        if response.url().as_str() != url {
-            // Add to redirects map
-            {
-                let mut redirect_map = redirects.lock().unwrap();
-                redirect_map.insert(url.to_string(), response.url().to_string());
+        if let Some(store) = &state.content_store {
+            if let Err(e) = store.save(url, &fetched).await {
+                warn!("Failed to store body of {}: {}", url, e);
             }
         }
 
+        // Check for redirects
+        if fetched.final_url != url {
+            state.add_redirect(url, &fetched.final_url);
+        }
+
+        let mut page = PageInfo {
+            url: url.to_string(),
+            final_url: fetched.final_url.clone(),
+            status: fetched.status,
+            content_type: fetched.content_type.clone(),
+            content_length: fetched.content_length,
+            response_time_ms: fetched.response_time.as_millis() as u64,
+            depth,
+            referrer: entry.referrer.clone(),
+            encoding: None,
+            title: None,
+            meta_description: None,
+            outgoing_links: 0,
+            content_hash: None,
+            simhash: None,
+            duplicate: None,
+            structured_data: None,
+            content: None,
+            last_modified: fetched
+                .headers
+                .get(reqwest::header::LAST_MODIFIED)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.trim().to_string()),
+            noindex: fetched
+                .headers
+                .get_all("x-robots-tag")
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .any(has_noindex),
+        };
+
+        // Error statuses are recorded but not parsed
+        if !(200..300).contains(&fetched.status) {
+            warn!("HTTP error status: {} for {}", fetched.status, url);
+            let message = format!("HTTP error status: {}", fetched.status);
+            state.add_unreachable(url, Some(fetched.status), &message);
+            state.add_page(page);
+            return;
+        }
+
         // Normalized current URL (after redirects)
-        let current_url = response.url().as_str().to_string();
+        let current_url = fetched.final_url.clone();
 
-        // Extract HTML content
-        let html = match self.network.extract_html(response).await {
-            Ok(html) => html,
-            Err(e) => {
-                // If we got a content type error, it's likely not HTML
-                if let SpiderError::ContentType(_) = e {
-                    debug!("Skipping non-HTML content: {}", url);
-                } else {
-                    warn!("Failed to get HTML from {}: {}", url, e);
-                }
-                return;
-            }
+        // Dispatch on the kind of document
+        let kind = ContentKind::detect(&fetched.content_type, &fetched.bytes);
+
+        if kind == ContentKind::Html {
+            let decoded = decode_html(&fetched.bytes, &fetched.content_type);
+            self.process_html(&mut page, decoded, state, base_domain);
+            state.add_page(page);
+            return;
+        }
+
+        // Non-HTML documents: extract links where we can
+        let links = if kind.has_links() {
+            let text = decode_text(&fetched.bytes, &fetched.content_type);
+            extract_links(kind, &text)
+        } else {
+            debug!("No link handler for {} ({})", url, fetched.content_type);
+            Vec::new()
         };
+        debug!("Found {} links in {:?} document {}", links.len(), kind, url);
+
+        let source = LinkSource {
+            url: &current_url,
+            depth,
+            encoding: UTF_8,
+            kind,
+        };
+        for link in &links {
+            self.enqueue_link(&source, link, &LinkContext::default(), state, base_domain);
+        }
+
+        page.outgoing_links = links.len();
+        state.add_page(page);
+    }
+
+    /// Extract metadata from an HTML page and enqueue its links
+    fn process_html(
+        &self,
+        page: &mut PageInfo,
+        decoded: DecodedHtml,
+        state: &CrawlState,
+        base_domain: &str,
+    ) {
+        let url = page.url.as_str();
+        let current_url = page.final_url.as_str();
+        let depth = page.depth;
+        let html = decoded.html;
+        let encoding = decoded.encoding;
+        debug!("Decoded {} as {}", current_url, encoding.name());
+        page.encoding = Some(encoding.name().to_string());
 
         let document = Html::parse_document(&html);
         let selector = Selector::parse("a[href]").unwrap();
@@ -490,75 +970,220 @@ impl Spider {
             debug!("Page stats: {}", self.network.get_html_stats(&html));
         }
 
-        for element in document.select(&selector) {
-            if let Some(href) = element.value().attr("href") {
-                // Skip empty links, anchors, javascript, and mailto
-                if href.is_empty()
-                    || href.starts_with('#')
-                    || href.starts_with("javascript:")
-                    || href.starts_with("mailto:")
-                {
-                    debug!("Skipping link: {}", href);
-                    continue;
+        let text = visible_text(&document);
+
+        if self.config.extract_contacts {
+            let contacts = extract_contacts(&document, current_url, Some(&text));
+            state.contacts.lock().unwrap().add(current_url, contacts);
+        }
+
+        if self.config.extract_text {
+            page.content = Some(extract_page_text(&document, self.config.max_text_bytes));
+        }
+
+        if self.config.extract_structured_data {
+            let data = extract_structured_data(&document);
+            if !data.is_empty() {
+                let mut groups = state.structured_data.lock().unwrap();
+                group_by_type(current_url, &data, &mut groups);
+                page.structured_data = Some(data);
+            }
+        }
+
+        // Fingerprint the visible text to find duplicate content
+        if let Some(fingerprint) = Fingerprint::of(&text) {
+            page.content_hash = Some(hash_hex(fingerprint.content_hash));
+            page.simhash = Some(hash_hex(fingerprint.simhash));
+            page.duplicate = state
+                .duplicates
+                .lock()
+                .unwrap()
+                .check(current_url, &fingerprint);
+        }
+
+        if let Some(duplicate) = &page.duplicate {
+            debug!(
+                "{} duplicates {} (distance {})",
+                current_url, duplicate.of, duplicate.distance
+            );
+        }
+
+        if page.duplicate.is_none() || !self.config.skip_duplicate_links {
+            let source = LinkSource {
+                url: current_url,
+                depth,
+                encoding,
+                kind: ContentKind::Html,
+            };
+            for element in document.select(&selector) {
+                if let Some(href) = element.value().attr("href") {
+                    let anchor_text = collapse_whitespace(&element.text().collect::<String>());
+                    let context = LinkContext {
+                        anchor_text: Some(&anchor_text),
+                        title: element.value().attr("title"),
+                        location: link_location(&element),
+                    };
+                    self.enqueue_link(&source, href, &context, state, base_domain);
                 }
+            }
+        }
 
-                // Resolve relative URLs
-                let absolute_url = match resolve_url(&current_url, href) {
-                    Ok(url) => url,
-                    Err(e) => {
-                        debug!("Failed to resolve URL {}: {}", href, e);
-                        continue;
-                    }
-                };
-
-                // Make sure URL is in the same domain
-                match is_same_domain(&absolute_url, base_domain) {
-                    Ok(same_domain) => {
-                        if !same_domain {
-                            debug!("Skipping external URL: {}", absolute_url);
-                            continue;
-                        }
-                    }
-                    Err(e) => {
-                        debug!("Failed to check domain for {}: {}", absolute_url, e);
-                        continue;
-                    }
+        page.title = extract_title(&document);
+        page.meta_description = extract_meta_description(&document);
+        page.noindex |= extract_meta_noindex(&document);
+        page.outgoing_links = link_count;
+    }
+
+    /// Resolve a link, record it in the link graph and add it to the queue if it should be crawled
+    fn enqueue_link(
+        &self,
+        source: &LinkSource,
+        href: &str,
+        context: &LinkContext,
+        state: &CrawlState,
+        base_domain: &str,
+    ) {
+        let current_url = source.url;
+        let depth = source.depth;
+
+        // Skip empty links, anchors, javascript, and mailto
+        if href.is_empty()
+            || href.starts_with('#')
+            || href.starts_with("javascript:")
+            || href.starts_with("mailto:")
+        {
+            debug!("Skipping link: {}", href);
+            return;
+        }
+
+        // Resolve relative URLs
+        let absolute_url = match resolve_url_with_encoding(current_url, href, source.encoding) {
+            Ok(url) => url,
+            Err(e) => {
+                debug!("Failed to resolve URL {}: {}", href, e);
+                return;
+            }
+        };
+
+        if !absolute_url.starts_with("http://") && !absolute_url.starts_with("https://") {
+            debug!("Skipping non-HTTP link: {}", absolute_url);
+            return;
+        }
+
+        // Make sure URL is in the domain scope of the crawl
+        let same_domain = match is_in_domain_scope(
+            &absolute_url,
+            base_domain,
+            self.config.domain_scope,
+            &self.config.allowed_hosts,
+        ) {
+            Ok(same_domain) => same_domain,
+            Err(e) => {
+                debug!("Failed to check domain for {}: {}", absolute_url, e);
+                return;
+            }
+        };
+
+        state.add_edge(LinkEdge {
+            source: current_url.to_string(),
+            target: absolute_url.clone(),
+            internal: same_domain,
+            source_kind: source.kind,
+        });
+
+        if !same_domain {
+            debug!("Skipping external URL: {}", absolute_url);
+            return;
+        }
+
+        // Check if URL is already visited or in queue
+        let should_add = {
+            let visited = state.visited_urls.lock().unwrap();
+            if visited.contains(&absolute_url) {
+                debug!("Already visited {}", absolute_url);
+                false
+            } else {
+                // Also check if the URL is already in the queue
+                let q = state.queue.lock().unwrap();
+                let already_in_queue = q.iter().any(|entry| entry.url == absolute_url);
+                if already_in_queue {
+                    debug!("Already in queue {}", absolute_url);
+                    false
+                } else {
+                    true
                 }
+            }
+        };
 
-                // Check if URL is already visited or in queue
-                let should_add = {
-                    let visited = visited_urls.lock().unwrap();
-                    if visited.contains(&absolute_url) {
-                        debug!("Already visited {}", absolute_url);
-                        false
-                    } else {
-                        // Also check if the URL is already in the queue
-                        let q = queue.lock().unwrap();
-                        let already_in_queue = q.iter().any(|entry| entry.url == absolute_url);
-                        if already_in_queue {
-                            debug!("Already in queue {}", absolute_url);
-                            false
-                        } else {
-                            true
-                        }
-                    }
-                };
-
-                if should_add {
-                    // Calculate priority: priority paths get higher value
-                    let is_priority = is_priority_url(&absolute_url, priority_paths);
-                    let priority = if is_priority { 50 } else { 10 };
-
-                    // Add URL to queue
-                    {
-                        let mut q = queue.lock().unwrap();
-                        q.push_back(UrlEntry {
-                            url: absolute_url,
-                            depth: depth + 1,
-                            priority,
-                        });
+        if should_add {
+            // Apply scope rules, then skip patterns unless a rule explicitly included the URL
+            let scope = evaluate_scope(
+                &absolute_url,
+                &self.config.scope_rules,
+                self.config.scope_default,
+            )
+            .unwrap_or(ScopeDecision::Default(ScopeAction::Include));
+            match &scope {
+                ScopeDecision::Excluded(rule) => {
+                    state.reject(&format!("scope:{}", rule), &absolute_url);
+                    return;
+                }
+                ScopeDecision::Default(ScopeAction::Exclude) => {
+                    state.reject("scope:default", &absolute_url);
+                    return;
+                }
+                _ => {}
+            }
+            let explicitly_included = matches!(scope, ScopeDecision::Included(_));
+            if !explicitly_included && should_skip_url(&absolute_url, &self.config.skip_patterns) {
+                state.reject("skip_pattern", &absolute_url);
+                return;
+            }
+
+            // Enforce template quotas before trap detection, so URLs over a quota
+            // do not count towards the trap limits
+            let mut templates = state.templates.lock().unwrap();
+            if let Some(pattern) = templates.check(&absolute_url) {
+                debug!("Skipping {} (quota for {} reached)", absolute_url, pattern);
+                state.reject(&format!("template_quota:{}", pattern), &absolute_url);
+                return;
+            }
+
+            // Skip crawler traps
+            let trap = state.trap_detector.lock().unwrap().check(&absolute_url);
+            if let Some(trap) = trap {
+                debug!("Skipping {} ({})", absolute_url, trap.reason());
+                if let TrapKind::TemplateCap(template) = &trap {
+                    let mut patterns = state.massive_link_patterns.lock().unwrap();
+                    if patterns.insert(template.clone()) {
+                        info!("URL template reached its cap: {}", template);
                     }
                 }
+                state.reject(trap.reason(), &absolute_url);
+                return;
+            }
+            templates.admit(&absolute_url);
+
+            // Score the link for the crawl strategy
+            let candidate = LinkCandidate {
+                url: &absolute_url,
+                depth: depth + 1,
+                anchor_text: context.anchor_text,
+                title: context.title,
+                location: context.location,
+                referrer: current_url,
+            };
+            let score = self.config.strategy.score(&candidate, &self.config);
+
+            // Add URL to queue
+            {
+                let mut q = state.queue.lock().unwrap();
+                q.push_back(UrlEntry {
+                    url: absolute_url,
+                    depth: depth + 1,
+                    score,
+                    referrer: Some(current_url.to_string()),
+                });
             }
         }
     }
@@ -577,6 +1202,73 @@ impl Spider {
     }
 }
 
+/// Extract the trimmed text of the `<title>` element
+fn extract_title(document: &Html) -> Option<String> {
+    let selector = Selector::parse("title").unwrap();
+    document
+        .select(&selector)
+        .next()
+        .map(|element| collapse_whitespace(&element.text().collect::<String>()))
+        .filter(|title| !title.is_empty())
+}
+
+/// Extract the content of `<meta name="description">`
+fn extract_meta_description(document: &Html) -> Option<String> {
+    let selector = Selector::parse("meta[name]").unwrap();
+    document
+        .select(&selector)
+        .find(|element| {
+            element
+                .value()
+                .attr("name")
+                .is_some_and(|name| name.eq_ignore_ascii_case("description"))
+        })
+        .and_then(|element| element.value().attr("content"))
+        .map(collapse_whitespace)
+        .filter(|description| !description.is_empty())
+}
+
+/// Check whether `<meta name="robots">` forbids indexing
+fn extract_meta_noindex(document: &Html) -> bool {
+    let selector = Selector::parse("meta[name]").unwrap();
+    document
+        .select(&selector)
+        .filter(|element| {
+            element
+                .value()
+                .attr("name")
+                .is_some_and(|name| name.trim().eq_ignore_ascii_case("robots"))
+        })
+        .filter_map(|element| element.value().attr("content"))
+        .any(has_noindex)
+}
+
+/// Check whether robots directives, such as `noindex, follow`, forbid indexing
+///
+/// Directives scoped to a user agent, like `googlebot: noindex`, also count.
+fn has_noindex(directives: &str) -> bool {
+    directives.split(',').any(|directive| {
+        let directive = directive.rsplit(':').next().unwrap_or("").trim();
+        directive.eq_ignore_ascii_case("noindex") || directive.eq_ignore_ascii_case("none")
+    })
+}
+
+/// Find the part of the page a link is in from its nearest landmark ancestor
+fn link_location(element: &ElementRef) -> LinkLocation {
+    element
+        .ancestors()
+        .filter_map(ElementRef::wrap)
+        .find_map(|ancestor| {
+            LinkLocation::from_element(ancestor.value().name(), ancestor.value().attr("role"))
+        })
+        .unwrap_or_default()
+}
+
+/// Collapse runs of whitespace into single spaces
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
 #[cfg(test)]
 mod tests {
     // Note: The following tests are commented out because they require an external mock HTTP server.
@@ -599,4 +1291,155 @@ mod tests {
         // A test that would verify handling of non-HTML content
     }
     */
+
+    use super::*;
+    use crate::spider::templates::TemplateQuota;
+    use crate::spider::utils::{ScopePattern, ScopeTarget};
+
+    #[tokio::test]
+    async fn test_unfinished_site_marked_failed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("crawl.db");
+        let sink = Arc::new(SqliteSink::open(&path).unwrap());
+        let site_id = sink.start_site("https://a.com/", "a.com").await.unwrap();
+
+        // A crawl returning an error drops its state without finishing the site
+        drop(SinkSite {
+            sink,
+            site_id,
+            finished: false,
+        });
+
+        let connection = rusqlite::Connection::open(&path).unwrap();
+        let status: String = connection
+            .query_row("SELECT status FROM sites WHERE id = ?1", [site_id], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(status, "failed");
+    }
+
+    /// Add a link found on an HTML page of `a.com` to the frontier
+    fn enqueue(spider: &Spider, state: &CrawlState, page: &str, href: &str) {
+        let source = LinkSource {
+            url: page,
+            depth: 0,
+            encoding: UTF_8,
+            kind: ContentKind::Html,
+        };
+        spider.enqueue_link(&source, href, &LinkContext::default(), state, "a.com");
+    }
+
+    #[test]
+    fn test_scope_applied_when_link_found() {
+        let config = SpiderConfig::builder()
+            .exclude(
+                "private",
+                ScopeTarget::Path,
+                ScopePattern::Prefix("/private/".to_string()),
+            )
+            .unwrap()
+            .build();
+        let state = CrawlState::new(&config);
+        let spider = Spider::new(config);
+
+        enqueue(&spider, &state, "https://a.com/", "/private/1");
+        enqueue(&spider, &state, "https://a.com/other", "/private/1");
+        enqueue(&spider, &state, "https://a.com/", "/public");
+
+        let skipped = state.skipped_urls.lock().unwrap();
+        assert_eq!(skipped["scope:private"], vec!["https://a.com/private/1"]);
+        let queue = state.queue.lock().unwrap();
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue[0].url, "https://a.com/public");
+        let templates = state.templates.lock().unwrap().report(&HashSet::new());
+        assert_eq!(templates.len(), 1);
+    }
+
+    #[test]
+    fn test_rejected_urls_counted_once() {
+        let config = SpiderConfig::builder()
+            .add_template_quota(TemplateQuota::new("/p/{n}", 1).unwrap())
+            .build();
+        let state = CrawlState::new(&config);
+        let spider = Spider::new(config);
+
+        for page in ["https://a.com/", "https://a.com/other"] {
+            enqueue(&spider, &state, page, "/p/1");
+            enqueue(&spider, &state, page, "/p/2");
+            enqueue(&spider, &state, page, "/cart;jsessionid=abc123");
+        }
+
+        let skipped = state.skipped_urls.lock().unwrap();
+        assert_eq!(skipped["template_quota:/p/{n}"], vec!["https://a.com/p/2"]);
+        assert_eq!(skipped["trap:session_id"].len(), 1);
+        assert_eq!(state.queue.lock().unwrap().len(), 1);
+        let templates = state.templates.lock().unwrap().report(&HashSet::new());
+        assert_eq!(templates[0].discovered, 2);
+        assert_eq!(templates[0].skipped_by_quota, 1);
+    }
+
+    #[test]
+    fn test_has_successful_page() {
+        let result = |statuses: &[u16]| {
+            let pages = statuses
+                .iter()
+                .enumerate()
+                .map(|(i, status)| PageInfo::fixture(&format!("https://a.com/{i}"), *status))
+                .collect();
+            CrawlResult::fixture(pages)
+        };
+
+        assert!(result(&[404, 200]).has_successful_page());
+        assert!(!result(&[404, 503]).has_successful_page());
+        assert!(!result(&[]).has_successful_page());
+    }
+
+    #[test]
+    fn test_extract_title_and_description() {
+        let document = Html::parse_document(
+            r#"<html><head><title>
+                Contact   us
+            </title><meta name="Description" content=" Reach our team "></head></html>"#,
+        );
+        assert_eq!(extract_title(&document).as_deref(), Some("Contact us"));
+        assert_eq!(
+            extract_meta_description(&document).as_deref(),
+            Some("Reach our team")
+        );
+    }
+
+    #[test]
+    fn test_noindex() {
+        let document = Html::parse_document(
+            r#"<html><head><meta name="ROBOTS" content="NoIndex, follow"></head></html>"#,
+        );
+        assert!(extract_meta_noindex(&document));
+        assert!(has_noindex("googlebot: noindex"));
+        assert!(has_noindex("none"));
+        assert!(!has_noindex("index, nofollow"));
+    }
+
+    #[test]
+    fn test_link_location() {
+        let document = Html::parse_document(
+            r#"<body><nav><ul><li><a href="/a">A</a></li></ul></nav>
+            <main><div role="navigation"><a href="/b">B</a></div><p><a href="/c">C</a></p></main>
+            <a href="/d">D</a></body>"#,
+        );
+        let selector = Selector::parse("a").unwrap();
+        let locations: Vec<LinkLocation> = document
+            .select(&selector)
+            .map(|a| link_location(&a))
+            .collect();
+        assert_eq!(
+            locations,
+            vec![
+                LinkLocation::Nav,
+                LinkLocation::Nav,
+                LinkLocation::Main,
+                LinkLocation::Other
+            ]
+        );
+    }
 }
@@ -0,0 +1,296 @@
+use scraper::{Html, Node};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Elements whose text is not part of the visible page content
+const HIDDEN_ELEMENTS: &[&str] = &["script", "style", "noscript", "template", "head"];
+
+/// Number of words per shingle used for SimHash
+const SHINGLE_SIZE: usize = 3;
+
+/// Content fingerprints of a page
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fingerprint {
+    /// Hash of the normalized visible text, identical for exact duplicates
+    pub content_hash: u64,
+
+    /// SimHash of the word shingles, close for near-duplicates
+    pub simhash: u64,
+}
+
+impl Fingerprint {
+    /// Fingerprint a page's visible text, or `None` if it has no words
+    pub fn of(text: &str) -> Option<Self> {
+        let words: Vec<String> = text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(|word| word.to_lowercase())
+            .collect();
+        if words.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            content_hash: fnv1a(words.join(" ").as_bytes()),
+            simhash: simhash(&words),
+        })
+    }
+
+    /// Number of differing SimHash bits
+    pub fn distance(&self, other: &Fingerprint) -> u32 {
+        (self.simhash ^ other.simhash).count_ones()
+    }
+}
+
+/// The page a duplicate was matched against
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DuplicateMatch {
+    /// URL of the first page seen with this content, after redirects
+    pub of: String,
+
+    /// SimHash distance to that page, 0 for exact duplicates
+    pub distance: u32,
+}
+
+/// A group of pages with the same or nearly the same content
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateCluster {
+    /// URL of the first page seen with this content, after redirects
+    pub representative: String,
+
+    /// Pages matched against the representative
+    pub duplicates: Vec<DuplicateMember>,
+}
+
+/// A page in a duplicate cluster
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateMember {
+    /// URL of the duplicate page, after redirects
+    pub url: String,
+
+    /// SimHash distance to the representative, 0 for exact duplicates
+    pub distance: u32,
+}
+
+/// Matches page fingerprints against the pages seen so far
+///
+/// Every page that is not a duplicate becomes a representative. Near-duplicates
+/// are found with a linear scan over the representatives.
+#[derive(Debug, Default)]
+pub struct DuplicateDetector {
+    /// Maximum SimHash distance for a near-duplicate
+    max_distance: u32,
+
+    /// Representative URL per content hash
+    exact: HashMap<u64, String>,
+
+    /// Representative URLs with their fingerprints
+    representatives: Vec<(String, Fingerprint)>,
+}
+
+impl DuplicateDetector {
+    /// Create a detector treating pages within `max_distance` SimHash bits as near-duplicates
+    pub fn new(max_distance: u32) -> Self {
+        Self {
+            max_distance,
+            ..Default::default()
+        }
+    }
+
+    /// Check a page, registering it as a representative if it is not a duplicate
+    pub fn check(&mut self, url: &str, fingerprint: &Fingerprint) -> Option<DuplicateMatch> {
+        if let Some(of) = self.exact.get(&fingerprint.content_hash) {
+            return Some(DuplicateMatch {
+                of: of.clone(),
+                distance: 0,
+            });
+        }
+
+        let nearest = self
+            .representatives
+            .iter()
+            .map(|(of, other)| (of, fingerprint.distance(other)))
+            .filter(|(_, distance)| *distance <= self.max_distance)
+            .min_by_key(|(_, distance)| *distance);
+        if let Some((of, distance)) = nearest {
+            return Some(DuplicateMatch {
+                of: of.clone(),
+                distance,
+            });
+        }
+
+        self.exact.insert(fingerprint.content_hash, url.to_string());
+        self.representatives
+            .push((url.to_string(), fingerprint.clone()));
+        None
+    }
+}
+
+/// Group duplicate pages by their representative
+///
+/// Pages are given by the same URL they were checked with, so a page reached
+/// again through another redirect is not listed as a duplicate of itself.
+pub fn cluster_duplicates<'a>(
+    duplicates: impl IntoIterator<Item = (&'a str, &'a DuplicateMatch)>,
+) -> Vec<DuplicateCluster> {
+    let mut clusters: BTreeMap<&str, Vec<DuplicateMember>> = BTreeMap::new();
+    for (url, duplicate) in duplicates {
+        if url == duplicate.of {
+            continue;
+        }
+        clusters
+            .entry(duplicate.of.as_str())
+            .or_default()
+            .push(DuplicateMember {
+                url: url.to_string(),
+                distance: duplicate.distance,
+            });
+    }
+
+    clusters
+        .into_iter()
+        .map(|(representative, mut duplicates)| {
+            duplicates.sort_by(|a, b| a.url.cmp(&b.url));
+            DuplicateCluster {
+                representative: representative.to_string(),
+                duplicates,
+            }
+        })
+        .collect()
+}
+
+/// Extract the visible text of a document, without scripts and styles
+pub fn visible_text(document: &Html) -> String {
+    let mut text = String::new();
+    for node in document.tree.root().descendants() {
+        let Node::Text(content) = node.value() else {
+            continue;
+        };
+        let hidden = node.ancestors().any(|ancestor| {
+            matches!(ancestor.value(), Node::Element(element) if HIDDEN_ELEMENTS.contains(&element.name()))
+        });
+        if !hidden {
+            text.push_str(content);
+            text.push(' ');
+        }
+    }
+
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Format a 64-bit hash as fixed-width hex
+pub fn hash_hex(hash: u64) -> String {
+    format!("{:016x}", hash)
+}
+
+/// Compute the SimHash of a list of words using overlapping shingles
+fn simhash(words: &[String]) -> u64 {
+    let size = SHINGLE_SIZE.min(words.len());
+    let mut weights = [0i64; 64];
+    for shingle in words.windows(size) {
+        let hash = fnv1a(shingle.join(" ").as_bytes());
+        for (bit, weight) in weights.iter_mut().enumerate() {
+            if hash & (1 << bit) != 0 {
+                *weight += 1;
+            } else {
+                *weight -= 1;
+            }
+        }
+    }
+
+    weights
+        .iter()
+        .enumerate()
+        .filter(|(_, weight)| **weight > 0)
+        .fold(0, |hash, (bit, _)| hash | (1 << bit))
+}
+
+/// 64-bit FNV-1a hash, stable across runs and platforms
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spider::config::defaults;
+
+    const ARTICLE: &str = "The quick brown fox jumps over the lazy dog while the farmer watches \
+        from the porch and the cat sleeps in the warm afternoon sun near the old red barn by \
+        the river where children play every summer and fishermen gather at dawn to cast their \
+        lines into the slow moving water hoping for a good catch before the heat of the day";
+
+    #[test]
+    fn test_visible_text() {
+        let document = Html::parse_document(
+            "<html><head><title>T</title><style>p{}</style></head>\
+             <body><p>Hello <b>world</b></p><script>var x;</script></body></html>",
+        );
+        assert_eq!(visible_text(&document), "Hello world");
+    }
+
+    #[test]
+    fn test_exact_and_near_duplicates() {
+        let original = Fingerprint::of(ARTICLE).unwrap();
+        let reformatted = Fingerprint::of(&ARTICLE.to_uppercase().replace(' ', "\n ")).unwrap();
+        let edited = Fingerprint::of(&format!("{} Sort by price", ARTICLE)).unwrap();
+        let different = Fingerprint::of("Contact us by phone or email for a quote").unwrap();
+
+        assert_eq!(original.content_hash, reformatted.content_hash);
+        assert_ne!(original.content_hash, edited.content_hash);
+        assert!(original.distance(&edited) <= 6);
+        assert!(original.distance(&different) > 6);
+        assert_eq!(Fingerprint::of(" \n "), None);
+    }
+
+    #[test]
+    fn test_default_distance() {
+        let mut detector = DuplicateDetector::new(defaults::NEAR_DUPLICATE_DISTANCE);
+        let original = Fingerprint::of(ARTICLE).unwrap();
+        let sorted = Fingerprint::of(&format!("{} Sort by price", ARTICLE)).unwrap();
+        let rewritten = Fingerprint::of(&ARTICLE.replace("lazy", "sleepy")).unwrap();
+        let different = Fingerprint::of("Contact us by phone or email for a quote").unwrap();
+
+        assert_eq!(detector.check("https://a.com/p", &original), None);
+        let near = detector
+            .check("https://a.com/p?sort=price", &sorted)
+            .unwrap();
+        assert_eq!(near.of, "https://a.com/p");
+        assert!(near.distance > 0 && near.distance <= defaults::NEAR_DUPLICATE_DISTANCE);
+        assert!(original.distance(&rewritten) > defaults::NEAR_DUPLICATE_DISTANCE);
+        assert_eq!(detector.check("https://a.com/q", &rewritten), None);
+        assert_eq!(detector.check("https://a.com/contact", &different), None);
+    }
+
+    #[test]
+    fn test_detector_and_clusters() {
+        let mut detector = DuplicateDetector::new(6);
+        let original = Fingerprint::of(ARTICLE).unwrap();
+        let edited = Fingerprint::of(&format!("{} Sort by price", ARTICLE)).unwrap();
+
+        assert_eq!(detector.check("https://a.com/p", &original), None);
+        let exact = detector
+            .check("https://a.com/p?print=1", &original)
+            .unwrap();
+        assert_eq!(exact.distance, 0);
+        let near = detector
+            .check("https://a.com/p?sort=price", &edited)
+            .unwrap();
+        assert_eq!(near.of, "https://a.com/p");
+
+        // The same page reached again, e.g. through another redirect
+        let again = detector.check("https://a.com/p", &original).unwrap();
+
+        let clusters = cluster_duplicates([
+            ("https://a.com/p?sort=price", &near),
+            ("https://a.com/p?print=1", &exact),
+            ("https://a.com/p", &again),
+        ]);
+        assert_eq!(clusters.len(), 1);
+        assert_eq!(clusters[0].duplicates.len(), 2);
+        assert_eq!(clusters[0].representative, "https://a.com/p");
+        assert_eq!(clusters[0].duplicates[0].url, "https://a.com/p?print=1");
+    }
+}
@@ -15,9 +15,18 @@ pub enum SpiderError {
     #[error("JSON error: {0}")]
     Json(#[from] serde_json::Error),
 
+    #[error("SQLite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+
+    #[error("CSV error: {0}")]
+    Csv(#[from] csv::Error),
+
     #[error("Invalid URL: {0}")]
     InvalidUrl(String),
 
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+
     #[error("Crawl limit reached: {0}")]
     CrawlLimitReached(String),
     
@@ -0,0 +1,127 @@
+use crate::spider::graph::LinkEdge;
+use crate::spider::network::{LinkCheck, NetworkClient};
+use futures::stream::{self, StreamExt};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use url::Url;
+
+/// A link to another site
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExternalLink {
+    /// The external URL
+    pub url: String,
+
+    /// The crawled pages linking to it
+    pub source_pages: Vec<String>,
+
+    /// Result of the optional liveness check
+    pub check: Option<LinkCheck>,
+}
+
+/// Group external links from the link graph by domain
+///
+/// Domains are host names with any `www.` prefix removed.
+pub fn group_external_links(edges: &[LinkEdge]) -> BTreeMap<String, Vec<ExternalLink>> {
+    let mut by_url: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    for edge in edges.iter().filter(|edge| !edge.internal) {
+        by_url
+            .entry(edge.target.as_str())
+            .or_default()
+            .insert(edge.source.as_str());
+    }
+
+    let mut by_domain: BTreeMap<String, Vec<ExternalLink>> = BTreeMap::new();
+    for (url, sources) in by_url {
+        let Some(domain) = link_domain(url) else {
+            continue;
+        };
+        by_domain.entry(domain).or_default().push(ExternalLink {
+            url: url.to_string(),
+            source_pages: sources.into_iter().map(|s| s.to_string()).collect(),
+            check: None,
+        });
+    }
+
+    by_domain
+}
+
+/// Check every external link once, at most `concurrency` at a time
+pub async fn validate_external_links(
+    network: &NetworkClient,
+    external_links: &mut BTreeMap<String, Vec<ExternalLink>>,
+    concurrency: usize,
+) {
+    let total: usize = external_links.values().map(|links| links.len()).sum();
+    info!("Validating {} external links", total);
+
+    let checks = stream::iter(external_links.values_mut().flatten())
+        .map(|link| async move {
+            let referrer = link.source_pages.first().cloned();
+            link.check = Some(network.check_link(&link.url, referrer.as_deref()).await);
+        })
+        .buffer_unordered(concurrency.max(1));
+    checks.collect::<Vec<_>>().await;
+
+    let dead = count_dead_links(external_links);
+    info!(
+        "External link validation complete: {} of {} dead",
+        dead, total
+    );
+}
+
+/// Count checked external links that are not alive
+pub fn count_dead_links(external_links: &BTreeMap<String, Vec<ExternalLink>>) -> usize {
+    external_links
+        .values()
+        .flatten()
+        .filter(|link| link.check.as_ref().is_some_and(|check| !check.alive))
+        .count()
+}
+
+/// Extract the domain of a URL for grouping
+fn link_domain(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    let host = url.host_str()?.to_lowercase();
+    Some(
+        host.strip_prefix("www.")
+            .map(|h| h.to_string())
+            .unwrap_or(host),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spider::content::ContentKind;
+
+    fn edge(source: &str, target: &str, internal: bool) -> LinkEdge {
+        LinkEdge {
+            source: source.to_string(),
+            target: target.to_string(),
+            internal,
+            source_kind: ContentKind::Html,
+        }
+    }
+
+    #[test]
+    fn test_group_external_links() {
+        let edges = vec![
+            edge("https://a.com/", "https://www.b.com/x", false),
+            edge("https://a.com/y", "https://www.b.com/x", false),
+            edge("https://a.com/", "https://b.com/z", false),
+            edge("https://a.com/", "https://c.org/", false),
+            edge("https://a.com/", "https://a.com/y", true),
+        ];
+
+        let grouped = group_external_links(&edges);
+        assert_eq!(grouped.len(), 2);
+        assert_eq!(grouped["b.com"].len(), 2);
+        assert_eq!(grouped["b.com"][0].url, "https://b.com/z");
+        assert_eq!(
+            grouped["b.com"][1].source_pages,
+            vec!["https://a.com/", "https://a.com/y"]
+        );
+        assert_eq!(grouped["c.org"][0].url, "https://c.org/");
+    }
+}
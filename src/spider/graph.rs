@@ -0,0 +1,450 @@
+use crate::spider::content::ContentKind;
+use crate::spider::error::SpiderError;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::io::Write;
+
+/// A link from one page to another
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct LinkEdge {
+    /// The page the link was found on
+    pub source: String,
+
+    /// The resolved link target
+    pub target: String,
+
+    /// Whether the target is in the crawl scope
+    pub internal: bool,
+
+    /// The kind of document the link was found in
+    pub source_kind: ContentKind,
+}
+
+/// Metrics computed from the internal link graph
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GraphMetrics {
+    /// Number of distinct pages linking to each internal URL
+    pub in_degree: BTreeMap<String, usize>,
+
+    /// Number of distinct internal URLs each page links to
+    pub out_degree: BTreeMap<String, usize>,
+
+    /// Minimum number of clicks from the start URL, following HTML links only
+    pub click_depth: BTreeMap<String, usize>,
+
+    /// Pages only linked from sitemaps or other non-HTML documents
+    pub sitemap_only_pages: Vec<String>,
+}
+
+/// The link graph of a crawl
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LinkGraph {
+    /// All links found, internal and external, without duplicates
+    pub edges: Vec<LinkEdge>,
+
+    /// Metrics computed from the internal links
+    pub metrics: GraphMetrics,
+}
+
+/// Supported link graph export formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    /// Edge list as CSV
+    Csv,
+
+    /// GraphML XML
+    GraphMl,
+
+    /// Graphviz DOT
+    Dot,
+}
+
+impl GraphFormat {
+    /// Parse a format name as used on the command line
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "csv" => Some(GraphFormat::Csv),
+            "graphml" => Some(GraphFormat::GraphMl),
+            "dot" => Some(GraphFormat::Dot),
+            _ => None,
+        }
+    }
+}
+
+impl LinkGraph {
+    /// Build a graph from the collected edges and compute its metrics
+    ///
+    /// Edge sources are pages after redirects, while targets are URLs as linked.
+    /// The metrics follow `redirects`, a map of requested to final URLs, so a
+    /// link to a redirected URL counts as a link to the page it redirects to.
+    pub fn new(
+        mut edges: Vec<LinkEdge>,
+        start_url: &str,
+        redirects: &HashMap<String, String>,
+    ) -> Self {
+        edges.sort();
+        edges.dedup();
+
+        let metrics = compute_metrics(&edges, start_url, redirects);
+        Self { edges, metrics }
+    }
+
+    /// Write the graph in the given format
+    pub fn export<W: Write>(&self, format: GraphFormat, writer: &mut W) -> Result<(), SpiderError> {
+        match format {
+            GraphFormat::Csv => self.write_csv(writer),
+            GraphFormat::GraphMl => self.write_graphml(writer),
+            GraphFormat::Dot => self.write_dot(writer),
+        }
+    }
+
+    /// Write the edge list as CSV
+    pub fn write_csv<W: Write>(&self, writer: &mut W) -> Result<(), SpiderError> {
+        writeln!(writer, "source,target,internal,source_kind")?;
+        for edge in &self.edges {
+            writeln!(
+                writer,
+                "{},{},{},{}",
+                csv_field(&edge.source),
+                csv_field(&edge.target),
+                edge.internal,
+                edge.source_kind.name()
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Write the graph as GraphML
+    pub fn write_graphml<W: Write>(&self, writer: &mut W) -> Result<(), SpiderError> {
+        let nodes = self.node_ids();
+
+        writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            writer,
+            r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+        )?;
+        writeln!(
+            writer,
+            r#"  <key id="url" for="node" attr.name="url" attr.type="string"/>"#
+        )?;
+        writeln!(
+            writer,
+            r#"  <key id="in_degree" for="node" attr.name="in_degree" attr.type="int"/>"#
+        )?;
+        writeln!(
+            writer,
+            r#"  <key id="click_depth" for="node" attr.name="click_depth" attr.type="int"/>"#
+        )?;
+        writeln!(
+            writer,
+            r#"  <key id="internal" for="edge" attr.name="internal" attr.type="boolean"/>"#
+        )?;
+        writeln!(writer, r#"  <graph id="links" edgedefault="directed">"#)?;
+
+        for (url, id) in &nodes {
+            writeln!(writer, r#"    <node id="n{}">"#, id)?;
+            writeln!(
+                writer,
+                r#"      <data key="url">{}</data>"#,
+                xml_escape(url)
+            )?;
+            if let Some(in_degree) = self.metrics.in_degree.get(*url) {
+                writeln!(
+                    writer,
+                    r#"      <data key="in_degree">{}</data>"#,
+                    in_degree
+                )?;
+            }
+            if let Some(depth) = self.metrics.click_depth.get(*url) {
+                writeln!(writer, r#"      <data key="click_depth">{}</data>"#, depth)?;
+            }
+            writeln!(writer, "    </node>")?;
+        }
+
+        for (index, edge) in self.edges.iter().enumerate() {
+            writeln!(
+                writer,
+                r#"    <edge id="e{}" source="n{}" target="n{}">"#,
+                index,
+                nodes[edge.source.as_str()],
+                nodes[edge.target.as_str()]
+            )?;
+            writeln!(
+                writer,
+                r#"      <data key="internal">{}</data>"#,
+                edge.internal
+            )?;
+            writeln!(writer, "    </edge>")?;
+        }
+
+        writeln!(writer, "  </graph>")?;
+        writeln!(writer, "</graphml>")?;
+        Ok(())
+    }
+
+    /// Write the graph in Graphviz DOT format
+    pub fn write_dot<W: Write>(&self, writer: &mut W) -> Result<(), SpiderError> {
+        writeln!(writer, "digraph links {{")?;
+        for edge in &self.edges {
+            let style = if edge.internal { "" } else { " [style=dashed]" };
+            writeln!(
+                writer,
+                "  \"{}\" -> \"{}\"{};",
+                dot_escape(&edge.source),
+                dot_escape(&edge.target),
+                style
+            )?;
+        }
+        writeln!(writer, "}}")?;
+        Ok(())
+    }
+
+    /// Assign a stable numeric id to every URL in the graph
+    fn node_ids(&self) -> BTreeMap<&str, usize> {
+        let urls: BTreeSet<&str> = self
+            .edges
+            .iter()
+            .flat_map(|edge| [edge.source.as_str(), edge.target.as_str()])
+            .collect();
+
+        urls.into_iter()
+            .enumerate()
+            .map(|(id, url)| (url, id))
+            .collect()
+    }
+}
+
+/// Compute degree, click depth and sitemap-only metrics for the internal links
+fn compute_metrics(
+    edges: &[LinkEdge],
+    start_url: &str,
+    redirects: &HashMap<String, String>,
+) -> GraphMetrics {
+    let resolve = |url: &str| -> String {
+        redirects
+            .get(url)
+            .cloned()
+            .unwrap_or_else(|| url.to_string())
+    };
+    let start_url = resolve(start_url);
+    let start_url = start_url.as_str();
+
+    // Distinct internal links between final URLs
+    let links: BTreeSet<(String, String, bool)> = edges
+        .iter()
+        .filter(|edge| edge.internal)
+        .map(|edge| {
+            let is_html = edge.source_kind == ContentKind::Html;
+            (resolve(&edge.source), resolve(&edge.target), is_html)
+        })
+        .filter(|(source, target, _)| source != target)
+        .collect();
+
+    let mut metrics = GraphMetrics::default();
+    let mut html_links: HashMap<&str, Vec<&str>> = HashMap::new();
+    let mut linked_from_html: BTreeSet<&str> = BTreeSet::new();
+    let mut linked_from_other: BTreeSet<&str> = BTreeSet::new();
+    let mut degrees: BTreeSet<(&str, &str)> = BTreeSet::new();
+
+    for (source, target, is_html) in &links {
+        if degrees.insert((source, target)) {
+            *metrics.in_degree.entry(target.clone()).or_insert(0) += 1;
+            *metrics.out_degree.entry(source.clone()).or_insert(0) += 1;
+        }
+
+        if *is_html {
+            html_links.entry(source).or_default().push(target);
+            linked_from_html.insert(target);
+        } else {
+            linked_from_other.insert(target);
+        }
+    }
+
+    // Breadth-first search from the start URL over HTML links
+    let mut queue = VecDeque::from([(start_url, 0)]);
+    metrics.click_depth.insert(start_url.to_string(), 0);
+    while let Some((url, depth)) = queue.pop_front() {
+        for target in html_links.get(url).into_iter().flatten() {
+            if !metrics.click_depth.contains_key(*target) {
+                metrics.click_depth.insert(target.to_string(), depth + 1);
+                queue.push_back((target, depth + 1));
+            }
+        }
+    }
+
+    metrics.sitemap_only_pages = linked_from_other
+        .difference(&linked_from_html)
+        .filter(|url| **url != start_url)
+        .map(|url| url.to_string())
+        .collect();
+
+    metrics
+}
+
+/// Quote a CSV field if needed
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Escape a string for use in XML text or attributes
+pub(crate) fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Escape a string for use in a quoted DOT identifier
+fn dot_escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edge(source: &str, target: &str, internal: bool, kind: ContentKind) -> LinkEdge {
+        LinkEdge {
+            source: source.to_string(),
+            target: target.to_string(),
+            internal,
+            source_kind: kind,
+        }
+    }
+
+    fn sample_graph() -> LinkGraph {
+        LinkGraph::new(
+            vec![
+                edge("https://a.com/", "https://a.com/x", true, ContentKind::Html),
+                edge("https://a.com/", "https://a.com/x", true, ContentKind::Html),
+                edge(
+                    "https://a.com/x",
+                    "https://a.com/y",
+                    true,
+                    ContentKind::Html,
+                ),
+                edge("https://a.com/x", "https://a.com/", true, ContentKind::Html),
+                edge(
+                    "https://a.com/x",
+                    "https://b.com/",
+                    false,
+                    ContentKind::Html,
+                ),
+                edge(
+                    "https://a.com/sitemap.xml",
+                    "https://a.com/z",
+                    true,
+                    ContentKind::Xml,
+                ),
+            ],
+            "https://a.com/",
+            &HashMap::new(),
+        )
+    }
+
+    #[test]
+    fn test_metrics() {
+        let graph = sample_graph();
+        assert_eq!(graph.edges.len(), 5);
+        assert_eq!(graph.metrics.in_degree["https://a.com/x"], 1);
+        assert_eq!(graph.metrics.out_degree["https://a.com/x"], 2);
+        assert_eq!(graph.metrics.click_depth["https://a.com/"], 0);
+        assert_eq!(graph.metrics.click_depth["https://a.com/y"], 2);
+        assert!(!graph.metrics.click_depth.contains_key("https://a.com/z"));
+        assert_eq!(graph.metrics.sitemap_only_pages, vec!["https://a.com/z"]);
+    }
+
+    #[test]
+    fn test_metrics_follow_redirects() {
+        let redirects = HashMap::from([
+            ("http://a.com/".to_string(), "https://a.com/".to_string()),
+            (
+                "https://a.com/old".to_string(),
+                "https://a.com/new".to_string(),
+            ),
+        ]);
+        let graph = LinkGraph::new(
+            vec![
+                edge(
+                    "https://a.com/",
+                    "https://a.com/old",
+                    true,
+                    ContentKind::Html,
+                ),
+                edge(
+                    "https://a.com/",
+                    "https://a.com/new",
+                    true,
+                    ContentKind::Html,
+                ),
+                edge(
+                    "https://a.com/new",
+                    "https://a.com/deep",
+                    true,
+                    ContentKind::Html,
+                ),
+                edge(
+                    "https://a.com/sitemap.xml",
+                    "https://a.com/old",
+                    true,
+                    ContentKind::Xml,
+                ),
+            ],
+            "http://a.com/",
+            &redirects,
+        );
+
+        // Edges keep the URLs as linked
+        assert!(graph
+            .edges
+            .iter()
+            .any(|edge| edge.target == "https://a.com/old"));
+        assert_eq!(graph.metrics.click_depth["https://a.com/"], 0);
+        assert_eq!(graph.metrics.click_depth["https://a.com/new"], 1);
+        assert_eq!(graph.metrics.click_depth["https://a.com/deep"], 2);
+        assert!(!graph.metrics.click_depth.contains_key("https://a.com/old"));
+        assert_eq!(graph.metrics.in_degree["https://a.com/new"], 2);
+        assert!(graph.metrics.sitemap_only_pages.is_empty());
+    }
+
+    #[test]
+    fn test_export_csv() {
+        let graph = LinkGraph::new(
+            vec![edge(
+                "https://a.com/",
+                "https://a.com/?a=1,2",
+                true,
+                ContentKind::Html,
+            )],
+            "https://a.com/",
+            &HashMap::new(),
+        );
+        let mut out = Vec::new();
+        graph.export(GraphFormat::Csv, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "source,target,internal,source_kind\nhttps://a.com/,\"https://a.com/?a=1,2\",true,html\n"
+        );
+    }
+
+    #[test]
+    fn test_export_graphml_and_dot() {
+        let graph = sample_graph();
+
+        let mut graphml = Vec::new();
+        graph.export(GraphFormat::GraphMl, &mut graphml).unwrap();
+        let graphml = String::from_utf8(graphml).unwrap();
+        assert_eq!(graphml.matches("<node ").count(), 6);
+        assert_eq!(graphml.matches("<edge ").count(), 5);
+
+        let mut dot = Vec::new();
+        graph.export(GraphFormat::Dot, &mut dot).unwrap();
+        let dot = String::from_utf8(dot).unwrap();
+        assert!(dot.contains("\"https://a.com/x\" -> \"https://b.com/\" [style=dashed];"));
+    }
+}
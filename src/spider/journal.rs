@@ -0,0 +1,204 @@
+use crate::spider::error::SpiderError;
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// File name of the batch journal
+pub const JOURNAL_FILE: &str = "batch_journal.jsonl";
+
+/// State of a site in a batch journal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JournalStatus {
+    /// Waiting to be crawled
+    Pending,
+
+    /// Being crawled; a site left running was interrupted
+    Running,
+
+    /// Crawled, with the result saved
+    Done,
+
+    /// The crawl failed
+    Failed,
+}
+
+/// One line of a batch journal
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// The start URL after normalization, e.g. `https://example.com/` for `example.com`
+    ///
+    /// Resuming matches input rows on this URL, not on the URL as written in the
+    /// input file, so entries edited by hand must use it too.
+    pub url: String,
+
+    /// The new state of the site
+    pub status: JournalStatus,
+
+    /// Path of the saved result, for done sites
+    #[serde(default)]
+    pub result_file: Option<String>,
+
+    /// Why the crawl failed, for failed sites
+    #[serde(default)]
+    pub error: Option<String>,
+
+    /// When the state changed, in RFC 3339 format
+    pub time: String,
+}
+
+/// Append-only record of the state of every site in a batch
+///
+/// Each state change is a JSON line flushed as soon as it happens, so the
+/// journal stays readable when a batch is interrupted. The last line for a
+/// URL gives its current state.
+pub struct BatchJournal {
+    /// Writer for the journal file
+    writer: Mutex<File>,
+}
+
+impl BatchJournal {
+    /// Start a new journal, replacing an existing one
+    pub fn create(path: &Path) -> Result<Self, SpiderError> {
+        Self::open(path, false)
+    }
+
+    /// Continue an existing journal, creating it if missing
+    pub fn append(path: &Path) -> Result<Self, SpiderError> {
+        Self::open(path, true)
+    }
+
+    fn open(path: &Path, append: bool) -> Result<Self, SpiderError> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(append)
+            .truncate(!append)
+            .open(path)?;
+
+        // Start on a new line after a line cut short by an interrupted run
+        let mut file = file;
+        if append && file.metadata()?.len() > 0 {
+            let mut last = [0u8];
+            let mut reader = File::open(path)?;
+            reader.seek(SeekFrom::End(-1))?;
+            reader.read_exact(&mut last)?;
+            if last[0] != b'\n' {
+                file.write_all(b"\n")?;
+            }
+        }
+
+        Ok(Self {
+            writer: Mutex::new(file),
+        })
+    }
+
+    /// Record a new state for a site
+    pub fn record(
+        &self,
+        url: &str,
+        status: JournalStatus,
+        result_file: Option<String>,
+        error: Option<String>,
+    ) -> Result<(), SpiderError> {
+        let entry = JournalEntry {
+            url: url.to_string(),
+            status,
+            result_file,
+            error,
+            time: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        };
+
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+        let mut writer = self.writer.lock().unwrap();
+        writer.write_all(&line)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Read the latest entry of every site in a journal
+    ///
+    /// A missing journal reads as empty. A truncated last line, as left by a
+    /// crash mid-write, is ignored.
+    pub fn read(path: &Path) -> Result<HashMap<String, JournalEntry>, SpiderError> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashMap::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut entries = HashMap::new();
+        for line in BufReader::new(file).lines() {
+            if let Ok(entry) = serde_json::from_str::<JournalEntry>(&line?) {
+                entries.insert(entry.url.clone(), entry);
+            }
+        }
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latest_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("batch_journal.jsonl");
+
+        let journal = BatchJournal::create(&path).unwrap();
+        journal
+            .record("https://a.com", JournalStatus::Pending, None, None)
+            .unwrap();
+        journal
+            .record("https://b.com", JournalStatus::Pending, None, None)
+            .unwrap();
+        journal
+            .record("https://a.com", JournalStatus::Running, None, None)
+            .unwrap();
+        drop(journal);
+
+        let journal = BatchJournal::append(&path).unwrap();
+        journal
+            .record(
+                "https://a.com",
+                JournalStatus::Done,
+                Some("output/crawler/a_com.json".to_string()),
+                None,
+            )
+            .unwrap();
+        drop(journal);
+        OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(br#"{"url": "https://b.com", "sta"#)
+            .unwrap();
+
+        let entries = BatchJournal::read(&path).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries["https://a.com"].status, JournalStatus::Done);
+        assert_eq!(entries["https://b.com"].status, JournalStatus::Pending);
+
+        BatchJournal::append(&path)
+            .unwrap()
+            .record("https://b.com", JournalStatus::Failed, None, None)
+            .unwrap();
+        let entries = BatchJournal::read(&path).unwrap();
+        assert_eq!(entries["https://b.com"].status, JournalStatus::Failed);
+        assert!(BatchJournal::read(&dir.path().join("missing.jsonl"))
+            .unwrap()
+            .is_empty());
+
+        BatchJournal::create(&path).unwrap();
+        assert!(BatchJournal::read(&path).unwrap().is_empty());
+    }
+}
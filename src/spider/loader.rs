@@ -1,12 +1,184 @@
-use crate::spider::config::defaults;
+use crate::spider::config::{defaults, ConfigOverrides, BATCH_ONLY_SETTINGS};
 use crate::spider::error::SpiderError;
-use crate::spider::{Spider, SpiderConfig};
+use crate::spider::journal::{BatchJournal, JournalStatus};
+use crate::spider::sqlite::SqliteSink;
+use crate::spider::summary::{BatchSummary, SiteStatus};
+use crate::spider::utils::{
+    domain_to_filename, extract_base_domain, normalize_input_url, result_file_name,
+};
+use crate::spider::{CrawlResult, Spider, SpiderConfig};
 use futures::stream::{self, StreamExt};
-use log::info;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{hash_map::Entry, HashMap};
 use std::fs::File;
 use std::io::{BufRead, BufReader};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// CSV columns holding lists, separated by `;` in a cell
+const CSV_LIST_COLUMNS: &[&str] = &[
+    "tags",
+    "skip_patterns",
+    "add_skip_patterns",
+    "skip_subdomain_patterns",
+    "priority_paths",
+    "user_agents",
+    "allowed_hosts",
+];
+
+/// CSV columns holding maps, written as `key=value` pairs separated by `;`
+const CSV_MAP_COLUMNS: &[&str] = &["headers", "template_quotas"];
+
+/// CSV columns holding JSON, for nested settings such as scope rules
+const CSV_JSON_COLUMNS: &[&str] = &["scope_rules", "host_headers", "traps", "link_scoring"];
+
+/// CSV columns whose values, or map values, are text even when they look like numbers
+const CSV_TEXT_COLUMNS: &[&str] = &[
+    "url",
+    "identify",
+    "headers",
+    "referer_policy",
+    "domain_scope",
+    "strategy",
+];
+
+/// A site to crawl, as read from the batch input file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SiteInput {
+    /// The start URL
+    pub url: String,
+
+    /// Line of the input file the site was read from
+    pub line: usize,
+
+    /// Labels copied to the crawl result
+    pub tags: Vec<String>,
+
+    /// Settings overriding the batch config for this site
+    pub overrides: ConfigOverrides,
+}
+
+impl SiteInput {
+    /// The config for this site: the batch config with the overrides and tags applied
+    pub fn config(&self, base: &SpiderConfig) -> Result<SpiderConfig, SpiderError> {
+        let mut config = self.overrides.apply(base)?;
+        config.tags.extend(self.tags.iter().cloned());
+        Ok(config)
+    }
+
+    /// Build a site from its URL and the rest of its JSON object: optional `tags` and overrides
+    fn from_json(url: String, mut row: Map<String, Value>, line: usize) -> Result<Self, String> {
+        if let Some(setting) = row
+            .keys()
+            .find(|key| BATCH_ONLY_SETTINGS.contains(&key.as_str()))
+        {
+            return Err(format!("{} can only be set for the whole batch", setting));
+        }
+        let tags = match row.remove("tags") {
+            Some(tags) => serde_json::from_value(tags).map_err(|e| format!("tags: {}", e))?,
+            None => Vec::new(),
+        };
+        let overrides = serde_json::from_value(Value::Object(row)).map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            url,
+            line,
+            tags,
+            overrides,
+        })
+    }
+}
+
+/// A line of the batch input file that was left out
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputWarning {
+    /// Line number in the input file
+    pub line: usize,
+
+    /// The URL as written on the line, or the whole line if it has no URL
+    pub input: String,
+
+    /// Why the line was left out
+    pub message: String,
+}
+
+/// How the crawl of one site of a batch ended
+#[derive(Debug)]
+pub struct SiteOutcome {
+    /// The normalized start URL
+    pub url: String,
+
+    /// Labels from the input file
+    pub tags: Vec<String>,
+
+    /// The crawl result, or the error that stopped the crawl
+    pub result: Result<CrawlResult, SpiderError>,
+
+    /// Time spent on the site
+    pub duration: Duration,
+
+    /// Whether the site was completed by an earlier run and its result read back
+    pub resumed: bool,
+}
+
+/// Outcomes of a batch crawl
+#[derive(Debug, Default)]
+pub struct BatchOutcome {
+    /// One outcome per site, in input order
+    pub sites: Vec<SiteOutcome>,
+
+    /// Input lines left out as invalid or duplicate
+    pub warnings: Vec<InputWarning>,
+}
+
+/// Which sites of the input file a batch crawls
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum BatchMode {
+    /// Crawl every site, starting a new journal
+    #[default]
+    Fresh,
+
+    /// Crawl the sites the journal does not record as done
+    Resume,
+
+    /// Crawl only the sites that failed in the given batch summary
+    RetryFailed(PathBuf),
+}
+
+/// What a batch does with one site of the input file
+enum SitePlan {
+    /// Crawl the site
+    Crawl(SiteInput),
+
+    /// Reuse the result of an earlier run
+    Reuse(SiteInput, Box<CrawlResult>),
+}
+
+/// Format of the batch input file, chosen by its extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InputFormat {
+    /// One URL per line
+    Text,
+
+    /// A header row naming `url`, `tags` and override columns, then one site per row
+    Csv,
+
+    /// One JSON object per line
+    JsonLines,
+}
+
+impl InputFormat {
+    fn of(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("csv") => InputFormat::Csv,
+            Some("jsonl" | "ndjson") => InputFormat::JsonLines,
+            _ => InputFormat::Text,
+        }
+    }
+}
 
 /// Loader for crawling multiple URLs in parallel
 pub struct Loader {
@@ -18,6 +190,12 @@ pub struct Loader {
 
     /// The path to the file containing URLs to crawl
     url_file_path: String,
+
+    /// The path of the batch journal, if one is kept
+    journal_path: Option<PathBuf>,
+
+    /// Which sites of the input file are crawled
+    mode: BatchMode,
 }
 
 impl Default for Loader {
@@ -26,6 +204,8 @@ impl Default for Loader {
             config: SpiderConfig::default(),
             max_concurrent_sites: defaults::MAX_CONCURRENT_SITES,
             url_file_path: "input/urls.txt".to_string(),
+            journal_path: None,
+            mode: BatchMode::default(),
         }
     }
 }
@@ -37,11 +217,31 @@ impl Loader {
             config,
             max_concurrent_sites,
             url_file_path: url_file_path.to_string(),
+            journal_path: None,
+            mode: BatchMode::default(),
         }
     }
 
-    /// Load URLs from a file
-    fn load_urls(&self) -> Result<Vec<String>, SpiderError> {
+    /// Record the state of every site in a journal at the given path
+    pub fn with_journal(mut self, path: impl Into<PathBuf>) -> Self {
+        self.journal_path = Some(path.into());
+        self
+    }
+
+    /// Set which sites of the input file are crawled
+    pub fn with_mode(mut self, mode: BatchMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Load the sites to crawl from the input file
+    ///
+    /// Plain text files list one URL per line. `.csv` and `.jsonl` files can
+    /// also give tags and config overrides per site. Empty lines and lines
+    /// starting with `#` are ignored in every format. Rows that are not valid
+    /// JSON or have no URL are left out with a warning; invalid settings are
+    /// an error.
+    fn load_urls(&self) -> Result<(Vec<SiteInput>, Vec<InputWarning>), SpiderError> {
         let path = Path::new(&self.url_file_path);
 
         let file = File::open(path).map_err(|e| {
@@ -51,29 +251,142 @@ impl Loader {
             ))
         })?;
 
-        let reader = BufReader::new(file);
-        let mut urls = Vec::new();
+        match InputFormat::of(path) {
+            InputFormat::Text => read_text(file),
+            InputFormat::Csv => read_csv(file),
+            InputFormat::JsonLines => read_json_lines(file),
+        }
+        .map_err(|(line, e)| self.input_error(line, e))
+    }
 
-        for line in reader.lines() {
-            let line = line.map_err(SpiderError::Io)?;
-            let trimmed = line.trim();
+    /// Error for a line of the input file
+    fn input_error(&self, line: usize, message: impl std::fmt::Display) -> SpiderError {
+        SpiderError::InvalidInput(format!("{}:{}: {}", self.url_file_path, line, message))
+    }
 
-            // Skip empty lines and comments
-            if !trimmed.is_empty() && !trimmed.starts_with('#') {
-                urls.push(trimmed.to_string());
+    /// Decide which sites to crawl and which earlier results to reuse
+    ///
+    /// A site is only reused if its result file can still be read; otherwise
+    /// it is crawled again.
+    fn plan(&self, sites: Vec<SiteInput>) -> Result<Vec<SitePlan>, SpiderError> {
+        let done: HashMap<String, String> = match &self.mode {
+            BatchMode::Fresh => return Ok(sites.into_iter().map(SitePlan::Crawl).collect()),
+            BatchMode::Resume => {
+                let Some(journal_path) = &self.journal_path else {
+                    return Err(SpiderError::InvalidInput(
+                        "resuming a batch needs a journal".to_string(),
+                    ));
+                };
+                BatchJournal::read(journal_path)?
+                    .into_values()
+                    .filter(|entry| entry.status == JournalStatus::Done)
+                    .filter_map(|entry| Some((entry.url, entry.result_file?)))
+                    .collect()
             }
-        }
+            BatchMode::RetryFailed(summary_path) => {
+                let file = File::open(summary_path)?;
+                let summary: BatchSummary = serde_json::from_reader(BufReader::new(file))?;
+                let known: HashMap<&str, SiteStatus> = summary
+                    .sites
+                    .iter()
+                    .map(|site| (site.url.as_str(), site.status))
+                    .collect();
+                for site in sites
+                    .iter()
+                    .filter(|site| !known.contains_key(site.url.as_str()))
+                {
+                    warn!("Not in {}, skipping: {}", summary_path.display(), site.url);
+                }
+                let sites: Vec<SiteInput> = sites
+                    .into_iter()
+                    .filter(|site| known.contains_key(site.url.as_str()))
+                    .collect();
 
-        Ok(urls)
+                let done = summary
+                    .sites
+                    .into_iter()
+                    .filter(|site| site.status == SiteStatus::Completed)
+                    .filter_map(|site| Some((site.url, site.result_file?)))
+                    .collect();
+                return Ok(reuse_done(sites, &done));
+            }
+        };
+
+        Ok(reuse_done(sites, &done))
     }
 
     /// Crawl all URLs in parallel
-    pub async fn crawl_all(&self) -> Result<Vec<Result<String, String>>, SpiderError> {
-        // Load URLs from file
-        let urls = self.load_urls()?;
-        let total_urls = urls.len();
+    ///
+    /// Returns one outcome per site in input order, plus warnings for the
+    /// input lines that were left out. Failed sites do not stop the batch; only
+    /// errors before crawling, such as an invalid input file, are returned as
+    /// `Err`. When resuming or retrying failures, sites completed by the
+    /// earlier run are returned with the result read back from their result
+    /// file.
+    pub async fn crawl_all(&self) -> Result<BatchOutcome, SpiderError> {
+        // Load URLs from file, checking every site's config before crawling any.
+        // Deduplication leaves one site per result file, so no two crawls write
+        // the same files.
+        let (sites, mut warnings) = self.load_urls()?;
+        let (sites, duplicates) = prepare_sites(sites);
+        warnings.extend(duplicates);
+        warnings.sort_by_key(|warning| warning.line);
+        for warning in &warnings {
+            warn!(
+                "{}:{}: {}: {}",
+                self.url_file_path, warning.line, warning.message, warning.input
+            );
+        }
+        info!("Loaded {} URLs from {}", sites.len(), self.url_file_path);
+        let plans = self.plan(sites)?;
 
-        info!("Loaded {} URLs from {}", total_urls, self.url_file_path);
+        let journal = match (&self.journal_path, &self.mode) {
+            (None, _) => None,
+            (Some(path), BatchMode::Fresh) => Some(Arc::new(BatchJournal::create(path)?)),
+            (Some(path), _) => Some(Arc::new(BatchJournal::append(path)?)),
+        };
+
+        let mut outcomes = Vec::new();
+        let mut crawls = Vec::new();
+        for (index, plan) in plans.into_iter().enumerate() {
+            match plan {
+                SitePlan::Crawl(site) => {
+                    let config = site.config(&self.config).map_err(|e| match e {
+                        SpiderError::InvalidInput(message) => self.input_error(site.line, message),
+                        e => self.input_error(site.line, e),
+                    })?;
+                    record(&journal, &site.url, JournalStatus::Pending, None, None);
+                    crawls.push((index, config, site));
+                }
+                SitePlan::Reuse(site, result) => {
+                    let result_file = domain_to_filename(&result.base_domain);
+                    record(
+                        &journal,
+                        &site.url,
+                        JournalStatus::Done,
+                        Some(result_file),
+                        None,
+                    );
+                    let outcome = SiteOutcome {
+                        url: site.url,
+                        tags: site.tags,
+                        result: Ok(*result),
+                        duration: Duration::ZERO,
+                        resumed: true,
+                    };
+                    outcomes.push((index, outcome));
+                }
+            }
+        }
+        let total_urls = crawls.len();
+
+        if !outcomes.is_empty() {
+            info!(
+                "Reusing {} results from an earlier run, {} URLs left to crawl",
+                outcomes.len(),
+                total_urls
+            );
+        }
         info!(
             "Starting crawl with {} concurrent sites",
             self.max_concurrent_sites
@@ -82,45 +395,337 @@ impl Loader {
         // Keep track of progress
         let processed = Arc::new(Mutex::new(0));
 
+        // Share one database between all sites of the batch
+        let sink = match &self.config.sqlite {
+            Some(path) => Some(Arc::new(SqliteSink::open(path)?)),
+            None => None,
+        };
+
         // Create futures for each URL
-        let futures = urls.into_iter().map(|url| {
-            let spider = Spider::new(self.config.clone());
+        let futures = crawls.into_iter().map(|(index, config, site)| {
+            let mut spider = Spider::new(config);
+            if let Some(sink) = &sink {
+                spider = spider.with_sqlite(sink.clone());
+            }
             let processed_clone = processed.clone();
+            let journal = journal.clone();
 
             async move {
-                let result = match spider.crawl(&url).await {
-                    Ok(result) => Ok(format!(
-                        "Successfully crawled {}: {} URLs found",
-                        url,
-                        result.urls.len()
-                    )),
-                    Err(e) => Err(format!("Failed to crawl {}: {}", url, e)),
-                };
+                record(&journal, &site.url, JournalStatus::Running, None, None);
+                let start = Instant::now();
+                let result = crawl_site(&spider, &site.url).await;
+                match &result {
+                    Ok(result) => {
+                        info!(
+                            "Successfully crawled {}: {} URLs found",
+                            site.url,
+                            result.urls.len()
+                        );
+                        let result_file = domain_to_filename(&result.base_domain);
+                        record(
+                            &journal,
+                            &site.url,
+                            JournalStatus::Done,
+                            Some(result_file),
+                            None,
+                        );
+                    }
+                    Err(e) => {
+                        warn!("Failed to crawl {}: {}", site.url, e);
+                        record(
+                            &journal,
+                            &site.url,
+                            JournalStatus::Failed,
+                            None,
+                            Some(e.to_string()),
+                        );
+                    }
+                }
 
                 // Update progress
                 let mut processed_count = processed_clone.lock().unwrap();
                 *processed_count += 1;
                 info!("Progress: {}/{} URLs crawled", *processed_count, total_urls);
 
-                result
+                let outcome = SiteOutcome {
+                    url: site.url,
+                    tags: site.tags,
+                    result,
+                    duration: start.elapsed(),
+                    resumed: false,
+                };
+                (index, outcome)
             }
         });
 
         // Process futures concurrently with a limit
-        let results = stream::iter(futures)
-            .buffer_unordered(self.max_concurrent_sites)
-            .collect::<Vec<_>>()
-            .await;
+        outcomes.extend(
+            stream::iter(futures)
+                .buffer_unordered(self.max_concurrent_sites)
+                .collect::<Vec<_>>()
+                .await,
+        );
+        outcomes.sort_by_key(|(index, _)| *index);
 
         info!("Completed crawling all URLs");
 
-        Ok(results)
+        Ok(BatchOutcome {
+            sites: outcomes.into_iter().map(|(_, outcome)| outcome).collect(),
+            warnings,
+        })
+    }
+}
+
+/// Normalize the URL of every site and drop sites that would share a result file
+///
+/// Sites are keyed by the result file name of their base domain, as the crawl
+/// saves it, so `example.com`, `https://www.example.com` and
+/// `https://example.com/` count as one site. The first line wins.
+fn prepare_sites(sites: Vec<SiteInput>) -> (Vec<SiteInput>, Vec<InputWarning>) {
+    let mut seen: HashMap<String, (usize, String)> = HashMap::new();
+    let mut prepared = Vec::new();
+    let mut warnings = Vec::new();
+
+    for mut site in sites {
+        let normalized =
+            normalize_input_url(&site.url).and_then(|url| Ok((extract_base_domain(&url)?, url)));
+        let (base_domain, url) = match normalized {
+            Ok(normalized) => normalized,
+            Err(e) => {
+                warnings.push(InputWarning {
+                    line: site.line,
+                    input: site.url,
+                    message: e.to_string(),
+                });
+                continue;
+            }
+        };
+
+        match seen.entry(result_file_name(&base_domain)) {
+            Entry::Occupied(first) => {
+                let (line, first_url) = first.get();
+                warnings.push(InputWarning {
+                    line: site.line,
+                    input: site.url,
+                    message: format!("same site as line {} ({})", line, first_url),
+                });
+            }
+            Entry::Vacant(entry) => {
+                entry.insert((site.line, url.clone()));
+                site.url = url;
+                prepared.push(site);
+            }
+        }
+    }
+
+    (prepared, warnings)
+}
+
+/// Plan to reuse the saved result of every site in `done`, mapping URLs to result files
+fn reuse_done(sites: Vec<SiteInput>, done: &HashMap<String, String>) -> Vec<SitePlan> {
+    sites
+        .into_iter()
+        .map(|site| {
+            let Some(result_file) = done.get(&site.url) else {
+                return SitePlan::Crawl(site);
+            };
+            let result = File::open(result_file)
+                .map_err(SpiderError::from)
+                .and_then(|file| Ok(serde_json::from_reader(BufReader::new(file))?));
+            match result {
+                Ok(result) => SitePlan::Reuse(site, Box::new(result)),
+                Err(e) => {
+                    warn!("Cannot reuse {}, crawling again: {}", result_file, e);
+                    SitePlan::Crawl(site)
+                }
+            }
+        })
+        .collect()
+}
+
+/// Record a state change in the journal, if one is kept
+///
+/// A journal that cannot be written is logged but does not stop the batch.
+fn record(
+    journal: &Option<Arc<BatchJournal>>,
+    url: &str,
+    status: JournalStatus,
+    result_file: Option<String>,
+    error: Option<String>,
+) {
+    if let Some(journal) = journal {
+        if let Err(e) = journal.record(url, status, result_file, error) {
+            warn!("Failed to write the batch journal: {}", e);
+        }
+    }
+}
+
+/// Crawl one site of a batch
+///
+/// A crawl without a single page returning a success status fails, but its
+/// result file has already been written and is left in place for inspection.
+async fn crawl_site(spider: &Spider, url: &str) -> Result<CrawlResult, SpiderError> {
+    spider.crawl(url).await.map_err(|e| {
+        e.downcast::<SpiderError>()
+            .unwrap_or_else(|e| SpiderError::Other(e.to_string()))
+    })
+}
+
+/// Read one URL per line
+fn read_text(file: File) -> Result<(Vec<SiteInput>, Vec<InputWarning>), (usize, String)> {
+    let mut sites = Vec::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| (i + 1, e.to_string()))?;
+        let trimmed = line.trim();
+
+        // Skip empty lines and comments
+        if !trimmed.is_empty() && !trimmed.starts_with('#') {
+            sites.push(SiteInput {
+                url: trimmed.to_string(),
+                line: i + 1,
+                ..Default::default()
+            });
+        }
+    }
+    Ok((sites, Vec::new()))
+}
+
+/// Read one JSON object per line
+fn read_json_lines(file: File) -> Result<(Vec<SiteInput>, Vec<InputWarning>), (usize, String)> {
+    let mut sites = Vec::new();
+    let mut warnings = Vec::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| (i + 1, e.to_string()))?;
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        let mut row = match serde_json::from_str(trimmed) {
+            Ok(row) => row,
+            Err(e) => {
+                warnings.push(InputWarning {
+                    line: i + 1,
+                    input: trimmed.to_string(),
+                    message: format!("not a JSON object: {}", e),
+                });
+                continue;
+            }
+        };
+        let Some(url) = take_url(&mut row) else {
+            warnings.push(missing_url(i + 1, trimmed));
+            continue;
+        };
+        sites.push(SiteInput::from_json(url, row, i + 1).map_err(|e| (i + 1, e))?);
+    }
+    Ok((sites, warnings))
+}
+
+/// Read a CSV file with a header row; empty cells keep the batch setting
+fn read_csv(file: File) -> Result<(Vec<SiteInput>, Vec<InputWarning>), (usize, String)> {
+    let mut reader = csv::ReaderBuilder::new()
+        .comment(Some(b'#'))
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(file);
+    let headers = reader.headers().map_err(|e| (1, e.to_string()))?.clone();
+    if !headers.iter().any(|header| header == "url") {
+        return Err((1, "missing \"url\" column".to_string()));
+    }
+
+    let mut sites = Vec::new();
+    let mut warnings = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| {
+            let line = e.position().map_or(0, |position| position.line() as usize);
+            (line, e.to_string())
+        })?;
+        let line = record
+            .position()
+            .map_or(0, |position| position.line() as usize);
+        if record.iter().all(str::is_empty) {
+            continue;
+        }
+
+        let mut row = headers
+            .iter()
+            .zip(record.iter())
+            .filter(|(_, cell)| !cell.is_empty())
+            .map(|(header, cell)| (header.to_string(), csv_value(header, cell)))
+            .collect();
+        let Some(url) = take_url(&mut row) else {
+            let input = record.iter().collect::<Vec<_>>().join(",");
+            warnings.push(missing_url(line, &input));
+            continue;
+        };
+        sites.push(SiteInput::from_json(url, row, line).map_err(|e| (line, e))?);
+    }
+    Ok((sites, warnings))
+}
+
+/// Remove the `url` of an input row, if it has a non-empty one
+fn take_url(row: &mut Map<String, Value>) -> Option<String> {
+    match row.remove("url") {
+        Some(Value::String(url)) if !url.trim().is_empty() => Some(url.trim().to_string()),
+        _ => None,
+    }
+}
+
+/// Warning for an input row without a URL
+fn missing_url(line: usize, input: &str) -> InputWarning {
+    InputWarning {
+        line,
+        input: input.to_string(),
+        message: "missing \"url\"".to_string(),
+    }
+}
+
+/// Convert a CSV cell to the JSON value expected for its column
+fn csv_value(column: &str, cell: &str) -> Value {
+    let items = || {
+        cell.split(';')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+    };
+
+    if CSV_JSON_COLUMNS.contains(&column) {
+        serde_json::from_str(cell).unwrap_or_else(|_| Value::String(cell.to_string()))
+    } else if CSV_LIST_COLUMNS.contains(&column) {
+        Value::Array(
+            items()
+                .map(|item| Value::String(item.to_string()))
+                .collect(),
+        )
+    } else if CSV_MAP_COLUMNS.contains(&column) {
+        Value::Object(
+            items()
+                .map(|item| {
+                    let (key, value) = item.split_once('=').unwrap_or((item, ""));
+                    (key.trim().to_string(), csv_scalar(column, value.trim()))
+                })
+                .collect(),
+        )
+    } else {
+        csv_scalar(column, cell)
+    }
+}
+
+/// A number or boolean if the text is one and the column is not a text column, otherwise a string
+fn csv_scalar(column: &str, text: &str) -> Value {
+    if CSV_TEXT_COLUMNS.contains(&column) {
+        return Value::String(text.to_string());
+    }
+    match serde_json::from_str(text) {
+        Ok(value @ (Value::Number(_) | Value::Bool(_))) => value,
+        _ => Value::String(text.to_string()),
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::spider::summary::BatchTotals;
+    use std::collections::BTreeMap;
     use std::io::Write;
     use tempfile::NamedTempFile;
 
@@ -130,7 +735,7 @@ mod tests {
         let path = temp_file.path().to_str().unwrap();
 
         let loader = Loader::new(SpiderConfig::default(), 30, path);
-        let urls = loader.load_urls().unwrap();
+        let (urls, _) = loader.load_urls().unwrap();
 
         assert_eq!(urls.len(), 0);
     }
@@ -142,17 +747,187 @@ mod tests {
             let mut file = temp_file.reopen().unwrap();
             writeln!(file, "https://example.com").unwrap();
             writeln!(file, "# Comment line").unwrap();
-            writeln!(file, "").unwrap();
+            writeln!(file).unwrap();
             writeln!(file, "https://test.com").unwrap();
         }
 
         let path = temp_file.path().to_str().unwrap();
         let loader = Loader::new(SpiderConfig::default(), 30, path);
-        let urls = loader.load_urls().unwrap();
+        let (urls, _) = loader.load_urls().unwrap();
 
         assert_eq!(urls.len(), 2);
-        assert_eq!(urls[0], "https://example.com");
-        assert_eq!(urls[1], "https://test.com");
+        assert_eq!(urls[0].url, "https://example.com");
+        assert_eq!(urls[1].url, "https://test.com");
+    }
+
+    fn input_file(extension: &str, lines: &[&str]) -> tempfile::TempPath {
+        let mut file = tempfile::Builder::new()
+            .suffix(extension)
+            .tempfile()
+            .unwrap();
+        for line in lines {
+            writeln!(file, "{}", line).unwrap();
+        }
+        file.into_temp_path()
+    }
+
+    #[test]
+    fn test_load_csv_with_overrides() {
+        let path = input_file(
+            ".csv",
+            &[
+                "url,tags,max_depth,add_skip_patterns,min_request_delay_ms,template_quotas",
+                "https://a.com,client;retail,2,/shop/;/cart/,500,/p/{id}=3",
+                "# paused",
+                "\"https://b.com/?q=a,b\",,,,,",
+            ],
+        );
+        let loader = Loader::new(SpiderConfig::default(), 30, path.to_str().unwrap());
+        let (sites, _) = loader.load_urls().unwrap();
+
+        assert_eq!(sites.len(), 2);
+        assert_eq!(sites[0].tags, vec!["client", "retail"]);
+        assert_eq!(sites[0].overrides.max_depth, Some(2));
+        assert_eq!(
+            sites[0].overrides.add_skip_patterns,
+            Some(vec!["/shop/".to_string(), "/cart/".to_string()])
+        );
+        assert_eq!(sites[1].url, "https://b.com/?q=a,b");
+        assert_eq!(sites[1].overrides, ConfigOverrides::default());
+
+        let config = sites[0].config(&SpiderConfig::default()).unwrap();
+        assert_eq!(config.max_depth, 2);
+        assert_eq!(config.min_request_delay_ms, 500);
+        assert_eq!(config.template_quotas.len(), 1);
+        assert_eq!(config.tags, vec!["client", "retail"]);
+    }
+
+    #[test]
+    fn test_load_csv_text_values() {
+        let path = input_file(
+            ".csv",
+            &[
+                "url,headers,template_quotas",
+                "https://a.com,X-Api-Version=2;X-Debug=true,/p/{id}=3",
+            ],
+        );
+        let loader = Loader::new(SpiderConfig::default(), 30, path.to_str().unwrap());
+        let (sites, _) = loader.load_urls().unwrap();
+
+        let headers = sites[0].overrides.headers.as_ref().unwrap();
+        assert_eq!(headers["X-Api-Version"], "2");
+        assert_eq!(headers["X-Debug"], "true");
+        assert_eq!(
+            sites[0].overrides.template_quotas,
+            Some(BTreeMap::from([("/p/{id}".to_string(), 3)]))
+        );
+    }
+
+    #[test]
+    fn test_load_csv_json_columns() {
+        let path = input_file(
+            ".csv",
+            &[
+                "url,scope_rules,traps",
+                r#"https://a.com,"[{""name"": ""blog"", ""action"": ""exclude"", ""target"": ""path"", ""prefix"": ""/blog/""}]","{""max_query_params"": 2}""#,
+            ],
+        );
+        let loader = Loader::new(SpiderConfig::default(), 30, path.to_str().unwrap());
+        let (sites, _) = loader.load_urls().unwrap();
+
+        let config = sites[0].config(&SpiderConfig::default()).unwrap();
+        assert_eq!(config.scope_rules.len(), 1);
+        assert_eq!(config.scope_rules[0].name, "blog");
+        assert_eq!(config.traps.max_query_params, 2);
+    }
+
+    #[test]
+    fn test_load_json_lines_with_overrides() {
+        let path = input_file(
+            ".jsonl",
+            &[
+                r#"{"url": "https://a.com", "tags": ["news"], "max_depth": 2, "strategy": "breadth_first"}"#,
+                "",
+                r#"{"url": "https://b.com"}"#,
+            ],
+        );
+        let loader = Loader::new(SpiderConfig::default(), 30, path.to_str().unwrap());
+        let (sites, _) = loader.load_urls().unwrap();
+        assert_eq!(sites.len(), 2);
+        assert_eq!(sites[0].tags, vec!["news"]);
+        assert_eq!(
+            sites[0].overrides.strategy.as_deref(),
+            Some("breadth_first")
+        );
+
+        let typo = input_file(
+            ".jsonl",
+            &["", r#"{"url": "https://a.com", "max_dept": 2}"#],
+        );
+        let loader = Loader::new(SpiderConfig::default(), 30, typo.to_str().unwrap());
+        let error = loader.load_urls().unwrap_err().to_string();
+        assert!(
+            error.contains(":2:") && error.contains("max_dept"),
+            "{}",
+            error
+        );
+
+        let batch_only = input_file(".jsonl", &[r#"{"url": "https://a.com", "sqlite": "a.db"}"#]);
+        let loader = Loader::new(SpiderConfig::default(), 30, batch_only.to_str().unwrap());
+        let error = loader.load_urls().unwrap_err().to_string();
+        assert!(
+            error.contains(":1: sqlite can only be set for the whole batch"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_load_rows_without_url() {
+        let path = input_file(
+            ".jsonl",
+            &[
+                r#"{"url": "https://a.com"}"#,
+                r#"{"url": "https://b.com", "max_depth": 2"#,
+                r#"{"tags": ["news"]}"#,
+                r#"["https://c.com"]"#,
+                r#"{"url": "https://d.com"}"#,
+            ],
+        );
+        let loader = Loader::new(SpiderConfig::default(), 30, path.to_str().unwrap());
+        let (sites, warnings) = loader.load_urls().unwrap();
+        assert_eq!(sites.len(), 2);
+        assert_eq!(sites[1].line, 5);
+        let lines: Vec<usize> = warnings.iter().map(|warning| warning.line).collect();
+        assert_eq!(lines, vec![2, 3, 4]);
+        assert_eq!(warnings[1].input, r#"{"tags": ["news"]}"#);
+        assert_eq!(warnings[1].message, "missing \"url\"");
+
+        let path = input_file(".csv", &["url,tags", "https://a.com,x", ",y"]);
+        let loader = Loader::new(SpiderConfig::default(), 30, path.to_str().unwrap());
+        let (sites, warnings) = loader.load_urls().unwrap();
+        assert_eq!(sites.len(), 1);
+        assert_eq!(warnings[0].line, 3);
+        assert_eq!(warnings[0].input, ",y");
+    }
+
+    #[tokio::test]
+    async fn test_invalid_site_config_stops_batch() {
+        let path = input_file(
+            ".jsonl",
+            &[
+                r#"{"url": "https://a.com"}"#,
+                r#"{"url": "https://b.com", "max_concurrent": 0}"#,
+            ],
+        );
+        let loader = Loader::new(SpiderConfig::default(), 30, path.to_str().unwrap());
+        let error = loader.crawl_all().await.unwrap_err();
+        assert!(matches!(error, SpiderError::InvalidInput(_)));
+        assert!(
+            error.to_string().contains(":2: max_concurrent"),
+            "{}",
+            error
+        );
     }
 
     #[test]
@@ -162,4 +937,130 @@ mod tests {
 
         assert!(result.is_err());
     }
+
+    fn planned(plans: &[SitePlan]) -> Vec<(&str, bool)> {
+        plans
+            .iter()
+            .map(|plan| match plan {
+                SitePlan::Crawl(site) => (site.url.as_str(), false),
+                SitePlan::Reuse(site, _) => (site.url.as_str(), true),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_plan_resume_and_retry_failed() {
+        let dir = tempfile::tempdir().unwrap();
+        let result_file = dir.path().join("a_com.json");
+        std::fs::write(
+            &result_file,
+            serde_json::to_string(&CrawlResult::fixture(Vec::new())).unwrap(),
+        )
+        .unwrap();
+        let result_file = result_file.to_str().unwrap().to_string();
+        let sites = || {
+            [
+                "https://a.com",
+                "https://b.com",
+                "https://c.com",
+                "https://d.com",
+            ]
+            .map(|url| SiteInput {
+                url: url.to_string(),
+                ..Default::default()
+            })
+            .to_vec()
+        };
+
+        let journal_path = dir.path().join("journal.jsonl");
+        let journal = BatchJournal::create(&journal_path).unwrap();
+        let done = Some(result_file.clone());
+        journal
+            .record("https://a.com", JournalStatus::Done, done, None)
+            .unwrap();
+        journal
+            .record("https://b.com", JournalStatus::Running, None, None)
+            .unwrap();
+        let missing = Some("missing.json".to_string());
+        journal
+            .record("https://c.com", JournalStatus::Done, missing, None)
+            .unwrap();
+
+        let loader = Loader::default()
+            .with_journal(&journal_path)
+            .with_mode(BatchMode::Resume);
+        assert_eq!(
+            planned(&loader.plan(sites()).unwrap()),
+            vec![
+                ("https://a.com", true),
+                ("https://b.com", false),
+                ("https://c.com", false),
+                ("https://d.com", false),
+            ]
+        );
+        let no_journal = Loader::default().with_mode(BatchMode::Resume);
+        assert!(no_journal.plan(sites()).is_err());
+
+        let summary_path = dir.path().join("batch_summary.json");
+        std::fs::write(
+            &summary_path,
+            serde_json::json!({
+                "sites": [
+                    {"url": "https://a.com", "tags": [], "status": "completed", "error": null,
+                     "duration_ms": 5, "base_domain": "a.com", "result_file": result_file,
+                     "found_urls": 0, "pages": 0, "skipped_urls": 0, "unreachable_urls": 0,
+                     "stats": {}},
+                    {"url": "https://b.com", "tags": [], "status": "failed", "error": "down",
+                     "duration_ms": 5, "base_domain": null, "result_file": null,
+                     "found_urls": 0, "pages": 0, "skipped_urls": 0, "unreachable_urls": 0,
+                     "stats": {}}
+                ],
+                "totals": BatchTotals::default(),
+            })
+            .to_string(),
+        )
+        .unwrap();
+        let loader = Loader::default().with_mode(BatchMode::RetryFailed(summary_path));
+        assert_eq!(
+            planned(&loader.plan(sites()).unwrap()),
+            vec![("https://a.com", true), ("https://b.com", false)]
+        );
+    }
+
+    #[test]
+    fn test_prepare_sites() {
+        let sites = [
+            "example.com",
+            "https://www.example.com",
+            "https://example.com/",
+            "http://shop.example.com/#top",
+            "ftp://example.org",
+            "https://",
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, url)| SiteInput {
+            url: url.to_string(),
+            line: i + 1,
+            ..Default::default()
+        })
+        .collect();
+
+        let (sites, warnings) = prepare_sites(sites);
+        let urls: Vec<&str> = sites.iter().map(|site| site.url.as_str()).collect();
+        assert_eq!(
+            urls,
+            vec!["https://example.com/", "http://shop.example.com/"]
+        );
+        assert_eq!(sites[1].line, 4);
+
+        let lines: Vec<usize> = warnings.iter().map(|warning| warning.line).collect();
+        assert_eq!(lines, vec![2, 3, 5, 6]);
+        assert_eq!(warnings[0].input, "https://www.example.com");
+        assert_eq!(
+            warnings[0].message,
+            "same site as line 1 (https://example.com/)"
+        );
+        assert!(warnings[2].message.contains("Unsupported scheme"));
+    }
 }
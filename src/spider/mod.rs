@@ -1,9 +1,27 @@
+pub mod charset;
 pub mod config;
+pub mod contacts;
+pub mod content;
+pub mod dedup;
 pub mod crawler;
 pub mod error;
+pub mod external;
+pub mod graph;
+pub mod journal;
 pub mod loader;
 pub mod network;
+pub mod report;
+pub mod sitemap;
+pub mod sqlite;
+pub mod store;
+pub mod strategy;
+pub mod structured;
+pub mod summary;
+pub mod templates;
+pub mod text;
+pub mod traps;
 pub mod utils;
+pub mod warc;
 
 pub use config::SpiderConfig;
 pub use crawler::CrawlResult;
@@ -1,60 +1,358 @@
-use crate::spider::config::SpiderConfig;
+use crate::spider::charset::{decode_html, DecodedHtml};
+use crate::spider::config::{RefererPolicy, SpiderConfig, UserAgentMode};
+use crate::spider::content::ContentKind;
 use crate::spider::error::SpiderError;
+use crate::spider::utils::robots_product_token;
+use crate::spider::warc::{HttpExchange, WarcOutput, WarcRecorder, WarcWriter};
 use log::debug;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
 use rand::Rng;
-use reqwest::{Client, Response};
-use std::time::Duration;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Client, Method, RequestBuilder, Response};
+use robotstxt::DefaultMatcher;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, OnceCell};
 use url::Url;
 
+/// Robots.txt body that disallows everything, used when robots.txt is unavailable due to a server error
+const DISALLOW_ALL_ROBOTS: &str = "User-agent: *\nDisallow: /\n";
+
+/// Maximum number of redirects followed for a request
+const MAX_REDIRECTS: usize = 10;
+
+/// Redirect statuses followed to the `Location` header
+const REDIRECT_STATUSES: &[u16] = &[301, 302, 303, 307, 308];
+
+/// Build a header map from name/value pairs
+fn build_header_map(headers: &[(String, String)]) -> Result<HeaderMap, SpiderError> {
+    let mut map = HeaderMap::new();
+    for (name, value) in headers {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|e| SpiderError::HttpClient(format!("Invalid header name {}: {}", name, e)))?;
+        let value = HeaderValue::from_str(value).map_err(|e| {
+            SpiderError::HttpClient(format!("Invalid value for header {}: {}", name, e))
+        })?;
+        map.insert(name, value);
+    }
+    Ok(map)
+}
+
+/// The lowercased Content-Type header, empty if missing
+fn content_type(headers: &HeaderMap) -> String {
+    headers
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("")
+        .to_lowercase()
+}
+
+/// The raw body of a response
+pub struct FetchedBody {
+    /// The lowercased Content-Type header, empty if missing
+    pub content_type: String,
+
+    /// The response body
+    pub bytes: Bytes,
+}
+
+/// A fully read response
+pub struct FetchedPage {
+    /// The HTTP status code
+    pub status: u16,
+
+    /// The URL after redirects
+    pub final_url: String,
+
+    /// The response headers
+    pub headers: HeaderMap,
+
+    /// The lowercased Content-Type header, empty if missing
+    pub content_type: String,
+
+    /// The response body, empty if it was not read
+    pub bytes: Bytes,
+
+    /// Size of the body in bytes, taken from Content-Length if it was not read
+    pub content_length: usize,
+
+    /// Time taken to send the request and read the body
+    pub response_time: Duration,
+
+    /// When the request was sent
+    pub fetched_at: DateTime<Utc>,
+}
+
+/// A request as sent, kept for WARC records
+struct SentRequest {
+    /// The HTTP method
+    method: Method,
+
+    /// The requested URL
+    url: String,
+
+    /// The URL whose redirect led to this request, if redirects were followed hop by hop
+    redirected_from: Option<String>,
+
+    /// The headers sent, including the client's default headers
+    headers: HeaderMap,
+
+    /// When the request was sent
+    sent_at: DateTime<Utc>,
+
+    /// When the request was sent, for measuring the response time
+    start: Instant,
+}
+
+/// Status line and headers of a response, kept after its body is read
+struct ResponseHead {
+    /// The HTTP status code
+    status: u16,
+
+    /// The HTTP version, e.g. `HTTP/1.1`
+    version: String,
+
+    /// The URL after redirects
+    url: String,
+
+    /// The response headers
+    headers: HeaderMap,
+}
+
+impl ResponseHead {
+    /// Copy the status line and headers of a response
+    fn of(response: &Response) -> Self {
+        Self {
+            status: response.status().as_u16(),
+            version: format!("{:?}", response.version()),
+            url: response.url().to_string(),
+            headers: response.headers().clone(),
+        }
+    }
+}
+
+/// Result of checking whether a link is alive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkCheck {
+    /// The HTTP method of the last request made
+    pub method: String,
+
+    /// The HTTP status code, if a response was received
+    pub status: Option<u16>,
+
+    /// The URL after redirects, if a response was received
+    pub final_url: Option<String>,
+
+    /// Whether the link returned a success status
+    pub alive: bool,
+
+    /// The network error, if no response was received
+    pub error: Option<String>,
+}
+
 /// Handles HTTP client creation and network requests with anti-bot detection measures
 pub struct NetworkClient {
     /// The HTTP client
     client: Client,
+
+    /// HTTP client that does not follow redirects, so every hop can be archived
+    archive_client: Client,
     
     /// Spider configuration
     config: SpiderConfig,
+
+    /// Headers sent with every request
+    default_headers: HeaderMap,
+
+    /// Extra headers per host
+    host_headers: HashMap<String, HeaderMap>,
+
+    /// Cached robots.txt bodies per origin, `None` when there are no rules
+    robots_cache: Mutex<HashMap<String, Arc<OnceCell<Option<String>>>>>,
+
+    /// Recorder of every fetch, while WARC output is active
+    warc: Mutex<Option<WarcRecorder>>,
 }
 
 impl NetworkClient {
     /// Create a new network client with the given configuration
     pub fn new(config: SpiderConfig) -> Result<Self, SpiderError> {
-        // Create a client with redirect policy, timeouts
-        let client = Client::builder()
-            .redirect(reqwest::redirect::Policy::limited(10))
-            .timeout(Duration::from_secs(30))
-            // Apply common browser-like headers to avoid detection
-            .default_headers({
-                let mut headers = reqwest::header::HeaderMap::new();
-                headers.insert(
-                    reqwest::header::ACCEPT,
-                    "text/html,application/xhtml+xml,application/xml;q=0.9,image/webp,*/*;q=0.8"
-                        .parse()
-                        .unwrap(),
-                );
-                headers.insert(
-                    reqwest::header::ACCEPT_LANGUAGE,
-                    "en-US,en;q=0.5".parse().unwrap(),
-                );
-                headers.insert(
-                    reqwest::header::ACCEPT_ENCODING,
-                    "gzip, deflate, br".parse().unwrap(),
-                );
-                headers.insert(
-                    "DNT", "1".parse().unwrap()
-                );
-                headers
-            })
-            .build()
-            .map_err(|e| SpiderError::HttpClient(format!("Failed to build HTTP client: {}", e)))?;
+        let host_headers = config
+            .host_headers
+            .iter()
+            .map(|(host, headers)| Ok((host.to_lowercase(), build_header_map(headers)?)))
+            .collect::<Result<HashMap<_, _>, SpiderError>>()?;
+
+        // Create a client with redirect policy, timeouts and the configured default headers
+        let default_headers = build_header_map(&config.default_headers)?;
+        let build_client = |policy| {
+            Client::builder()
+                .redirect(policy)
+                .timeout(Duration::from_secs(30))
+                .default_headers(default_headers.clone())
+                .build()
+                .map_err(|e| SpiderError::HttpClient(format!("Failed to build HTTP client: {}", e)))
+        };
+        let client = build_client(reqwest::redirect::Policy::limited(MAX_REDIRECTS))?;
+        let archive_client = build_client(reqwest::redirect::Policy::none())?;
+
+        Ok(Self {
+            client,
+            archive_client,
+            config,
+            default_headers,
+            host_headers,
+            robots_cache: Mutex::new(HashMap::new()),
+            warc: Mutex::new(None),
+        })
+    }
+
+    /// Start recording every fetch with a WARC writer
+    pub async fn start_warc(&self, writer: WarcWriter) {
+        *self.warc.lock().await = Some(WarcRecorder::spawn(writer));
+    }
+
+    /// Stop recording fetches, closing the WARC files and writing the CDX index
+    pub async fn finish_warc(&self) -> Result<Option<WarcOutput>, SpiderError> {
+        let recorder = self.warc.lock().await.take();
+        match recorder {
+            Some(recorder) => recorder.finish().await.map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Check whether fetches are being recorded
+    async fn is_archiving(&self) -> bool {
+        self.warc.lock().await.is_some()
+    }
+
+    /// Send the request `build` makes for a URL, keeping what was sent for WARC records
+    ///
+    /// While archiving, redirects are followed here one hop at a time, so each
+    /// redirect response is recorded under the URL that returned it. The
+    /// returned request is then the last hop.
+    async fn send(
+        &self,
+        url: &str,
+        referrer: Option<&str>,
+        build: impl Fn(&str) -> Result<RequestBuilder, SpiderError>,
+    ) -> Result<(SentRequest, Response), SpiderError> {
+        let failed = |e: reqwest::Error| {
+            SpiderError::NetworkError(format!("Failed to fetch {}: {}", url, e))
+        };
+        if !self.is_archiving().await {
+            return self
+                .send_once(&self.client, build(url)?, None)
+                .await
+                .map_err(failed);
+        }
+
+        let mut hop_url = url.to_string();
+        let mut redirected_from = None;
+        for _ in 0..=MAX_REDIRECTS {
+            let (sent, response) = self
+                .send_once(
+                    &self.archive_client,
+                    build(&hop_url)?,
+                    redirected_from.take(),
+                )
+                .await
+                .map_err(failed)?;
+            let location = Some(response.status().as_u16())
+                .filter(|status| REDIRECT_STATUSES.contains(status))
+                .and_then(|_| response.headers().get(reqwest::header::LOCATION))
+                .and_then(|location| location.to_str().ok())
+                .and_then(|location| response.url().join(location).ok());
+            let Some(location) = location else {
+                return Ok((sent, response));
+            };
+
+            let head = ResponseHead::of(&response);
+            let body = response.bytes().await.unwrap_or_default();
+            self.archive(&sent, &head, body, referrer).await;
+            redirected_from = Some(hop_url);
+            hop_url = location.to_string();
+        }
+
+        Err(SpiderError::NetworkError(format!(
+            "Failed to fetch {}: more than {} redirects",
+            url, MAX_REDIRECTS
+        )))
+    }
+
+    /// Send a single request with a client
+    async fn send_once(
+        &self,
+        client: &Client,
+        request: RequestBuilder,
+        redirected_from: Option<String>,
+    ) -> Result<(SentRequest, Response), reqwest::Error> {
+        let request = request.build()?;
+
+        // The client adds its default headers when sending, unless overridden
+        let mut headers = self.default_headers.clone();
+        for (name, value) in request.headers() {
+            headers.insert(name.clone(), value.clone());
+        }
+        let sent = SentRequest {
+            method: request.method().clone(),
+            url: request.url().to_string(),
+            redirected_from,
+            headers,
+            sent_at: Utc::now(),
+            start: Instant::now(),
+        };
 
-        Ok(Self { client, config })
+        let response = client.execute(request).await?;
+        Ok((sent, response))
+    }
+
+    /// Record an exchange in the WARC files, if WARC output is active
+    async fn archive(
+        &self,
+        sent: &SentRequest,
+        head: &ResponseHead,
+        body: Bytes,
+        referrer: Option<&str>,
+    ) {
+        let warc = self.warc.lock().await;
+        let Some(recorder) = warc.as_ref() else {
+            return;
+        };
+
+        recorder
+            .record(HttpExchange {
+                method: sent.method.to_string(),
+                url: sent.url.clone(),
+                redirected_from: sent.redirected_from.clone(),
+                referrer: referrer.map(str::to_string),
+                request_headers: sent.headers.clone(),
+                version: head.version.clone(),
+                status: head.status,
+                response_headers: head.headers.clone(),
+                body,
+                fetched_at: sent.sent_at,
+                response_time: sent.start.elapsed(),
+            })
+            .await;
     }
     
-    /// Get a random user agent from the config
-    fn get_random_user_agent(&self) -> String {
-        let user_agents = &self.config.user_agents;
-        let idx = rand::thread_rng().gen_range(0..user_agents.len());
-        user_agents[idx].clone()
+    /// Get the user agent for the next request
+    ///
+    /// In identify mode this is always the declared bot user agent, otherwise a
+    /// random one from the config.
+    fn get_user_agent(&self) -> String {
+        match &self.config.user_agent_mode {
+            UserAgentMode::Identify(user_agent) => user_agent.clone(),
+            UserAgentMode::Rotate => {
+                let user_agents = &self.config.user_agents;
+                let idx = rand::thread_rng().gen_range(0..user_agents.len());
+                user_agents[idx].clone()
+            }
+        }
     }
     
     /// Add random delay between requests
@@ -68,31 +366,55 @@ impl NetworkClient {
         tokio::time::sleep(Duration::from_millis(delay_ms)).await;
     }
     
-    /// Fetch a URL with anti-bot measures
-    pub async fn fetch(&self, url: &str) -> Result<Response, SpiderError> {
-        let parsed_url = Url::parse(url)
-            .map_err(|e| SpiderError::UrlParse(e))?;
-        
-        // Apply delay before making the request
-        self.apply_delay().await;
-        
-        // Get a random user agent
-        let user_agent = self.get_random_user_agent();
+    /// Build a request with the configured headers
+    ///
+    /// `referrer` is the page the URL was linked from, if any.
+    fn build_request(
+        &self,
+        method: Method,
+        url: &str,
+        referrer: Option<&str>,
+    ) -> Result<RequestBuilder, SpiderError> {
+        let parsed_url = Url::parse(url).map_err(SpiderError::UrlParse)?;
         
         // Start with a basic request
-        let mut request = self.client.get(url);
+        let mut request = self.client.request(method, url);
         
         // Set the user agent for this specific request
-        request = request.header(reqwest::header::USER_AGENT, user_agent);
+        request = request.header(reqwest::header::USER_AGENT, self.get_user_agent());
         
-        // Add a referer header
-        // Use a plausible referer (Google, Bing, or current domain)
-        let domain = parsed_url.host_str().unwrap_or("example.com");
-        let referer = format!("{}://{}/", parsed_url.scheme(), domain);
-        request = request.header(reqwest::header::REFERER, referer);
-            
-        // Include an empty cookies header
-        request = request.header(reqwest::header::COOKIE, "");
+        // Add a referer header according to the configured policy
+        let referer = match self.config.referer_policy {
+            RefererPolicy::None => None,
+            RefererPolicy::Origin => referrer
+                .and_then(|r| Url::parse(r).ok())
+                .map(|r| format!("{}/", r.origin().ascii_serialization())),
+            RefererPolicy::LinkingPage => referrer.map(|r| r.to_string()),
+        };
+        if let Some(referer) = referer {
+            request = request.header(reqwest::header::REFERER, referer);
+        }
+
+        // Add host specific headers
+        if let Some(headers) = parsed_url
+            .host_str()
+            .and_then(|host| self.host_headers.get(&host.to_lowercase()))
+        {
+            request = request.headers(headers.clone());
+        }
+
+        Ok(request)
+    }
+
+    /// Fetch a URL, sending the configured headers
+    ///
+    /// `referrer` is the page the URL was linked from, if any. Non-success
+    /// statuses are returned as errors.
+    pub async fn fetch(&self, url: &str, referrer: Option<&str>) -> Result<Response, SpiderError> {
+        let request = self.build_request(Method::GET, url, referrer)?;
+        
+        // Apply delay before making the request
+        self.apply_delay().await;
         
         // Send the request
         let response = request.send().await
@@ -107,29 +429,215 @@ impl NetworkClient {
             
         Ok(response)
     }
+
+    /// Fetch a URL and read the whole response, whatever its status
+    ///
+    /// The response time covers sending the request and reading the body, but
+    /// not the politeness delay.
+    pub async fn fetch_page(&self, url: &str, referrer: Option<&str>) -> Result<FetchedPage, SpiderError> {
+        // Apply delay before making the request
+        self.apply_delay().await;
+
+        let (sent, response) = self
+            .send(url, referrer, |url| {
+                self.build_request(Method::GET, url, referrer)
+            })
+            .await?;
+
+        let head = ResponseHead::of(&response);
+        let body = if self.should_read_body(&head).await {
+            self.read_body(response).await?
+        } else {
+            debug!("Not reading the body of {}", head.url);
+            FetchedBody {
+                content_type: content_type(&head.headers),
+                bytes: Bytes::new(),
+            }
+        };
+        let response_time = sent.start.elapsed();
+        self.archive(&sent, &head, body.bytes.clone(), referrer).await;
+
+        let content_length = if body.bytes.is_empty() {
+            head.headers
+                .get(reqwest::header::CONTENT_LENGTH)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.trim().parse().ok())
+                .unwrap_or(0)
+        } else {
+            body.bytes.len()
+        };
+
+        Ok(FetchedPage {
+            status: head.status,
+            final_url: head.url,
+            headers: head.headers,
+            content_type: body.content_type,
+            bytes: body.bytes,
+            content_length,
+            response_time,
+            fetched_at: sent.sent_at,
+        })
+    }
+
+    /// Check whether the body of a response is needed
+    ///
+    /// Bodies of documents we cannot extract links from, such as PDFs, images
+    /// and archives, are only read when they are archived or stored.
+    async fn should_read_body(&self, head: &ResponseHead) -> bool {
+        // Other is decided from the content type alone, so no body is needed here
+        ContentKind::detect(&content_type(&head.headers), &[]) != ContentKind::Other
+            || self.config.content_store.is_some()
+            || self.is_archiving().await
+    }
+
+    /// Check whether a URL is alive with a HEAD request, falling back to GET
+    ///
+    /// Some servers reject or mishandle HEAD, so any HEAD failure is retried with
+    /// GET. No politeness delay is applied, as checked URLs are on other sites.
+    pub async fn check_link(&self, url: &str, referrer: Option<&str>) -> LinkCheck {
+        let mut result = self.send_check(Method::HEAD, url, referrer).await;
+        if !result.alive {
+            debug!("HEAD failed for {}, retrying with GET", url);
+            result = self.send_check(Method::GET, url, referrer).await;
+        }
+        result
+    }
+
+    /// Send a single request for `check_link`
+    ///
+    /// The body of a GET is only read when WARC output is active.
+    async fn send_check(&self, method: Method, url: &str, referrer: Option<&str>) -> LinkCheck {
+        let response = self
+            .send(url, referrer, |url| {
+                self.build_request(method.clone(), url, referrer)
+            })
+            .await
+            .map_err(|e| e.to_string());
+
+        match response {
+            Ok((sent, response)) => {
+                let head = ResponseHead::of(&response);
+                if self.is_archiving().await {
+                    let body = match method {
+                        Method::HEAD => Bytes::new(),
+                        _ => response.bytes().await.unwrap_or_default(),
+                    };
+                    self.archive(&sent, &head, body, referrer).await;
+                }
+
+                LinkCheck {
+                    method: method.to_string(),
+                    status: Some(head.status),
+                    alive: (200..300).contains(&head.status),
+                    final_url: Some(head.url),
+                    error: None,
+                }
+            }
+            Err(error) => LinkCheck {
+                method: method.to_string(),
+                status: None,
+                final_url: None,
+                alive: false,
+                error: Some(error),
+            },
+        }
+    }
+
+    /// Check whether robots.txt allows fetching a URL
+    ///
+    /// Always true unless the spider identifies itself as a bot. Rules are matched
+    /// against the product token of the declared user agent.
+    pub async fn is_allowed_by_robots(&self, url: &str) -> bool {
+        let Some(user_agent) = self.config.robots_user_agent() else {
+            return true;
+        };
+
+        let parsed_url = match Url::parse(url) {
+            Ok(parsed_url) => parsed_url,
+            Err(_) => return true,
+        };
+        let origin = parsed_url.origin().ascii_serialization();
+
+        // Download outside the cache lock, so only requests to the same origin wait
+        let cell = self
+            .robots_cache
+            .lock()
+            .await
+            .entry(origin.clone())
+            .or_default()
+            .clone();
+        let robots = cell
+            .get_or_init(|| self.fetch_robots(&origin, user_agent))
+            .await;
+
+        match robots {
+            Some(robots) => DefaultMatcher::default().one_agent_allowed_by_robots(
+                robots,
+                robots_product_token(user_agent),
+                url,
+            ),
+            None => true,
+        }
+    }
+
+    /// Download robots.txt for an origin
+    async fn fetch_robots(&self, origin: &str, user_agent: &str) -> Option<String> {
+        let robots_url = format!("{}/robots.txt", origin);
+        let request = |url: &str| {
+            Ok(self
+                .client
+                .get(url)
+                .header(reqwest::header::USER_AGENT, user_agent))
+        };
+        let (sent, response) = match self.send(&robots_url, None, request).await {
+            Ok(sent) => sent,
+            Err(e) => {
+                debug!("Failed to fetch {}: {}", robots_url, e);
+                return None;
+            }
+        };
+
+        let head = ResponseHead::of(&response);
+        let body = response.bytes().await.ok();
+        if let Some(body) = &body {
+            self.archive(&sent, &head, body.clone(), None).await;
+        }
+
+        if (500..600).contains(&head.status) {
+            debug!("Server error for {}, assuming full disallow", robots_url);
+            return Some(DISALLOW_ALL_ROBOTS.to_string());
+        }
+
+        if !(200..300).contains(&head.status) {
+            return None;
+        }
+
+        body.map(|body| String::from_utf8_lossy(&body).into_owned())
+    }
     
-    /// Extract HTML content from a response, handling various content types
-    pub async fn extract_html(&self, response: Response) -> Result<String, SpiderError> {
-        // Check content type
-        let content_type = response.headers()
-            .get(reqwest::header::CONTENT_TYPE)
-            .and_then(|v| v.to_str().ok())
-            .unwrap_or("")
-            .to_lowercase();
-            
+    /// Read the body of a response along with its content type
+    pub async fn read_body(&self, response: Response) -> Result<FetchedBody, SpiderError> {
+        let content_type = content_type(response.headers());
+
+        let bytes = response.bytes().await
+            .map_err(|e| SpiderError::NetworkError(format!("Failed to read body: {}", e)))?;
+
+        Ok(FetchedBody { content_type, bytes })
+    }
+
+    /// Extract HTML content from a response, detecting its character encoding
+    pub async fn extract_html(&self, response: Response) -> Result<DecodedHtml, SpiderError> {
+        let body = self.read_body(response).await?;
+
         // Only process HTML content
-        if !content_type.contains("text/html") && !content_type.contains("application/xhtml+xml") {
+        if ContentKind::detect(&body.content_type, &body.bytes) != ContentKind::Html {
             return Err(SpiderError::ContentType(
-                format!("Not HTML content: {}", content_type)
+                format!("Not HTML content: {}", body.content_type)
             ));
         }
-        
-        // Get the HTML content
-        let html = response.text().await
-            .map_err(|e| SpiderError::HtmlParse(format!("Failed to get HTML: {}", e)))?;
-            
-        
-        Ok(html)
+
+        // Decode the bytes ourselves, since the declared encoding may only be in a <meta> tag
+        Ok(decode_html(&body.bytes, &body.content_type))
     }
     
     /// Write debug HTML to file when no links are found
@@ -148,7 +656,7 @@ impl NetworkClient {
         // Create domain-specific directory
         let debug_dir = format!("debug/{}", domain);
         std::fs::create_dir_all(&debug_dir)
-            .map_err(|e| SpiderError::Io(e))?;
+            .map_err(SpiderError::Io)?;
         
         // Create a safe filename from the URL path
         let url_path = url.replace("://", "_")
@@ -158,7 +666,7 @@ impl NetworkClient {
         
         // Write the HTML to a file
         std::fs::write(&filename, html)
-            .map_err(|e| SpiderError::Io(e))?;
+            .map_err(SpiderError::Io)?;
             
         debug!("Saved debug HTML to {}", filename);
         
@@ -191,4 +699,227 @@ impl NetworkClient {
                 html.matches("<a ").count(),
                 html.matches("<script").count())
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::Matcher;
+
+    /// A client without politeness delays
+    fn client(builder: crate::spider::config::SpiderConfigBuilder) -> NetworkClient {
+        NetworkClient::new(
+            builder
+                .min_request_delay_ms(0)
+                .max_request_delay_ms(0)
+                .build(),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_default_and_host_headers() {
+        let mut server = mockito::Server::new_async().await;
+        let host = Url::parse(&server.url()).unwrap();
+        let client = client(
+            SpiderConfig::builder()
+                .add_default_header("X-Team", "crawl")
+                .add_host_header("LOCALHOST", "Authorization", "Bearer token"),
+        );
+
+        let page = server
+            .mock("GET", "/page")
+            .match_header("accept-encoding", "gzip, deflate, br")
+            .match_header("accept-language", "en-US,en;q=0.5")
+            .match_header("x-team", "crawl")
+            .match_header("authorization", Matcher::Missing)
+            .create_async()
+            .await;
+        let fetched = client
+            .fetch_page(&format!("{}/page", server.url()), None)
+            .await
+            .unwrap();
+        assert_eq!(fetched.status, 200);
+        page.assert_async().await;
+
+        // The same server under another host name gets the host headers
+        let private = server
+            .mock("GET", "/private")
+            .match_header("x-team", "crawl")
+            .match_header("authorization", "Bearer token")
+            .create_async()
+            .await;
+        let url = format!("http://localhost:{}/private", host.port().unwrap());
+        assert_eq!(client.fetch_page(&url, None).await.unwrap().status, 200);
+        private.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_binary_body_not_read() {
+        let mut server = mockito::Server::new_async().await;
+        let video = vec![0u8; 4 * 1024 * 1024];
+        server
+            .mock("GET", "/video.mp4")
+            .with_header("content-type", "video/mp4")
+            .with_body(&video)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/feed")
+            .with_header("content-type", "application/rss+xml")
+            .with_body("<rss><channel><link>https://a.com/</link></channel></rss>")
+            .create_async()
+            .await;
+
+        let plain = client(SpiderConfig::builder());
+        let fetched = plain
+            .fetch_page(&format!("{}/video.mp4", server.url()), None)
+            .await
+            .unwrap();
+        assert_eq!(fetched.status, 200);
+        assert_eq!(fetched.content_type, "video/mp4");
+        assert!(fetched.bytes.is_empty());
+        assert_eq!(fetched.content_length, video.len());
+
+        let fetched = plain
+            .fetch_page(&format!("{}/feed", server.url()), None)
+            .await
+            .unwrap();
+        assert_eq!(fetched.content_length, 57);
+        assert_eq!(fetched.bytes.len(), 57);
+
+        // Stored bodies are always read
+        let store = crate::spider::store::ContentStoreConfig::default();
+        let storing = client(SpiderConfig::builder().content_store(store));
+        let fetched = storing
+            .fetch_page(&format!("{}/video.mp4", server.url()), None)
+            .await
+            .unwrap();
+        assert_eq!(fetched.bytes.len(), video.len());
+    }
+
+    #[tokio::test]
+    async fn test_referer_policies() {
+        let mut server = mockito::Server::new_async().await;
+        let url = format!("{}/page", server.url());
+        let linking_page = "https://example.com/blog/post?id=1";
+
+        for (policy, from_link, direct) in [
+            (RefererPolicy::None, Matcher::Missing, Matcher::Missing),
+            (
+                RefererPolicy::Origin,
+                Matcher::Exact("https://example.com/".to_string()),
+                Matcher::Missing,
+            ),
+            (
+                RefererPolicy::LinkingPage,
+                Matcher::Exact(linking_page.to_string()),
+                Matcher::Missing,
+            ),
+        ] {
+            let client = client(SpiderConfig::builder().referer_policy(policy));
+
+            let mock = server
+                .mock("GET", "/page")
+                .match_header("referer", from_link)
+                .create_async()
+                .await;
+            client.fetch_page(&url, Some(linking_page)).await.unwrap();
+            mock.assert_async().await;
+            mock.remove_async().await;
+
+            let mock = server
+                .mock("GET", "/page")
+                .match_header("referer", direct)
+                .create_async()
+                .await;
+            client.fetch_page(&url, None).await.unwrap();
+            mock.assert_async().await;
+            mock.remove_async().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_robots_disallow_in_identify_mode() {
+        let mut server = mockito::Server::new_async().await;
+        let robots = server
+            .mock("GET", "/robots.txt")
+            .match_header("user-agent", "TinyCrawler/0.1 (+https://example.com/bot)")
+            .with_body(
+                "User-agent: TinyCrawler\nDisallow: /private\n\nUser-agent: *\nDisallow: /\n",
+            )
+            .expect(1)
+            .create_async()
+            .await;
+
+        let identified =
+            client(SpiderConfig::builder().identify("TinyCrawler/0.1 (+https://example.com/bot)"));
+        let private = format!("{}/private/page", server.url());
+        let public = format!("{}/public", server.url());
+        let (private_allowed, public_allowed) = tokio::join!(
+            identified.is_allowed_by_robots(&private),
+            identified.is_allowed_by_robots(&public),
+        );
+        assert!(!private_allowed);
+        assert!(public_allowed);
+        robots.assert_async().await;
+
+        // Rotating user agents never consults robots.txt
+        let rotating = client(SpiderConfig::builder());
+        assert!(rotating.is_allowed_by_robots(&private).await);
+        robots.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_archive_records_redirect_hops() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/old")
+            .with_status(301)
+            .with_header("location", "/new")
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/new")
+            .with_header("content-type", "text/html")
+            .with_body("<p>moved</p>")
+            .create_async()
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = crate::spider::warc::WarcConfig {
+            directory: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let client = client(SpiderConfig::builder());
+        client
+            .start_warc(WarcWriter::new(config, "localhost").unwrap())
+            .await;
+        let fetched = client
+            .fetch_page(&format!("{}/old", server.url()), None)
+            .await
+            .unwrap();
+        assert_eq!(fetched.status, 200);
+        assert_eq!(fetched.final_url, format!("{}/new", server.url()));
+        let output = client.finish_warc().await.unwrap().unwrap();
+
+        let mut warc = String::new();
+        std::io::Read::read_to_string(
+            &mut flate2::read::MultiGzDecoder::new(std::fs::File::open(&output.files[0]).unwrap()),
+            &mut warc,
+        )
+        .unwrap();
+        assert_eq!(warc.matches("WARC-Type: response").count(), 2);
+        assert!(warc.contains("GET /old HTTP/1.1\r\n"));
+        assert!(warc.contains("GET /new HTTP/1.1\r\n"));
+        assert!(warc.contains("HTTP/1.1 301 Moved Permanently\r\n"));
+        assert!(warc.contains(&format!("redirectedFrom: {}/old\r\n", server.url())));
+
+        let cdx = std::fs::read_to_string(output.cdx_file.unwrap()).unwrap();
+        let old = cdx
+            .lines()
+            .map(|line| line.split(' ').collect::<Vec<_>>())
+            .find(|fields| fields[2].ends_with("/old"))
+            .unwrap();
+        assert_eq!((old[4], old[6]), ("301", "/new"));
+    }
+}
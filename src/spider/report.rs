@@ -0,0 +1,489 @@
+use crate::spider::crawler::{CrawlResult, PageInfo};
+use crate::spider::error::SpiderError;
+use crate::spider::graph::xml_escape as escape;
+use log::warn;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write;
+use std::path::Path;
+
+/// Number of slowest pages listed per site
+const SLOWEST_PAGES: usize = 10;
+
+/// Status label of URLs that got no HTTP response
+const NO_RESPONSE: &str = "no response";
+
+/// Styles of the report, inlined so it works offline
+const STYLE: &str = "
+body { font-family: system-ui, sans-serif; margin: 2rem auto; max-width: 72rem; color: #222; }
+h1, h2 { margin-top: 2rem; }
+table { border-collapse: collapse; width: 100%; margin: 0.5rem 0 1rem; }
+th, td { text-align: left; padding: 0.25rem 0.5rem; border-bottom: 1px solid #ddd; vertical-align: top; }
+td.num { text-align: right; white-space: nowrap; }
+.url { word-break: break-all; }
+.bar { background: #4a7fd4; height: 0.9rem; min-width: 1px; }
+.cards { display: flex; flex-wrap: wrap; gap: 1rem; }
+.card { border: 1px solid #ddd; border-radius: 6px; padding: 0.75rem 1rem; min-width: 9rem; }
+.card b { display: block; font-size: 1.5rem; }
+details { border: 1px solid #ddd; border-radius: 6px; padding: 0.5rem 1rem; margin: 0.5rem 0; }
+summary { cursor: pointer; font-weight: 600; }
+.muted { color: #777; }
+";
+
+/// A broken internal link or dead external link
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrokenLink {
+    /// The link target
+    pub url: String,
+
+    /// The HTTP status, or `no response`
+    pub status: String,
+
+    /// Pages linking to the target
+    pub referrers: Vec<String>,
+}
+
+/// Load crawl results from a result file or a directory of them
+///
+/// Files in a directory that are not crawl results are skipped with a warning.
+pub fn load_results(path: &Path) -> Result<Vec<CrawlResult>, SpiderError> {
+    if path.is_file() {
+        let file = std::fs::File::open(path)?;
+        return Ok(vec![serde_json::from_reader(std::io::BufReader::new(
+            file,
+        ))?]);
+    }
+
+    let mut files: Vec<_> = std::fs::read_dir(path)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    files.sort();
+
+    let mut results = Vec::new();
+    for file in files {
+        let reader = std::io::BufReader::new(std::fs::File::open(&file)?);
+        match serde_json::from_reader(reader) {
+            Ok(result) => results.push(result),
+            Err(e) => warn!("Skipping {}: {}", file.display(), e),
+        }
+    }
+    Ok(results)
+}
+
+/// Render a self-contained HTML report of one or more crawls
+pub fn render_report(results: &[CrawlResult]) -> String {
+    let mut html = String::new();
+    let _ = write!(
+        html,
+        "<!DOCTYPE html>\n<html lang=\"en\"><head><meta charset=\"utf-8\">\
+         <title>Crawl report</title><style>{}</style></head><body>\n<h1>Crawl report</h1>\n",
+        STYLE
+    );
+
+    let mut statuses = BTreeMap::new();
+    let mut skips = BTreeMap::new();
+    for result in results {
+        for (status, count) in status_breakdown(result) {
+            *statuses.entry(status).or_insert(0) += count;
+        }
+        for (reason, urls) in &result.skipped_urls {
+            *skips.entry(reason.clone()).or_insert(0) += urls.len();
+        }
+    }
+    let broken: usize = results.iter().map(|r| broken_links(r).len()).sum();
+
+    html.push_str("<div class=\"cards\">");
+    for (label, value) in [
+        ("Sites", results.len()),
+        ("Pages", results.iter().map(|r| r.pages.len()).sum()),
+        ("Found URLs", results.iter().map(|r| r.urls.len()).sum()),
+        ("Skipped URLs", skips.values().sum()),
+        ("Redirects", results.iter().map(|r| r.redirects.len()).sum()),
+        ("Broken links", broken),
+    ] {
+        let _ = write!(html, "<div class=\"card\"><b>{}</b>{}</div>", value, label);
+    }
+    html.push_str("</div>\n");
+
+    html.push_str("<h2>Status codes</h2>\n");
+    html.push_str(&bar_chart("Status", &statuses));
+    html.push_str("<h2>Skip reasons</h2>\n");
+    html.push_str(&bar_chart("Reason", &skips));
+
+    html.push_str("<h2>Sites</h2>\n");
+    let mut sites: Vec<&CrawlResult> = results.iter().collect();
+    sites.sort_by(|a, b| a.base_domain.cmp(&b.base_domain));
+    for result in sites {
+        render_site(&mut html, result);
+    }
+
+    html.push_str("</body></html>\n");
+    html
+}
+
+/// Render the report of the given crawls to an HTML file
+pub fn write_report(results: &[CrawlResult], path: &Path) -> Result<(), SpiderError> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, render_report(results))?;
+    Ok(())
+}
+
+/// Render the drill-down section of one site
+fn render_site(html: &mut String, result: &CrawlResult) {
+    let broken = broken_links(result);
+    let _ = write!(
+        html,
+        "<details><summary>{} <span class=\"muted\">{} pages, {} broken links</span></summary>\n\
+         <p class=\"muted\">Start URL: {}</p>\n",
+        escape(&result.base_domain),
+        result.pages.len(),
+        broken.len(),
+        escape(&result.base_url)
+    );
+
+    let stats: BTreeMap<String, usize> = result
+        .stats
+        .iter()
+        .map(|(name, value)| (name.clone(), *value))
+        .collect();
+    html.push_str("<h3>Statistics</h3>\n<table>");
+    for (name, value) in &stats {
+        let _ = write!(
+            html,
+            "<tr><td>{}</td><td class=\"num\">{}</td></tr>",
+            escape(name),
+            value
+        );
+    }
+    html.push_str("</table>\n<h3>Status codes</h3>\n");
+    html.push_str(&bar_chart("Status", &status_breakdown(result)));
+
+    let skips: BTreeMap<String, usize> = result
+        .skipped_urls
+        .iter()
+        .map(|(reason, urls)| (reason.clone(), urls.len()))
+        .collect();
+    html.push_str("<h3>Skip reasons</h3>\n");
+    html.push_str(&bar_chart("Reason", &skips));
+
+    html.push_str("<h3>Detected patterns</h3>\n");
+    let mut patterns: Vec<(&str, String)> = result
+        .massive_link_patterns
+        .iter()
+        .map(|pattern| (pattern.as_str(), "reached the per-template cap".to_string()))
+        .collect();
+    patterns.extend(
+        result
+            .url_templates
+            .iter()
+            .filter(|template| template.discovered > 1)
+            .map(|template| {
+                let counts = format!(
+                    "{} discovered, {} crawled, {} over quota",
+                    template.discovered, template.crawled, template.skipped_by_quota
+                );
+                (template.template.as_str(), counts)
+            }),
+    );
+    rows(
+        html,
+        &["Pattern", "Details"],
+        patterns
+            .iter()
+            .map(|(pattern, details)| vec![url_cell(pattern), escape(details)]),
+    );
+
+    html.push_str("<h3>Redirect chains</h3>\n");
+    rows(
+        html,
+        &["Chain"],
+        redirect_chains(result).iter().map(|chain| {
+            let hops: Vec<String> = chain.iter().map(|url| escape(url)).collect();
+            vec![format!(
+                "<span class=\"url\">{}</span>",
+                hops.join(" &rarr; ")
+            )]
+        }),
+    );
+
+    html.push_str("<h3>Broken links</h3>\n");
+    rows(
+        html,
+        &["URL", "Status", "Linked from"],
+        broken.iter().map(|link| {
+            let referrers: Vec<String> = link.referrers.iter().map(|r| escape(r)).collect();
+            vec![
+                url_cell(&link.url),
+                escape(&link.status),
+                referrers.join("<br>"),
+            ]
+        }),
+    );
+
+    html.push_str("<h3>Slowest pages</h3>\n");
+    rows(
+        html,
+        &["URL", "Response time", "Status"],
+        slowest_pages(result, SLOWEST_PAGES).iter().map(|page| {
+            vec![
+                url_cell(&page.url),
+                format!("{} ms", page.response_time_ms),
+                page.status.to_string(),
+            ]
+        }),
+    );
+
+    html.push_str("</details>\n");
+}
+
+/// Count fetched URLs per HTTP status, plus URLs that got no response
+pub fn status_breakdown(result: &CrawlResult) -> BTreeMap<String, usize> {
+    let mut statuses = BTreeMap::new();
+    for page in &result.pages {
+        *statuses.entry(page.status.to_string()).or_insert(0) += 1;
+    }
+
+    let fetched: BTreeSet<&str> = result.pages.iter().map(|p| p.url.as_str()).collect();
+    let no_response = result
+        .unreachable_urls
+        .iter()
+        .filter(|url| !fetched.contains(url.as_str()))
+        .count();
+    if no_response > 0 {
+        statuses.insert(NO_RESPONSE.to_string(), no_response);
+    }
+    statuses
+}
+
+/// Internal URLs that failed and dead external links, with the pages linking to them
+pub fn broken_links(result: &CrawlResult) -> Vec<BrokenLink> {
+    let mut referrers: HashMap<&str, BTreeSet<&str>> = HashMap::new();
+    for edge in &result.link_graph.edges {
+        referrers
+            .entry(edge.target.as_str())
+            .or_default()
+            .insert(edge.source.as_str());
+    }
+    let statuses: HashMap<&str, u16> = result
+        .pages
+        .iter()
+        .map(|page| (page.url.as_str(), page.status))
+        .collect();
+
+    let mut broken: Vec<BrokenLink> = result
+        .unreachable_urls
+        .iter()
+        .map(|url| BrokenLink {
+            url: url.clone(),
+            status: statuses
+                .get(url.as_str())
+                .map_or(NO_RESPONSE.to_string(), |status| status.to_string()),
+            referrers: referrers
+                .get(url.as_str())
+                .map(|sources| sources.iter().map(|s| s.to_string()).collect())
+                .unwrap_or_default(),
+        })
+        .collect();
+
+    broken.extend(result.external_links.values().flatten().filter_map(|link| {
+        let check = link.check.as_ref().filter(|check| !check.alive)?;
+        Some(BrokenLink {
+            url: link.url.clone(),
+            status: check
+                .status
+                .map_or(NO_RESPONSE.to_string(), |status| status.to_string()),
+            referrers: link.source_pages.clone(),
+        })
+    }));
+
+    broken.sort_by(|a, b| a.url.cmp(&b.url));
+    broken.dedup_by(|a, b| a.url == b.url);
+    broken
+}
+
+/// Redirects as chains of URLs, following redirects whose target redirects again
+pub fn redirect_chains(result: &CrawlResult) -> Vec<Vec<String>> {
+    let targets: BTreeSet<&str> = result.redirects.values().map(String::as_str).collect();
+    let mut starts: Vec<&String> = result
+        .redirects
+        .keys()
+        .filter(|source| !targets.contains(source.as_str()))
+        .collect();
+    starts.sort();
+
+    starts
+        .into_iter()
+        .map(|start| {
+            let mut chain = vec![start.clone()];
+            while let Some(next) = result.redirects.get(chain.last().unwrap()) {
+                if chain.contains(next) {
+                    chain.push(next.clone());
+                    break;
+                }
+                chain.push(next.clone());
+            }
+            chain
+        })
+        .collect()
+}
+
+/// The pages with the longest response times, slowest first
+pub fn slowest_pages(result: &CrawlResult, count: usize) -> Vec<&PageInfo> {
+    let mut pages: Vec<&PageInfo> = result.pages.iter().collect();
+    pages.sort_by_key(|page| std::cmp::Reverse(page.response_time_ms));
+    pages.truncate(count);
+    pages
+}
+
+/// Render counts as a table with proportional bars
+fn bar_chart(label: &str, counts: &BTreeMap<String, usize>) -> String {
+    if counts.is_empty() {
+        return "<p class=\"muted\">None</p>\n".to_string();
+    }
+
+    let max = counts.values().copied().max().unwrap_or(1).max(1);
+    let mut html = format!(
+        "<table><tr><th>{}</th><th>Count</th><th style=\"width:50%\"></th></tr>",
+        escape(label)
+    );
+    for (name, count) in counts {
+        let _ = write!(
+            html,
+            "<tr><td>{}</td><td class=\"num\">{}</td>\
+             <td><div class=\"bar\" style=\"width:{:.1}%\"></div></td></tr>",
+            escape(name),
+            count,
+            *count as f64 * 100.0 / max as f64
+        );
+    }
+    html.push_str("</table>\n");
+    html
+}
+
+/// Append a table with the given headers and pre-escaped cells
+fn rows(html: &mut String, headers: &[&str], rows: impl Iterator<Item = Vec<String>>) {
+    let mut rows = rows.peekable();
+    if rows.peek().is_none() {
+        html.push_str("<p class=\"muted\">None</p>\n");
+        return;
+    }
+
+    html.push_str("<table><tr>");
+    for header in headers {
+        let _ = write!(html, "<th>{}</th>", header);
+    }
+    html.push_str("</tr>");
+    for row in rows {
+        html.push_str("<tr>");
+        for cell in row {
+            let _ = write!(html, "<td>{}</td>", cell);
+        }
+        html.push_str("</tr>");
+    }
+    html.push_str("</table>\n");
+}
+
+/// An escaped URL for a table cell
+fn url_cell(url: &str) -> String {
+    format!("<span class=\"url\">{}</span>", escape(url))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spider::content::ContentKind;
+    use crate::spider::graph::LinkEdge;
+
+    fn result() -> CrawlResult {
+        let pages = vec![
+            PageInfo {
+                response_time_ms: 40,
+                outgoing_links: 2,
+                ..PageInfo::fixture("https://a.com/", 200)
+            },
+            PageInfo {
+                response_time_ms: 90,
+                depth: 1,
+                ..PageInfo::fixture("https://a.com/missing", 404)
+            },
+        ];
+        let mut result = CrawlResult {
+            skipped_urls: HashMap::from([(
+                "robots_txt".to_string(),
+                vec!["https://a.com/admin".to_string()],
+            )]),
+            redirects: HashMap::from([
+                (
+                    "https://a.com/old".to_string(),
+                    "https://a.com/new".to_string(),
+                ),
+                (
+                    "https://a.com/new".to_string(),
+                    "https://a.com/".to_string(),
+                ),
+            ]),
+            unreachable_urls: vec![
+                "https://a.com/missing".to_string(),
+                "https://a.com/down".to_string(),
+            ],
+            stats: HashMap::from([("pages".to_string(), 2)]),
+            ..CrawlResult::fixture(pages)
+        };
+        result.link_graph.edges.push(LinkEdge {
+            source: "https://a.com/".to_string(),
+            target: "https://a.com/missing".to_string(),
+            internal: true,
+            source_kind: ContentKind::Html,
+        });
+        result
+    }
+
+    #[test]
+    fn test_site_summaries() {
+        let result = result();
+        assert_eq!(
+            status_breakdown(&result),
+            BTreeMap::from([
+                ("200".to_string(), 1),
+                ("404".to_string(), 1),
+                (NO_RESPONSE.to_string(), 1),
+            ])
+        );
+        assert_eq!(
+            broken_links(&result),
+            vec![
+                BrokenLink {
+                    url: "https://a.com/down".to_string(),
+                    status: NO_RESPONSE.to_string(),
+                    referrers: vec![],
+                },
+                BrokenLink {
+                    url: "https://a.com/missing".to_string(),
+                    status: "404".to_string(),
+                    referrers: vec!["https://a.com/".to_string()],
+                },
+            ]
+        );
+        assert_eq!(
+            redirect_chains(&result),
+            vec![vec![
+                "https://a.com/old",
+                "https://a.com/new",
+                "https://a.com/"
+            ]]
+        );
+        assert_eq!(slowest_pages(&result, 1)[0].url, "https://a.com/missing");
+    }
+
+    #[test]
+    fn test_render_report() {
+        let html = render_report(&[result()]);
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("http://") && !html.contains("src="));
+        assert!(html.contains("<td>robots_txt</td>"));
+        assert!(html.contains("https://a.com/old &rarr; https://a.com/new &rarr; https://a.com/"));
+    }
+}
@@ -0,0 +1,283 @@
+use crate::spider::crawler::{CrawlResult, PageInfo};
+use crate::spider::error::SpiderError;
+use crate::spider::graph::xml_escape;
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use url::Url;
+
+/// Maximum number of URLs in one sitemap file, set by the sitemap protocol
+pub const MAX_SITEMAP_URLS: usize = 50_000;
+
+/// XML namespace of sitemaps and sitemap indexes
+const SITEMAP_NAMESPACE: &str = "http://www.sitemaps.org/schemas/sitemap/0.9";
+
+/// A URL listed in a sitemap
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SitemapEntry {
+    /// The page URL
+    pub loc: String,
+
+    /// Last modification time in W3C datetime format, if known
+    pub lastmod: Option<String>,
+}
+
+/// A sitemap built from crawl results
+#[derive(Debug, Clone, Default)]
+pub struct Sitemap {
+    /// URLs in the sitemap, sorted
+    pub entries: Vec<SitemapEntry>,
+
+    /// Origin of the listed pages, e.g. `https://www.example.com`
+    pub origin: Option<String>,
+}
+
+impl Sitemap {
+    /// Build a sitemap from the indexable HTML pages of a crawl
+    ///
+    /// Pages that were redirected, could not be fetched or ask not to be
+    /// indexed are left out, as are pages outside the origin of the base URL
+    /// (after redirects), since a sitemap may only list URLs of its own site.
+    /// `lastmod` comes from the Last-Modified header.
+    pub fn from_result(result: &CrawlResult) -> Self {
+        let unreachable: HashSet<&str> =
+            result.unreachable_urls.iter().map(String::as_str).collect();
+        let base_url = result
+            .redirects
+            .get(&result.base_url)
+            .unwrap_or(&result.base_url);
+        let origin = Url::parse(base_url).ok().map(|url| url.origin());
+
+        let mut entries: Vec<SitemapEntry> = result
+            .pages
+            .iter()
+            .filter(|page| is_indexable(page))
+            .filter(|page| {
+                let page_origin = Url::parse(&page.url).ok().map(|url| url.origin());
+                origin.is_some() && page_origin == origin
+            })
+            .filter(|page| {
+                !unreachable.contains(page.url.as_str())
+                    && !result.redirects.contains_key(&page.url)
+            })
+            .map(|page| SitemapEntry {
+                loc: page.url.clone(),
+                lastmod: page.last_modified.as_deref().and_then(w3c_datetime),
+            })
+            .collect();
+        entries.sort_by(|a, b| a.loc.cmp(&b.loc));
+        entries.dedup_by(|a, b| a.loc == b.loc);
+
+        Self {
+            entries,
+            origin: origin
+                .filter(|origin| origin.is_tuple())
+                .map(|origin| origin.ascii_serialization()),
+        }
+    }
+
+    /// Write `sitemap.xml` to a directory, returning the paths written
+    ///
+    /// Above `MAX_SITEMAP_URLS` URLs, the entries are split into numbered
+    /// `sitemap-N.xml` files and `sitemap.xml` becomes an index of them.
+    /// `base_url` is the URL the files will be served from, used in the index.
+    pub fn write(&self, directory: &Path, base_url: &str) -> Result<Vec<PathBuf>, SpiderError> {
+        self.write_split(directory, base_url, MAX_SITEMAP_URLS)
+    }
+
+    /// Write the sitemap files with at most `max_urls` URLs per file
+    fn write_split(
+        &self,
+        directory: &Path,
+        base_url: &str,
+        max_urls: usize,
+    ) -> Result<Vec<PathBuf>, SpiderError> {
+        std::fs::create_dir_all(directory)?;
+        let index_path = directory.join("sitemap.xml");
+        if self.entries.len() <= max_urls {
+            write_file(&index_path, |writer| write_urlset(writer, &self.entries))?;
+            return Ok(vec![index_path]);
+        }
+
+        let base = match base_url.ends_with('/') {
+            true => Url::parse(base_url)?,
+            false => Url::parse(&format!("{}/", base_url))?,
+        };
+        let mut paths = vec![index_path.clone()];
+        let mut locations = Vec::new();
+        for (i, chunk) in self.entries.chunks(max_urls).enumerate() {
+            let name = format!("sitemap-{}.xml", i + 1);
+            let path = directory.join(&name);
+            write_file(&path, |writer| write_urlset(writer, chunk))?;
+            locations.push(base.join(&name)?.to_string());
+            paths.push(path);
+        }
+        write_file(&index_path, |writer| write_index(writer, &locations))?;
+
+        Ok(paths)
+    }
+}
+
+/// Check whether a page belongs in a sitemap
+fn is_indexable(page: &PageInfo) -> bool {
+    (200..300).contains(&page.status)
+        && page.final_url == page.url
+        && !page.noindex
+        && page.content_type.contains("html")
+}
+
+/// Convert an HTTP date, such as a Last-Modified header, to W3C datetime format
+fn w3c_datetime(http_date: &str) -> Option<String> {
+    DateTime::parse_from_rfc2822(http_date.trim())
+        .ok()
+        .map(|date| {
+            date.with_timezone(&Utc)
+                .to_rfc3339_opts(SecondsFormat::Secs, true)
+        })
+}
+
+/// Create a file and write it with the given function
+fn write_file(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> Result<(), SpiderError>,
+) -> Result<(), SpiderError> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write(&mut writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Write a `<urlset>` sitemap
+fn write_urlset<W: Write>(writer: &mut W, entries: &[SitemapEntry]) -> Result<(), SpiderError> {
+    writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(writer, r#"<urlset xmlns="{}">"#, SITEMAP_NAMESPACE)?;
+    for entry in entries {
+        writeln!(writer, "  <url>")?;
+        writeln!(writer, "    <loc>{}</loc>", xml_escape(&entry.loc))?;
+        if let Some(lastmod) = &entry.lastmod {
+            writeln!(writer, "    <lastmod>{}</lastmod>", lastmod)?;
+        }
+        writeln!(writer, "  </url>")?;
+    }
+    writeln!(writer, "</urlset>")?;
+    Ok(())
+}
+
+/// Write a `<sitemapindex>` listing the given sitemap URLs
+fn write_index<W: Write>(writer: &mut W, locations: &[String]) -> Result<(), SpiderError> {
+    writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(writer, r#"<sitemapindex xmlns="{}">"#, SITEMAP_NAMESPACE)?;
+    for location in locations {
+        writeln!(writer, "  <sitemap>")?;
+        writeln!(writer, "    <loc>{}</loc>", xml_escape(location))?;
+        writeln!(writer, "  </sitemap>")?;
+    }
+    writeln!(writer, "</sitemapindex>")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn page(url: &str, status: u16, content_type: &str) -> PageInfo {
+        PageInfo {
+            content_type: content_type.to_string(),
+            depth: 1,
+            ..PageInfo::fixture(url, status)
+        }
+    }
+
+    fn result() -> CrawlResult {
+        let html = |url: &str| page(url, 200, "text/html");
+        let pages = vec![
+            PageInfo {
+                last_modified: Some("Wed, 21 Oct 2015 07:28:00 GMT".to_string()),
+                ..html("https://a.com/b?x=1&y=2")
+            },
+            html("https://a.com/"),
+            PageInfo {
+                noindex: true,
+                ..html("https://a.com/private")
+            },
+            page("https://a.com/gone", 404, "text/html"),
+            PageInfo {
+                final_url: "https://a.com/".to_string(),
+                ..html("https://a.com/old")
+            },
+            html("http://a.com/insecure"),
+            html("https://www.a.com/"),
+            html("https://a.com:8443/admin"),
+            page("https://a.com/feed", 200, "application/rss+xml"),
+        ];
+        CrawlResult {
+            redirects: HashMap::from([(
+                "https://a.com/old".to_string(),
+                "https://a.com/".to_string(),
+            )]),
+            unreachable_urls: vec!["https://a.com/gone".to_string()],
+            ..CrawlResult::fixture(pages)
+        }
+    }
+
+    #[test]
+    fn test_from_result() {
+        let sitemap = Sitemap::from_result(&result());
+        assert_eq!(
+            sitemap.entries,
+            vec![
+                SitemapEntry {
+                    loc: "https://a.com/".to_string(),
+                    lastmod: None,
+                },
+                SitemapEntry {
+                    loc: "https://a.com/b?x=1&y=2".to_string(),
+                    lastmod: Some("2015-10-21T07:28:00Z".to_string()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_origin_after_redirect() {
+        let mut result = result();
+        result.base_url = "http://a.com/".to_string();
+        result
+            .redirects
+            .insert("http://a.com/".to_string(), "https://a.com/".to_string());
+        let sitemap = Sitemap::from_result(&result);
+        assert_eq!(sitemap.entries.len(), 2);
+        assert_eq!(sitemap.origin.as_deref(), Some("https://a.com"));
+
+        result.base_url = "https://www.a.com/".to_string();
+        let sitemap = Sitemap::from_result(&result);
+        assert_eq!(sitemap.entries.len(), 1);
+        assert_eq!(sitemap.origin.as_deref(), Some("https://www.a.com"));
+        assert_eq!(sitemap.entries[0].loc, "https://www.a.com/");
+    }
+
+    #[test]
+    fn test_write_split_with_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let sitemap = Sitemap::from_result(&result());
+
+        let single = sitemap.write(dir.path(), "https://a.com/").unwrap();
+        assert_eq!(single.len(), 1);
+        let xml = std::fs::read_to_string(&single[0]).unwrap();
+        assert!(xml.contains("<loc>https://a.com/b?x=1&amp;y=2</loc>"));
+        assert!(xml.contains("<lastmod>2015-10-21T07:28:00Z</lastmod>"));
+
+        let split = sitemap
+            .write_split(dir.path(), "https://a.com/maps", 1)
+            .unwrap();
+        assert_eq!(split.len(), 3);
+        let index = std::fs::read_to_string(&split[0]).unwrap();
+        assert!(index.contains("<sitemapindex"));
+        assert!(index.contains("<loc>https://a.com/maps/sitemap-2.xml</loc>"));
+        let second = std::fs::read_to_string(&split[2]).unwrap();
+        assert_eq!(second.matches("<url>").count(), 1);
+    }
+}
@@ -0,0 +1,437 @@
+use crate::spider::crawler::{CrawlResult, PageInfo};
+use crate::spider::error::SpiderError;
+use crate::spider::graph::LinkEdge;
+use chrono::{SecondsFormat, Utc};
+use log::warn;
+use rusqlite::{params, Connection};
+use std::path::Path;
+use std::thread::JoinHandle;
+use tokio::sync::{mpsc, oneshot};
+
+/// Tables of the crawl database, created if missing
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS sites (
+    id INTEGER PRIMARY KEY,
+    base_url TEXT NOT NULL,
+    base_domain TEXT NOT NULL,
+    started_at TEXT NOT NULL,
+    finished_at TEXT,
+    status TEXT NOT NULL,
+    found_urls INTEGER,
+    stats TEXT,
+    tags TEXT
+);
+CREATE TABLE IF NOT EXISTS pages (
+    id INTEGER PRIMARY KEY,
+    site_id INTEGER NOT NULL REFERENCES sites (id),
+    url TEXT NOT NULL,
+    final_url TEXT NOT NULL,
+    status INTEGER NOT NULL,
+    content_type TEXT NOT NULL,
+    content_length INTEGER NOT NULL,
+    response_time_ms INTEGER NOT NULL,
+    depth INTEGER NOT NULL,
+    referrer TEXT,
+    title TEXT,
+    meta_description TEXT,
+    outgoing_links INTEGER NOT NULL,
+    duplicate_of TEXT,
+    UNIQUE (site_id, url)
+);
+CREATE TABLE IF NOT EXISTS links (
+    site_id INTEGER NOT NULL REFERENCES sites (id),
+    source TEXT NOT NULL,
+    target TEXT NOT NULL,
+    internal INTEGER NOT NULL,
+    source_kind TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS links_target ON links (site_id, target);
+CREATE TABLE IF NOT EXISTS skips (
+    site_id INTEGER NOT NULL REFERENCES sites (id),
+    url TEXT NOT NULL,
+    reason TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS skips_reason ON skips (site_id, reason);
+CREATE TABLE IF NOT EXISTS redirects (
+    site_id INTEGER NOT NULL REFERENCES sites (id),
+    source TEXT NOT NULL,
+    target TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS errors (
+    site_id INTEGER NOT NULL REFERENCES sites (id),
+    url TEXT NOT NULL,
+    status INTEGER,
+    message TEXT NOT NULL
+);
+";
+
+/// Maximum number of writes committed in one transaction
+const MAX_BATCH_WRITES: usize = 1000;
+
+/// A write queued for the writer thread
+enum SinkWrite {
+    /// Insert a site, replying with its ID once committed
+    StartSite {
+        base_url: String,
+        base_domain: String,
+        reply: oneshot::Sender<i64>,
+    },
+
+    /// Insert or replace a fetched page
+    Page(i64, Box<PageInfo>),
+
+    /// Insert a link
+    Link(i64, LinkEdge),
+
+    /// Insert a skipped URL and the reason
+    Skip(i64, String, String),
+
+    /// Insert a redirect source and target
+    Redirect(i64, String, String),
+
+    /// Insert an unreachable URL with its status and message
+    Error(i64, String, Option<u16>, String),
+
+    /// Set the final status of a site, replying once committed
+    FinishSite {
+        site_id: i64,
+        status: &'static str,
+        found_urls: Option<i64>,
+        stats: Option<String>,
+        tags: Option<String>,
+        reply: Option<oneshot::Sender<()>>,
+    },
+}
+
+/// Writes crawl results to a SQLite database as they are found
+///
+/// One database can hold any number of sites, so a single sink can be shared by
+/// all the crawls of a batch. Rows reference their crawl through `site_id`.
+///
+/// Writes are queued to a dedicated thread that owns the connection and commits
+/// whatever is queued in one transaction, so crawls never wait on the database
+/// except to start and finish a site. Failed writes are logged.
+pub struct SqliteSink {
+    /// Queue of writes, `None` once the sink is dropped
+    sender: Option<mpsc::UnboundedSender<SinkWrite>>,
+
+    /// The writer thread, joined when the sink is dropped
+    writer: Option<JoinHandle<()>>,
+}
+
+impl SqliteSink {
+    /// Open or create a database, adding any missing tables
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, SpiderError> {
+        if let Some(parent) = path.as_ref().parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let connection = Connection::open(path)?;
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        connection.pragma_update(None, "synchronous", "NORMAL")?;
+        connection.execute_batch(SCHEMA)?;
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let writer = std::thread::Builder::new()
+            .name("sqlite-sink".to_string())
+            .spawn(move || write_batches(connection, receiver))?;
+
+        Ok(Self {
+            sender: Some(sender),
+            writer: Some(writer),
+        })
+    }
+
+    /// Record the start of a crawl, returning its site ID
+    pub async fn start_site(&self, base_url: &str, base_domain: &str) -> Result<i64, SpiderError> {
+        let (reply, site_id) = oneshot::channel();
+        self.queue(SinkWrite::StartSite {
+            base_url: base_url.to_string(),
+            base_domain: base_domain.to_string(),
+            reply,
+        });
+        site_id.await.map_err(|_| {
+            SpiderError::Other(format!("Failed to record site {} in SQLite", base_url))
+        })
+    }
+
+    /// Record a fetched page
+    pub fn add_page(&self, site_id: i64, page: &PageInfo) {
+        self.queue(SinkWrite::Page(site_id, Box::new(page.clone())));
+    }
+
+    /// Record a link found on a page
+    pub fn add_link(&self, site_id: i64, edge: &LinkEdge) {
+        self.queue(SinkWrite::Link(site_id, edge.clone()));
+    }
+
+    /// Record a skipped URL with the reason it was skipped
+    pub fn add_skip(&self, site_id: i64, url: &str, reason: &str) {
+        self.queue(SinkWrite::Skip(
+            site_id,
+            url.to_string(),
+            reason.to_string(),
+        ));
+    }
+
+    /// Record a redirect
+    pub fn add_redirect(&self, site_id: i64, source: &str, target: &str) {
+        self.queue(SinkWrite::Redirect(
+            site_id,
+            source.to_string(),
+            target.to_string(),
+        ));
+    }
+
+    /// Record a URL that could not be fetched, with the HTTP status if one was received
+    pub fn add_error(&self, site_id: i64, url: &str, status: Option<u16>, message: &str) {
+        self.queue(SinkWrite::Error(
+            site_id,
+            url.to_string(),
+            status,
+            message.to_string(),
+        ));
+    }
+
+    /// Record the end of a crawl with its statistics, once every earlier write is committed
+    pub async fn finish_site(&self, site_id: i64, result: &CrawlResult) -> Result<(), SpiderError> {
+        let (reply, done) = oneshot::channel();
+        self.queue(SinkWrite::FinishSite {
+            site_id,
+            status: "completed",
+            found_urls: Some(result.urls.len() as i64),
+            stats: Some(serde_json::to_string(&result.stats)?),
+            tags: Some(serde_json::to_string(&result.tags)?),
+            reply: Some(reply),
+        });
+        done.await
+            .map_err(|_| SpiderError::Other(format!("Failed to finish site {} in SQLite", site_id)))
+    }
+
+    /// Record that a crawl ended with an error, without waiting for the write
+    pub fn fail_site(&self, site_id: i64) {
+        self.queue(SinkWrite::FinishSite {
+            site_id,
+            status: "failed",
+            found_urls: None,
+            stats: None,
+            tags: None,
+            reply: None,
+        });
+    }
+
+    /// Hand a write to the writer thread
+    fn queue(&self, write: SinkWrite) {
+        let sent = self
+            .sender
+            .as_ref()
+            .is_some_and(|sender| sender.send(write).is_ok());
+        if !sent {
+            warn!("SQLite writer stopped, dropping write");
+        }
+    }
+}
+
+impl Drop for SqliteSink {
+    /// Wait for the queued writes to be committed
+    fn drop(&mut self) {
+        self.sender.take();
+        if let Some(writer) = self.writer.take() {
+            if writer.join().is_err() {
+                warn!("SQLite writer panicked");
+            }
+        }
+    }
+}
+
+/// Commit queued writes in batches until the sink is dropped
+fn write_batches(mut connection: Connection, mut receiver: mpsc::UnboundedReceiver<SinkWrite>) {
+    while let Some(first) = receiver.blocking_recv() {
+        let mut batch = vec![first];
+        while batch.len() < MAX_BATCH_WRITES {
+            match receiver.try_recv() {
+                Ok(write) => batch.push(write),
+                Err(_) => break,
+            }
+        }
+        if let Err(e) = write_batch(&mut connection, batch) {
+            warn!("Failed to write to SQLite: {}", e);
+        }
+    }
+}
+
+/// Write a batch in one transaction, replying to waiting callers once it is committed
+///
+/// A failed row is logged and skipped; callers of a failed transaction get no reply.
+fn write_batch(connection: &mut Connection, batch: Vec<SinkWrite>) -> Result<(), SpiderError> {
+    let transaction = connection.transaction()?;
+    let mut started = Vec::new();
+    let mut finished = Vec::new();
+    for write in batch {
+        let written = match write {
+            SinkWrite::StartSite {
+                base_url,
+                base_domain,
+                reply,
+            } => transaction
+                .execute(
+                    "INSERT INTO sites (base_url, base_domain, started_at, status)
+                     VALUES (?1, ?2, ?3, 'running')",
+                    params![base_url, base_domain, now()],
+                )
+                .map(|_| started.push((reply, transaction.last_insert_rowid()))),
+            SinkWrite::Page(site_id, page) => transaction
+                .execute(
+                    "INSERT OR REPLACE INTO pages (site_id, url, final_url, status, content_type,
+                         content_length, response_time_ms, depth, referrer, title,
+                         meta_description, outgoing_links, duplicate_of)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+                    params![
+                        site_id,
+                        page.url,
+                        page.final_url,
+                        page.status,
+                        page.content_type,
+                        page.content_length as i64,
+                        page.response_time_ms as i64,
+                        page.depth as i64,
+                        page.referrer,
+                        page.title,
+                        page.meta_description,
+                        page.outgoing_links as i64,
+                        page.duplicate.as_ref().map(|duplicate| &duplicate.of),
+                    ],
+                )
+                .map(drop),
+            SinkWrite::Link(site_id, edge) => transaction
+                .execute(
+                    "INSERT INTO links (site_id, source, target, internal, source_kind)
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![
+                        site_id,
+                        edge.source,
+                        edge.target,
+                        edge.internal,
+                        edge.source_kind.name()
+                    ],
+                )
+                .map(drop),
+            SinkWrite::Skip(site_id, url, reason) => transaction
+                .execute(
+                    "INSERT INTO skips (site_id, url, reason) VALUES (?1, ?2, ?3)",
+                    params![site_id, url, reason],
+                )
+                .map(drop),
+            SinkWrite::Redirect(site_id, source, target) => transaction
+                .execute(
+                    "INSERT INTO redirects (site_id, source, target) VALUES (?1, ?2, ?3)",
+                    params![site_id, source, target],
+                )
+                .map(drop),
+            SinkWrite::Error(site_id, url, status, message) => transaction
+                .execute(
+                    "INSERT INTO errors (site_id, url, status, message) VALUES (?1, ?2, ?3, ?4)",
+                    params![site_id, url, status, message],
+                )
+                .map(drop),
+            SinkWrite::FinishSite {
+                site_id,
+                status,
+                found_urls,
+                stats,
+                tags,
+                reply,
+            } => transaction
+                .execute(
+                    "UPDATE sites SET finished_at = ?1, status = ?2, found_urls = ?3, stats = ?4,
+                         tags = ?5
+                     WHERE id = ?6",
+                    params![now(), status, found_urls, stats, tags, site_id],
+                )
+                .map(|_| finished.extend(reply)),
+        };
+        if let Err(e) = written {
+            warn!("Failed to write to SQLite: {}", e);
+        }
+    }
+    transaction.commit()?;
+
+    for (reply, site_id) in started {
+        let _ = reply.send(site_id);
+    }
+    for reply in finished {
+        let _ = reply.send(());
+    }
+    Ok(())
+}
+
+/// Current time in RFC 3339 format
+fn now() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spider::content::ContentKind;
+    use std::collections::HashMap;
+
+    #[tokio::test]
+    async fn test_sink_tables() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("crawl.db");
+        let sink = SqliteSink::open(&path).unwrap();
+        let first = sink.start_site("https://a.com/", "a.com").await.unwrap();
+        let second = sink.start_site("https://b.com/", "b.com").await.unwrap();
+        assert_ne!(first, second);
+
+        let page = PageInfo {
+            outgoing_links: 1,
+            ..PageInfo::fixture("https://a.com/", 200)
+        };
+        sink.add_page(first, &page);
+        sink.add_link(
+            first,
+            &LinkEdge {
+                source: "https://a.com/".to_string(),
+                target: "https://a.com/x".to_string(),
+                internal: true,
+                source_kind: ContentKind::Html,
+            },
+        );
+        sink.add_skip(first, "https://a.com/x", "robots_txt");
+        sink.add_redirect(second, "https://b.com/", "https://www.b.com/");
+        sink.add_error(second, "https://www.b.com/", Some(500), "HTTP 500");
+
+        let result = CrawlResult {
+            urls: vec!["https://a.com/".to_string()],
+            stats: HashMap::from([("pages".to_string(), 1)]),
+            ..CrawlResult::fixture(vec![page])
+        };
+        sink.finish_site(first, &result).await.unwrap();
+        sink.fail_site(second);
+        drop(sink);
+
+        let connection = Connection::open(&path).unwrap();
+        let count = |sql: &str| -> i64 { connection.query_row(sql, [], |row| row.get(0)).unwrap() };
+        assert_eq!(count("SELECT COUNT(*) FROM pages WHERE site_id = 1"), 1);
+        assert_eq!(
+            count("SELECT COUNT(*) FROM links WHERE source_kind = 'html'"),
+            1
+        );
+        assert_eq!(
+            count("SELECT COUNT(*) FROM skips WHERE reason = 'robots_txt'"),
+            1
+        );
+        assert_eq!(count("SELECT COUNT(*) FROM redirects WHERE site_id = 2"), 1);
+        assert_eq!(count("SELECT status FROM errors"), 500);
+        assert_eq!(
+            count("SELECT COUNT(*) FROM sites WHERE status = 'completed' AND found_urls = 1"),
+            1
+        );
+        assert_eq!(
+            count("SELECT COUNT(*) FROM sites WHERE status = 'failed' AND finished_at IS NOT NULL"),
+            1
+        );
+    }
+}
@@ -0,0 +1,290 @@
+use crate::spider::error::SpiderError;
+use crate::spider::network::FetchedPage;
+use chrono::SecondsFormat;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// File name of the manifest in a site's store directory
+const MANIFEST_FILE: &str = "manifest.jsonl";
+
+/// File extensions per content type; bodies of other types get `.bin`
+const EXTENSIONS: &[(&str, &str)] = &[
+    ("text/html", "html"),
+    ("application/xhtml+xml", "html"),
+    ("application/rss+xml", "xml"),
+    ("application/atom+xml", "xml"),
+    ("application/xml", "xml"),
+    ("text/xml", "xml"),
+    ("application/json", "json"),
+    ("application/ld+json", "json"),
+    ("text/plain", "txt"),
+    ("text/css", "css"),
+    ("text/javascript", "js"),
+    ("application/javascript", "js"),
+    ("application/pdf", "pdf"),
+    ("image/png", "png"),
+    ("image/jpeg", "jpg"),
+    ("image/gif", "gif"),
+    ("image/webp", "webp"),
+    ("image/svg+xml", "svg"),
+];
+
+/// Settings for the content store
+#[derive(Debug, Clone)]
+pub struct ContentStoreConfig {
+    /// Directory holding one store directory per site
+    pub directory: PathBuf,
+
+    /// Gzip the stored bodies
+    pub compress: bool,
+}
+
+impl Default for ContentStoreConfig {
+    fn default() -> Self {
+        Self {
+            directory: PathBuf::from("output").join("content"),
+            compress: false,
+        }
+    }
+}
+
+/// A stored body, as listed in the manifest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// The requested URL
+    pub url: String,
+
+    /// The URL after redirects
+    pub final_url: String,
+
+    /// Path of the stored body, relative to the site's store directory
+    pub file: String,
+
+    /// The Content-Type header of the response
+    pub content_type: String,
+
+    /// The HTTP status code
+    pub status: u16,
+
+    /// Size of the body in bytes, before compression
+    pub size: usize,
+
+    /// Hex SHA-1 of the body, before compression
+    pub sha1: String,
+
+    /// Whether the stored file is gzipped
+    pub compressed: bool,
+
+    /// When the request was sent, in RFC 3339 format
+    pub fetched_at: String,
+}
+
+/// Saves every fetched body of a site, with a JSON Lines manifest
+///
+/// Bodies are stored under a path derived from the SHA-1 of the URL, so any
+/// URL maps to a short, unique file name. Manifest entries are written as the
+/// bodies are saved, after those of earlier crawls of the site.
+pub struct ContentStore {
+    /// The site's store directory
+    directory: PathBuf,
+
+    /// Gzip the stored bodies
+    compress: bool,
+
+    /// Writer for the manifest, shared with the threads saving bodies
+    manifest: Arc<Mutex<BufWriter<File>>>,
+}
+
+impl ContentStore {
+    /// Open the store of a site, in a directory named after `site`
+    ///
+    /// New entries are appended to an existing manifest for the site, so a
+    /// re-crawl or resumed crawl keeps the entries of earlier runs. A URL's body
+    /// is overwritten when it is saved again, so its last entry is the current one.
+    pub fn new(config: &ContentStoreConfig, site: &str) -> Result<Self, SpiderError> {
+        let directory = config.directory.join(site.replace(['.', ':', '/'], "_"));
+        std::fs::create_dir_all(&directory)?;
+        let manifest = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(directory.join(MANIFEST_FILE))?;
+
+        Ok(Self {
+            directory,
+            compress: config.compress,
+            manifest: Arc::new(Mutex::new(BufWriter::new(manifest))),
+        })
+    }
+
+    /// Path of the manifest
+    pub fn manifest_path(&self) -> String {
+        self.directory
+            .join(MANIFEST_FILE)
+            .to_string_lossy()
+            .to_string()
+    }
+
+    /// Save the body of a fetched URL and add it to the manifest
+    ///
+    /// Hashing, compression and file IO run on a blocking thread.
+    pub async fn save(&self, url: &str, page: &FetchedPage) -> Result<ManifestEntry, SpiderError> {
+        let file = body_path(url, &page.content_type, self.compress);
+        let path = self.directory.join(&file);
+        let bytes = page.bytes.clone();
+        let compress = self.compress;
+        let manifest = Arc::clone(&self.manifest);
+        let mut entry = ManifestEntry {
+            url: url.to_string(),
+            final_url: page.final_url.clone(),
+            file: file.to_string_lossy().replace('\\', "/"),
+            content_type: page.content_type.clone(),
+            status: page.status,
+            size: bytes.len(),
+            sha1: String::new(),
+            compressed: compress,
+            fetched_at: page.fetched_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+        };
+
+        tokio::task::spawn_blocking(move || {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            write_body(&path, &bytes, compress)?;
+            entry.sha1 = hex(&Sha1::digest(&bytes));
+
+            let mut manifest = manifest.lock().unwrap();
+            serde_json::to_writer(&mut *manifest, &entry)?;
+            manifest.write_all(b"\n")?;
+            manifest.flush()?;
+            Ok(entry)
+        })
+        .await
+        .map_err(|e| SpiderError::Other(format!("Failed to store body of {}: {}", url, e)))?
+    }
+}
+
+/// Relative path of a URL's body, e.g. `3f/a2/3fa2...c1.html`
+///
+/// The first two bytes of the hash are used as directories to keep directory
+/// sizes small.
+pub fn body_path(url: &str, content_type: &str, compress: bool) -> PathBuf {
+    let hash = hex(&Sha1::digest(url.as_bytes()));
+    let mime = content_type.split(';').next().unwrap_or("").trim();
+    let extension = EXTENSIONS
+        .iter()
+        .find(|(known, _)| *known == mime)
+        .map_or("bin", |(_, extension)| extension);
+
+    let mut name = format!("{}.{}", hash, extension);
+    if compress {
+        name.push_str(".gz");
+    }
+    Path::new(&hash[..2]).join(&hash[2..4]).join(name)
+}
+
+/// Write a body to a file, gzipped if requested
+fn write_body(path: &Path, bytes: &[u8], compress: bool) -> Result<(), SpiderError> {
+    let file = BufWriter::new(File::create(path)?);
+    if compress {
+        let mut encoder = GzEncoder::new(file, Compression::default());
+        encoder.write_all(bytes)?;
+        encoder.finish()?.flush()?;
+    } else {
+        let mut file = file;
+        file.write_all(bytes)?;
+        file.flush()?;
+    }
+    Ok(())
+}
+
+/// Format bytes as lowercase hex
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use chrono::Utc;
+    use flate2::read::GzDecoder;
+    use reqwest::header::HeaderMap;
+    use std::io::Read;
+    use std::time::Duration;
+
+    fn page(url: &str, body: &'static str) -> FetchedPage {
+        FetchedPage {
+            status: 200,
+            final_url: url.to_string(),
+            headers: HeaderMap::new(),
+            content_type: "text/html; charset=utf-8".to_string(),
+            bytes: Bytes::from_static(body.as_bytes()),
+            content_length: body.len(),
+            response_time: Duration::from_millis(5),
+            fetched_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_body_path() {
+        let html = body_path("https://a.com/x.y/z", "text/html; charset=utf-8", false);
+        let similar = body_path("https://a.com/x_y/z", "text/html", false);
+        assert_ne!(html, similar);
+        assert!(html.to_string_lossy().ends_with(".html"));
+        assert_eq!(html.components().count(), 3);
+        assert!(body_path("https://a.com/f", "font/woff2", true)
+            .to_string_lossy()
+            .ends_with(".bin.gz"));
+    }
+
+    #[tokio::test]
+    async fn test_save_with_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = ContentStoreConfig {
+            directory: dir.path().to_path_buf(),
+            compress: true,
+        };
+        let store = ContentStore::new(&config, "a.com").unwrap();
+        let entry = store
+            .save("https://a.com/", &page("https://a.com/", "<p>home</p>"))
+            .await
+            .unwrap();
+        store
+            .save("https://a.com/b", &page("https://a.com/b", "<p>b</p>"))
+            .await
+            .unwrap();
+
+        let mut body = String::new();
+        GzDecoder::new(File::open(dir.path().join("a_com").join(&entry.file)).unwrap())
+            .read_to_string(&mut body)
+            .unwrap();
+        assert_eq!(body, "<p>home</p>");
+
+        let manifest = std::fs::read_to_string(store.manifest_path()).unwrap();
+        let entries: Vec<ManifestEntry> = manifest
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].url, "https://a.com/b");
+        assert_eq!(entries[0].size, 11);
+        assert!(entries[0].compressed);
+
+        // Crawling the site again keeps the earlier entries
+        drop(store);
+        let store = ContentStore::new(&config, "a.com").unwrap();
+        store
+            .save("https://a.com/", &page("https://a.com/", "<p>new home</p>"))
+            .await
+            .unwrap();
+        let manifest = std::fs::read_to_string(store.manifest_path()).unwrap();
+        assert_eq!(manifest.lines().count(), 3);
+        assert!(manifest.lines().last().unwrap().contains("\"size\":15"));
+    }
+}
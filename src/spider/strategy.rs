@@ -0,0 +1,446 @@
+use crate::spider::config::{defaults, SpiderConfig};
+use crate::spider::utils::is_priority_url;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::Arc;
+use url::Url;
+
+/// Score given to the start URL
+pub const START_URL_SCORE: i64 = 100;
+
+/// Score given to links matching `priority_paths` by the default strategy
+pub const PRIORITY_PATH_SCORE: i64 = 50;
+
+/// Score given to all other links by the default strategy
+pub const DEFAULT_SCORE: i64 = 10;
+
+/// A discovered link, as seen by a scoring function
+#[derive(Debug, Clone, Copy)]
+pub struct LinkCandidate<'a> {
+    /// The resolved URL
+    pub url: &'a str,
+
+    /// The depth the URL will be crawled at
+    pub depth: usize,
+
+    /// The text of the link, for links found in HTML
+    pub anchor_text: Option<&'a str>,
+
+    /// The `title` attribute of the link
+    pub title: Option<&'a str>,
+
+    /// Where on the page the link was found
+    pub location: LinkLocation,
+
+    /// The page the link was found on
+    pub referrer: &'a str,
+}
+
+/// The part of an HTML page a link was found in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum LinkLocation {
+    /// A `<nav>` element or `role="navigation"`
+    Nav,
+
+    /// A `<header>` element or `role="banner"`
+    Header,
+
+    /// A `<footer>` element or `role="contentinfo"`
+    Footer,
+
+    /// An `<aside>` element or `role="complementary"`
+    Aside,
+
+    /// A `<main>` or `<article>` element or `role="main"`
+    Main,
+
+    /// Anywhere else, including non-HTML documents
+    #[default]
+    Other,
+}
+
+impl LinkLocation {
+    /// Classify an element by its tag name and `role` attribute
+    pub fn from_element(name: &str, role: Option<&str>) -> Option<Self> {
+        let location = match (name, role.map(|r| r.to_ascii_lowercase()).as_deref()) {
+            (_, Some("navigation")) | ("nav", _) => LinkLocation::Nav,
+            (_, Some("banner")) | ("header", _) => LinkLocation::Header,
+            (_, Some("contentinfo")) | ("footer", _) => LinkLocation::Footer,
+            (_, Some("complementary")) | ("aside", _) => LinkLocation::Aside,
+            (_, Some("main")) | ("main", _) | ("article", _) => LinkLocation::Main,
+            _ => return None,
+        };
+        Some(location)
+    }
+
+    /// Parse a location name such as `nav`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "nav" => Some(LinkLocation::Nav),
+            "header" => Some(LinkLocation::Header),
+            "footer" => Some(LinkLocation::Footer),
+            "aside" => Some(LinkLocation::Aside),
+            "main" => Some(LinkLocation::Main),
+            "other" => Some(LinkLocation::Other),
+            _ => None,
+        }
+    }
+}
+
+/// Weights used by the default strategy to score links by their context
+///
+/// A link's score is [`DEFAULT_SCORE`], raised to [`PRIORITY_PATH_SCORE`] when the
+/// URL matches `priority_paths`, plus the weight of every keyword found in the
+/// anchor text or `title` attribute, plus the weight of the link's location.
+#[derive(Debug, Clone)]
+pub struct LinkScoring {
+    /// Case-insensitive keywords and the score they add
+    pub keyword_weights: Vec<(String, i64)>,
+
+    /// Score added for links in each part of the page
+    pub location_weights: HashMap<LinkLocation, i64>,
+}
+
+impl Default for LinkScoring {
+    fn default() -> Self {
+        Self {
+            keyword_weights: defaults::PRIORITY_KEYWORDS
+                .iter()
+                .map(|keyword| (keyword.to_string(), defaults::PRIORITY_KEYWORD_WEIGHT))
+                .collect(),
+            location_weights: HashMap::new(),
+        }
+    }
+}
+
+impl LinkScoring {
+    /// Score a link from its URL, text and location
+    pub fn score(&self, candidate: &LinkCandidate, priority_paths: &[String]) -> i64 {
+        let mut score = if is_priority_url(candidate.url, priority_paths) {
+            PRIORITY_PATH_SCORE
+        } else {
+            DEFAULT_SCORE
+        };
+
+        let text = [candidate.anchor_text, candidate.title]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase();
+        if !text.is_empty() {
+            score += self
+                .keyword_weights
+                .iter()
+                .filter(|(keyword, _)| text.contains(&keyword.to_lowercase()))
+                .map(|(_, weight)| weight)
+                .sum::<i64>();
+        }
+
+        score
+            + self
+                .location_weights
+                .get(&candidate.location)
+                .copied()
+                .unwrap_or(0)
+    }
+}
+
+/// A function scoring links for best-first crawling; higher scores are crawled first
+pub type ScoreFn = Arc<dyn Fn(&LinkCandidate) -> i64 + Send + Sync>;
+
+/// Order in which queued URLs are crawled
+///
+/// URLs are taken from the frontier in batches of `max_concurrent`, so the
+/// traversal order is exact between batches and concurrent within a batch.
+#[derive(Clone, Default)]
+pub enum CrawlStrategy {
+    /// Highest [`LinkScoring`] score first, then in discovery order
+    #[default]
+    PriorityPaths,
+
+    /// Shallowest URLs first, in discovery order
+    BreadthFirst,
+
+    /// Deepest URLs first, most recently discovered first
+    DepthFirst,
+
+    /// Highest score first, as computed by the given function
+    BestFirst(ScoreFn),
+
+    /// One URL per host in turn, so no subdomain starves the others
+    RoundRobinHosts,
+}
+
+impl fmt::Debug for CrawlStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CrawlStrategy::PriorityPaths => write!(f, "PriorityPaths"),
+            CrawlStrategy::BreadthFirst => write!(f, "BreadthFirst"),
+            CrawlStrategy::DepthFirst => write!(f, "DepthFirst"),
+            CrawlStrategy::BestFirst(_) => write!(f, "BestFirst(..)"),
+            CrawlStrategy::RoundRobinHosts => write!(f, "RoundRobinHosts"),
+        }
+    }
+}
+
+impl CrawlStrategy {
+    /// Parse the name of a strategy without a custom score, such as `breadth_first`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "priority_paths" => Some(CrawlStrategy::PriorityPaths),
+            "breadth_first" => Some(CrawlStrategy::BreadthFirst),
+            "depth_first" => Some(CrawlStrategy::DepthFirst),
+            "round_robin_hosts" => Some(CrawlStrategy::RoundRobinHosts),
+            _ => None,
+        }
+    }
+
+    /// Best-first crawling with the given scoring function
+    pub fn best_first<F>(score: F) -> Self
+    where
+        F: Fn(&LinkCandidate) -> i64 + Send + Sync + 'static,
+    {
+        CrawlStrategy::BestFirst(Arc::new(score))
+    }
+
+    /// Score a discovered link
+    pub fn score(&self, candidate: &LinkCandidate, config: &SpiderConfig) -> i64 {
+        match self {
+            CrawlStrategy::PriorityPaths => {
+                config.link_scoring.score(candidate, &config.priority_paths)
+            }
+            CrawlStrategy::BestFirst(score) => score(candidate),
+            _ => DEFAULT_SCORE,
+        }
+    }
+
+    /// Remove the next batch of up to `size` entries from the frontier
+    ///
+    /// `round` is the number of batches taken so far; round-robin uses it to rotate
+    /// which host goes first. Entries left in the frontier keep their discovery order.
+    pub(crate) fn take_batch(
+        &self,
+        frontier: &mut VecDeque<UrlEntry>,
+        size: usize,
+        round: usize,
+    ) -> Vec<UrlEntry> {
+        let mut entries: Vec<(usize, UrlEntry)> = frontier.drain(..).enumerate().collect();
+
+        match self {
+            CrawlStrategy::PriorityPaths | CrawlStrategy::BestFirst(_) => {
+                entries.sort_by_key(|(_, entry)| std::cmp::Reverse(entry.score));
+            }
+            CrawlStrategy::BreadthFirst => entries.sort_by_key(|(_, entry)| entry.depth),
+            CrawlStrategy::DepthFirst => {
+                entries.sort_by_key(|(index, entry)| std::cmp::Reverse((entry.depth, *index)));
+            }
+            CrawlStrategy::RoundRobinHosts => entries = interleave_hosts(entries, round),
+        }
+
+        let size = size.min(entries.len());
+        let batch = entries.drain(..size).map(|(_, entry)| entry).collect();
+        entries.sort_by_key(|(index, _)| *index);
+        frontier.extend(entries.into_iter().map(|(_, entry)| entry));
+        batch
+    }
+}
+
+/// Reorder entries so hosts take turns, keeping discovery order within each host
+///
+/// Hosts are ordered by their first queued URL, starting at `round` modulo the
+/// number of hosts.
+fn interleave_hosts(entries: Vec<(usize, UrlEntry)>, round: usize) -> Vec<(usize, UrlEntry)> {
+    let mut hosts: Vec<VecDeque<(usize, UrlEntry)>> = Vec::new();
+    let mut slots: HashMap<String, usize> = HashMap::new();
+    for entry in entries {
+        let host = Url::parse(&entry.1.url)
+            .ok()
+            .and_then(|url| url.host_str().map(|host| host.to_string()))
+            .unwrap_or_default();
+        let slot = *slots.entry(host).or_insert_with(|| {
+            hosts.push(VecDeque::new());
+            hosts.len() - 1
+        });
+        hosts[slot].push_back(entry);
+    }
+
+    if !hosts.is_empty() {
+        let start = round % hosts.len();
+        hosts.rotate_left(start);
+    }
+
+    let mut ordered = Vec::new();
+    while hosts.iter().any(|queue| !queue.is_empty()) {
+        for queue in hosts.iter_mut() {
+            if let Some(entry) = queue.pop_front() {
+                ordered.push(entry);
+            }
+        }
+    }
+    ordered
+}
+
+/// A URL waiting in the frontier
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct UrlEntry {
+    /// The URL
+    pub url: String,
+
+    /// The depth of this URL in the crawl
+    pub depth: usize,
+
+    /// Score from the crawl strategy, higher is crawled first
+    pub score: i64,
+
+    /// The page this URL was found on
+    pub referrer: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frontier(entries: &[(&str, usize, i64)]) -> VecDeque<UrlEntry> {
+        entries
+            .iter()
+            .map(|(url, depth, score)| UrlEntry {
+                url: url.to_string(),
+                depth: *depth,
+                score: *score,
+                referrer: None,
+            })
+            .collect()
+    }
+
+    fn urls(batch: &[UrlEntry]) -> Vec<&str> {
+        batch.iter().map(|entry| entry.url.as_str()).collect()
+    }
+
+    #[test]
+    fn test_breadth_and_depth_first() {
+        let entries = [
+            ("https://a.com/1", 1, 0),
+            ("https://a.com/1/2", 2, 0),
+            ("https://a.com/3", 1, 0),
+            ("https://a.com/3/4", 2, 0),
+        ];
+
+        let mut queue = frontier(&entries);
+        let batch = CrawlStrategy::BreadthFirst.take_batch(&mut queue, 2, 0);
+        assert_eq!(urls(&batch), vec!["https://a.com/1", "https://a.com/3"]);
+        assert_eq!(queue.len(), 2);
+
+        let mut queue = frontier(&entries);
+        let batch = CrawlStrategy::DepthFirst.take_batch(&mut queue, 2, 0);
+        assert_eq!(urls(&batch), vec!["https://a.com/3/4", "https://a.com/1/2"]);
+        assert_eq!(queue[0].url, "https://a.com/1");
+    }
+
+    #[test]
+    fn test_best_first() {
+        let strategy = CrawlStrategy::best_first(|link| {
+            let keyword = link
+                .anchor_text
+                .is_some_and(|text| text.contains("Contact"));
+            if keyword {
+                100
+            } else {
+                -(link.depth as i64)
+            }
+        });
+        let candidate = LinkCandidate {
+            url: "https://a.com/page?id=17",
+            depth: 3,
+            anchor_text: Some("Contact us"),
+            title: None,
+            location: LinkLocation::Other,
+            referrer: "https://a.com/",
+        };
+        assert_eq!(strategy.score(&candidate, &SpiderConfig::default()), 100);
+
+        let mut queue = frontier(&[("https://a.com/x", 1, -1), ("https://a.com/y", 1, 100)]);
+        let batch = strategy.take_batch(&mut queue, 1, 0);
+        assert_eq!(urls(&batch), vec!["https://a.com/y"]);
+    }
+
+    #[test]
+    fn test_link_scoring() {
+        let mut scoring = LinkScoring::default();
+        scoring.location_weights.insert(LinkLocation::Footer, 5);
+        let priority_paths = vec!["/contact".to_string()];
+        let candidate = |url, anchor_text, title, location| LinkCandidate {
+            url,
+            depth: 1,
+            anchor_text,
+            title,
+            location,
+            referrer: "https://a.com/",
+        };
+
+        let opaque = candidate(
+            "https://a.com/page?id=17",
+            Some("Contact us"),
+            None,
+            LinkLocation::Footer,
+        );
+        assert_eq!(scoring.score(&opaque, &priority_paths), DEFAULT_SCORE + 45);
+
+        let titled = candidate(
+            "https://a.com/page?id=18",
+            Some("More"),
+            Some("About the company"),
+            LinkLocation::Nav,
+        );
+        assert_eq!(scoring.score(&titled, &priority_paths), DEFAULT_SCORE + 40);
+
+        let path = candidate("https://a.com/contact", None, None, LinkLocation::Other);
+        assert_eq!(scoring.score(&path, &priority_paths), PRIORITY_PATH_SCORE);
+
+        let plain = candidate("https://a.com/blog", Some("Blog"), None, LinkLocation::Main);
+        assert_eq!(scoring.score(&plain, &priority_paths), DEFAULT_SCORE);
+    }
+
+    #[test]
+    fn test_location_from_element() {
+        assert_eq!(
+            LinkLocation::from_element("nav", None),
+            Some(LinkLocation::Nav)
+        );
+        assert_eq!(
+            LinkLocation::from_element("div", Some("contentinfo")),
+            Some(LinkLocation::Footer)
+        );
+        assert_eq!(LinkLocation::from_element("div", None), None);
+    }
+
+    #[test]
+    fn test_round_robin_hosts() {
+        let mut queue = frontier(&[
+            ("https://a.com/1", 1, 0),
+            ("https://a.com/2", 1, 0),
+            ("https://a.com/3", 1, 0),
+            ("https://blog.a.com/1", 1, 0),
+            ("https://shop.a.com/1", 1, 0),
+        ]);
+        let batch = CrawlStrategy::RoundRobinHosts.take_batch(&mut queue, 4, 0);
+        assert_eq!(
+            urls(&batch),
+            vec![
+                "https://a.com/1",
+                "https://blog.a.com/1",
+                "https://shop.a.com/1",
+                "https://a.com/2"
+            ]
+        );
+
+        queue.push_back(UrlEntry {
+            url: "https://blog.a.com/2".to_string(),
+            depth: 1,
+            score: 0,
+            referrer: None,
+        });
+        let batch = CrawlStrategy::RoundRobinHosts.take_batch(&mut queue, 1, 1);
+        assert_eq!(urls(&batch), vec!["https://blog.a.com/2"]);
+    }
+}
@@ -0,0 +1,445 @@
+use scraper::{ElementRef, Html, Selector};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+/// Structured data found on one page
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PageStructuredData {
+    /// Parsed `<script type="application/ld+json">` blocks, with `@graph` flattened
+    pub json_ld: Vec<Value>,
+
+    /// Top-level schema.org microdata items
+    pub microdata: Vec<Value>,
+
+    /// Top-level RDFa items
+    pub rdfa: Vec<Value>,
+
+    /// OpenGraph `og:*` meta tags; repeated properties become arrays
+    pub opengraph: Map<String, Value>,
+
+    /// Twitter card `twitter:*` meta tags; repeated properties become arrays
+    pub twitter: Map<String, Value>,
+}
+
+impl PageStructuredData {
+    /// Whether nothing was found
+    pub fn is_empty(&self) -> bool {
+        self.json_ld.is_empty()
+            && self.microdata.is_empty()
+            && self.rdfa.is_empty()
+            && self.opengraph.is_empty()
+            && self.twitter.is_empty()
+    }
+
+    /// Look up a JSON-LD, microdata or RDFa item by format and position
+    pub fn item(&self, format: &str, index: usize) -> Option<&Value> {
+        match format {
+            "json-ld" => self.json_ld.get(index),
+            "microdata" => self.microdata.get(index),
+            "rdfa" => self.rdfa.get(index),
+            _ => None,
+        }
+    }
+}
+
+/// Reference to a structured data item stored with the page it was found on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StructuredItem {
+    /// The page the item was found on, after redirects
+    pub page: String,
+
+    /// Where the item came from: `json-ld`, `microdata` or `rdfa`
+    pub format: String,
+
+    /// Position of the item in the page's list for its format
+    pub index: usize,
+}
+
+/// Extract all structured data from a parsed page
+pub fn extract_structured_data(document: &Html) -> PageStructuredData {
+    PageStructuredData {
+        json_ld: extract_json_ld(document),
+        microdata: extract_microdata(document),
+        rdfa: extract_rdfa(document),
+        opengraph: extract_meta_prefix(document, "og:"),
+        twitter: extract_meta_prefix(document, "twitter:"),
+    }
+}
+
+/// Group references to a page's JSON-LD, microdata and RDFa items by schema type
+///
+/// Types are shortened to their name, e.g. `https://schema.org/Product` becomes
+/// `Product`. Items with several types are listed under each; untyped items are
+/// listed under `Thing`.
+pub fn group_by_type(
+    page_url: &str,
+    data: &PageStructuredData,
+    groups: &mut BTreeMap<String, Vec<StructuredItem>>,
+) {
+    let sources = [
+        ("json-ld", &data.json_ld),
+        ("microdata", &data.microdata),
+        ("rdfa", &data.rdfa),
+    ];
+    for (format, items) in sources {
+        for (index, item) in items.iter().enumerate() {
+            let mut types = item_types(item);
+            if types.is_empty() {
+                types.push("Thing".to_string());
+            }
+            for item_type in types {
+                groups.entry(item_type).or_default().push(StructuredItem {
+                    page: page_url.to_string(),
+                    format: format.to_string(),
+                    index,
+                });
+            }
+        }
+    }
+}
+
+/// Read the short type names of an item from `@type`
+fn item_types(item: &Value) -> Vec<String> {
+    let names: Vec<&str> = match item.get("@type") {
+        Some(Value::String(name)) => name.split_whitespace().collect(),
+        Some(Value::Array(names)) => names.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    };
+
+    names
+        .into_iter()
+        .map(|name| {
+            name.rsplit(['/', '#', ':'])
+                .next()
+                .unwrap_or(name)
+                .to_string()
+        })
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+/// Parse JSON-LD blocks, skipping invalid ones
+fn extract_json_ld(document: &Html) -> Vec<Value> {
+    let selector = Selector::parse("script[type]").unwrap();
+    let mut items = Vec::new();
+
+    for script in document.select(&selector) {
+        let is_json_ld = script
+            .value()
+            .attr("type")
+            .is_some_and(|t| t.trim().eq_ignore_ascii_case("application/ld+json"));
+        if !is_json_ld {
+            continue;
+        }
+
+        let text = script.text().collect::<String>();
+        let text = text
+            .trim()
+            .trim_start_matches("<!--")
+            .trim_end_matches("-->")
+            .trim()
+            .trim_end_matches(';');
+        let Ok(value) = serde_json::from_str::<Value>(text) else {
+            continue;
+        };
+
+        flatten_json_ld(value, &mut items);
+    }
+
+    items
+}
+
+/// Split top-level arrays and `@graph` containers into separate items
+fn flatten_json_ld(value: Value, items: &mut Vec<Value>) {
+    match value {
+        Value::Array(values) => {
+            for value in values {
+                flatten_json_ld(value, items);
+            }
+        }
+        Value::Object(mut object) => match object.remove("@graph") {
+            Some(graph) => {
+                let context = object.get("@context").cloned();
+                let mut graph_items = Vec::new();
+                flatten_json_ld(graph, &mut graph_items);
+                for mut item in graph_items {
+                    if let (Some(context), Value::Object(item)) = (&context, &mut item) {
+                        item.entry("@context").or_insert_with(|| context.clone());
+                    }
+                    items.push(item);
+                }
+            }
+            None => items.push(Value::Object(object)),
+        },
+        _ => {}
+    }
+}
+
+/// Extract top-level microdata items
+fn extract_microdata(document: &Html) -> Vec<Value> {
+    let selector = Selector::parse("[itemscope]").unwrap();
+    document
+        .select(&selector)
+        .filter(|element| element.value().attr("itemprop").is_none())
+        .map(|element| {
+            let mut item = Map::new();
+            if let Some(types) = element.value().attr("itemtype") {
+                item.insert("@type".to_string(), Value::String(types.trim().to_string()));
+            }
+            if let Some(id) = element.value().attr("itemid") {
+                item.insert("@id".to_string(), Value::String(id.to_string()));
+            }
+            collect_properties(element, &mut item, &Vocabulary::Microdata);
+            Value::Object(item)
+        })
+        .collect()
+}
+
+/// Extract top-level RDFa items
+fn extract_rdfa(document: &Html) -> Vec<Value> {
+    let selector = Selector::parse("[typeof]").unwrap();
+    document
+        .select(&selector)
+        .filter(|element| {
+            element.value().attr("property").is_none()
+                && !element
+                    .ancestors()
+                    .filter_map(ElementRef::wrap)
+                    .any(|ancestor| ancestor.value().attr("typeof").is_some())
+        })
+        .map(rdfa_item)
+        .collect()
+}
+
+/// Build an RDFa item from an element with a `typeof` attribute
+fn rdfa_item(element: ElementRef) -> Value {
+    let mut item = Map::new();
+    if let Some(types) = element.value().attr("typeof") {
+        item.insert("@type".to_string(), Value::String(types.trim().to_string()));
+    }
+    if let Some(id) = element
+        .value()
+        .attr("resource")
+        .or_else(|| element.value().attr("about"))
+    {
+        item.insert("@id".to_string(), Value::String(id.to_string()));
+    }
+    collect_properties(element, &mut item, &Vocabulary::Rdfa);
+    Value::Object(item)
+}
+
+/// The attribute names used by a structured data syntax
+enum Vocabulary {
+    Microdata,
+    Rdfa,
+}
+
+impl Vocabulary {
+    /// Attribute naming a property
+    fn property_attr(&self) -> &'static str {
+        match self {
+            Vocabulary::Microdata => "itemprop",
+            Vocabulary::Rdfa => "property",
+        }
+    }
+
+    /// Attribute starting a nested item
+    fn scope_attr(&self) -> &'static str {
+        match self {
+            Vocabulary::Microdata => "itemscope",
+            Vocabulary::Rdfa => "typeof",
+        }
+    }
+}
+
+/// Collect the properties of an item, without descending into nested items
+fn collect_properties(element: ElementRef, item: &mut Map<String, Value>, vocab: &Vocabulary) {
+    for child in element.children().filter_map(ElementRef::wrap) {
+        let is_scope = child.value().attr(vocab.scope_attr()).is_some();
+
+        if let Some(names) = child.value().attr(vocab.property_attr()) {
+            let value = if is_scope {
+                match vocab {
+                    Vocabulary::Microdata => {
+                        let mut nested = Map::new();
+                        if let Some(types) = child.value().attr("itemtype") {
+                            nested.insert(
+                                "@type".to_string(),
+                                Value::String(types.trim().to_string()),
+                            );
+                        }
+                        collect_properties(child, &mut nested, vocab);
+                        Value::Object(nested)
+                    }
+                    Vocabulary::Rdfa => rdfa_item(child),
+                }
+            } else {
+                Value::String(property_value(child, vocab))
+            };
+
+            for name in names.split_whitespace() {
+                let name = name.rsplit(['/', '#', ':']).next().unwrap_or(name);
+                insert_value(item, name, value.clone());
+            }
+        }
+
+        if !is_scope {
+            collect_properties(child, item, vocab);
+        }
+    }
+}
+
+/// Read the value of a property element following the microdata rules
+fn property_value(element: ElementRef, vocab: &Vocabulary) -> String {
+    let value = element.value();
+    if let Vocabulary::Rdfa = vocab {
+        if let Some(content) = value.attr("content") {
+            return content.to_string();
+        }
+    }
+
+    let attr = match value.name() {
+        "meta" => Some("content"),
+        "a" | "link" | "area" => Some("href"),
+        "img" | "audio" | "video" | "source" | "iframe" | "embed" | "track" => Some("src"),
+        "object" => Some("data"),
+        "time" => Some("datetime"),
+        "data" | "meter" => Some("value"),
+        _ => None,
+    };
+
+    attr.and_then(|attr| value.attr(attr))
+        .map(|v| v.to_string())
+        .unwrap_or_else(|| {
+            element
+                .text()
+                .flat_map(|t| t.split_whitespace())
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+}
+
+/// Extract `<meta>` properties with the given prefix, from `property` or `name`
+fn extract_meta_prefix(document: &Html, prefix: &str) -> Map<String, Value> {
+    let selector = Selector::parse("meta[content]").unwrap();
+    let mut properties = Map::new();
+
+    for meta in document.select(&selector) {
+        let key = meta
+            .value()
+            .attr("property")
+            .or_else(|| meta.value().attr("name"))
+            .map(|key| key.trim().to_lowercase());
+        let Some(key) = key.filter(|key| key.starts_with(prefix)) else {
+            continue;
+        };
+        let content = meta.value().attr("content").unwrap_or("").trim();
+        insert_value(&mut properties, &key, Value::String(content.to_string()));
+    }
+
+    properties
+}
+
+/// Insert a property value, turning repeated properties into arrays
+fn insert_value(map: &mut Map<String, Value>, key: &str, value: Value) {
+    match map.get_mut(key) {
+        Some(Value::Array(values)) => values.push(value),
+        Some(existing) => {
+            let first = existing.take();
+            *existing = Value::Array(vec![first, value]);
+        }
+        None => {
+            map.insert(key.to_string(), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_json_ld() {
+        let document = Html::parse_document(
+            r#"<script type="application/ld+json">
+            {"@context": "https://schema.org", "@graph": [
+                {"@type": "Organization", "name": "Acme"},
+                {"@type": ["Product", "IndividualProduct"], "name": "Rocket"}
+            ]}</script>
+            <script type="application/ld+json">{ invalid</script>"#,
+        );
+        let data = extract_structured_data(&document);
+        assert_eq!(data.json_ld.len(), 2);
+        assert_eq!(data.json_ld[0]["@context"], "https://schema.org");
+
+        let mut groups = BTreeMap::new();
+        group_by_type("https://a.com/", &data, &mut groups);
+        assert_eq!(
+            groups.keys().collect::<Vec<_>>(),
+            vec!["IndividualProduct", "Organization", "Product"]
+        );
+        let product = &groups["Product"][0];
+        assert_eq!(product.format, "json-ld");
+        assert_eq!(product.index, 1);
+        assert_eq!(
+            data.item(&product.format, product.index).unwrap()["name"],
+            "Rocket"
+        );
+        assert_eq!(data.item("rdfa", 0), None);
+    }
+
+    #[test]
+    fn test_microdata() {
+        let document = Html::parse_document(
+            r#"<div itemscope itemtype="https://schema.org/Event">
+                <h1 itemprop="name">Launch   party</h1>
+                <time itemprop="startDate" datetime="2024-05-01T19:00">May 1</time>
+                <div itemprop="location" itemscope itemtype="https://schema.org/Place">
+                    <span itemprop="name">Hangar 9</span>
+                </div>
+                <a itemprop="url sameAs" href="https://a.com/launch">Details</a>
+            </div>"#,
+        );
+        let data = extract_structured_data(&document);
+        assert_eq!(
+            data.microdata,
+            vec![json!({
+                "@type": "https://schema.org/Event",
+                "name": "Launch party",
+                "startDate": "2024-05-01T19:00",
+                "location": {"@type": "https://schema.org/Place", "name": "Hangar 9"},
+                "url": "https://a.com/launch",
+                "sameAs": "https://a.com/launch"
+            })]
+        );
+
+        let mut groups = BTreeMap::new();
+        group_by_type("https://a.com/", &data, &mut groups);
+        assert_eq!(groups.keys().collect::<Vec<_>>(), vec!["Event"]);
+    }
+
+    #[test]
+    fn test_rdfa_and_meta_tags() {
+        let document = Html::parse_document(
+            r#"<head>
+                <meta property="og:title" content="Rocket">
+                <meta property="og:image" content="/a.png">
+                <meta property="og:image" content="/b.png">
+                <meta name="twitter:card" content="summary">
+            </head><body vocab="https://schema.org/">
+                <div typeof="Product"><span property="name">Rocket</span>
+                <meta property="sku" content="R-1"></div>
+            </body>"#,
+        );
+        let data = extract_structured_data(&document);
+        assert_eq!(
+            data.rdfa,
+            vec![json!({"@type": "Product", "name": "Rocket", "sku": "R-1"})]
+        );
+        assert_eq!(data.opengraph["og:title"], "Rocket");
+        assert_eq!(data.opengraph["og:image"], json!(["/a.png", "/b.png"]));
+        assert_eq!(data.twitter["twitter:card"], "summary");
+    }
+}
@@ -0,0 +1,322 @@
+use crate::spider::error::SpiderError;
+use crate::spider::loader::{BatchOutcome, InputWarning, SiteOutcome};
+use crate::spider::utils::domain_to_filename;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// File name of the JSON batch summary
+pub const SUMMARY_JSON: &str = "batch_summary.json";
+
+/// File name of the CSV batch summary
+pub const SUMMARY_CSV: &str = "batch_summary.csv";
+
+/// How the crawl of a site ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SiteStatus {
+    /// The crawl finished and its result was saved
+    Completed,
+
+    /// The crawl stopped with an error
+    Failed,
+}
+
+impl SiteStatus {
+    /// Name used in the summary files
+    pub fn name(&self) -> &'static str {
+        match self {
+            SiteStatus::Completed => "completed",
+            SiteStatus::Failed => "failed",
+        }
+    }
+}
+
+/// One site of a batch summary
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SiteSummary {
+    /// The normalized start URL
+    pub url: String,
+
+    /// Labels from the input file
+    pub tags: Vec<String>,
+
+    /// Whether the crawl completed
+    pub status: SiteStatus,
+
+    /// The error that stopped the crawl
+    pub error: Option<String>,
+
+    /// Time spent on the site in milliseconds
+    pub duration_ms: u64,
+
+    /// Whether the site was completed by an earlier run of the batch
+    #[serde(default)]
+    pub resumed: bool,
+
+    /// The base domain of a completed crawl
+    pub base_domain: Option<String>,
+
+    /// Path of the saved result file
+    pub result_file: Option<String>,
+
+    /// Number of URLs found
+    pub found_urls: usize,
+
+    /// Number of pages fetched
+    pub pages: usize,
+
+    /// Number of skipped URLs
+    pub skipped_urls: usize,
+
+    /// Number of URLs that could not be fetched
+    pub unreachable_urls: usize,
+
+    /// Stats of the crawl result
+    pub stats: BTreeMap<String, usize>,
+}
+
+/// Totals over all sites of a batch
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchTotals {
+    /// Number of sites in the batch
+    pub sites: usize,
+
+    /// Number of completed crawls
+    pub completed: usize,
+
+    /// Number of failed crawls
+    pub failed: usize,
+
+    /// Number of URLs found
+    pub found_urls: usize,
+
+    /// Number of pages fetched
+    pub pages: usize,
+
+    /// Number of skipped URLs
+    pub skipped_urls: usize,
+
+    /// Number of URLs that could not be fetched
+    pub unreachable_urls: usize,
+
+    /// Wall-clock time of the batch in milliseconds
+    pub duration_ms: u64,
+}
+
+/// Per-site outcomes and totals of a batch crawl
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BatchSummary {
+    /// One entry per site, in input order
+    pub sites: Vec<SiteSummary>,
+
+    /// Totals over all sites
+    pub totals: BatchTotals,
+
+    /// Input lines left out as invalid or duplicate
+    #[serde(default)]
+    pub warnings: Vec<InputWarning>,
+}
+
+impl SiteSummary {
+    /// Summarize the outcome of one site
+    pub fn from_outcome(outcome: &SiteOutcome) -> Self {
+        let mut summary = Self {
+            url: outcome.url.clone(),
+            tags: outcome.tags.clone(),
+            status: SiteStatus::Failed,
+            error: None,
+            duration_ms: outcome.duration.as_millis() as u64,
+            resumed: outcome.resumed,
+            base_domain: None,
+            result_file: None,
+            found_urls: 0,
+            pages: 0,
+            skipped_urls: 0,
+            unreachable_urls: 0,
+            stats: BTreeMap::new(),
+        };
+
+        match &outcome.result {
+            Ok(result) => {
+                summary.status = SiteStatus::Completed;
+                summary.base_domain = Some(result.base_domain.clone());
+                summary.result_file = Some(domain_to_filename(&result.base_domain));
+                summary.found_urls = result.urls.len();
+                summary.pages = result.pages.len();
+                summary.skipped_urls = result.skipped_urls.values().map(Vec::len).sum();
+                summary.unreachable_urls = result.unreachable_urls.len();
+                summary.stats = result.stats.clone().into_iter().collect();
+            }
+            Err(e) => summary.error = Some(e.to_string()),
+        }
+        summary
+    }
+}
+
+impl BatchSummary {
+    /// Summarize the outcomes of a batch that took `duration` in total
+    pub fn from_outcomes(outcomes: &[SiteOutcome], duration: Duration) -> Self {
+        let sites: Vec<SiteSummary> = outcomes.iter().map(SiteSummary::from_outcome).collect();
+
+        let mut totals = BatchTotals {
+            sites: sites.len(),
+            duration_ms: duration.as_millis() as u64,
+            ..Default::default()
+        };
+        for site in &sites {
+            match site.status {
+                SiteStatus::Completed => totals.completed += 1,
+                SiteStatus::Failed => totals.failed += 1,
+            }
+            totals.found_urls += site.found_urls;
+            totals.pages += site.pages;
+            totals.skipped_urls += site.skipped_urls;
+            totals.unreachable_urls += site.unreachable_urls;
+        }
+
+        Self {
+            sites,
+            totals,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Summarize a batch that took `duration` in total, with its input warnings
+    pub fn from_batch(batch: &BatchOutcome, duration: Duration) -> Self {
+        Self {
+            warnings: batch.warnings.clone(),
+            ..Self::from_outcomes(&batch.sites, duration)
+        }
+    }
+
+    /// Write `batch_summary.json` and `batch_summary.csv` to a directory
+    ///
+    /// The CSV has one row per site followed by a `total` row.
+    pub fn write(&self, directory: &Path) -> Result<Vec<PathBuf>, SpiderError> {
+        std::fs::create_dir_all(directory)?;
+
+        let json_path = directory.join(SUMMARY_JSON);
+        let file = std::io::BufWriter::new(std::fs::File::create(&json_path)?);
+        serde_json::to_writer_pretty(file, self)?;
+
+        let csv_path = directory.join(SUMMARY_CSV);
+        let mut writer = csv::Writer::from_path(&csv_path)?;
+        writer.write_record([
+            "url",
+            "tags",
+            "status",
+            "error",
+            "duration_ms",
+            "resumed",
+            "base_domain",
+            "result_file",
+            "found_urls",
+            "pages",
+            "skipped_urls",
+            "unreachable_urls",
+        ])?;
+        for site in &self.sites {
+            writer.write_record([
+                site.url.clone(),
+                site.tags.join(";"),
+                site.status.name().to_string(),
+                site.error.clone().unwrap_or_default(),
+                site.duration_ms.to_string(),
+                site.resumed.to_string(),
+                site.base_domain.clone().unwrap_or_default(),
+                site.result_file.clone().unwrap_or_default(),
+                site.found_urls.to_string(),
+                site.pages.to_string(),
+                site.skipped_urls.to_string(),
+                site.unreachable_urls.to_string(),
+            ])?;
+        }
+
+        let totals = &self.totals;
+        writer.write_record([
+            "total".to_string(),
+            String::new(),
+            format!("{} completed, {} failed", totals.completed, totals.failed),
+            String::new(),
+            totals.duration_ms.to_string(),
+            String::new(),
+            String::new(),
+            String::new(),
+            totals.found_urls.to_string(),
+            totals.pages.to_string(),
+            totals.skipped_urls.to_string(),
+            totals.unreachable_urls.to_string(),
+        ])?;
+        writer.flush()?;
+
+        Ok(vec![json_path, csv_path])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spider::crawler::CrawlResult;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_summary_from_outcomes() {
+        let result = CrawlResult {
+            urls: vec!["https://a.com/".to_string(), "https://a.com/b".to_string()],
+            skipped_urls: HashMap::from([(
+                "robots_txt".to_string(),
+                vec!["https://a.com/admin".to_string()],
+            )]),
+            stats: HashMap::from([("pages".to_string(), 2)]),
+            ..CrawlResult::fixture(Vec::new())
+        };
+        let outcomes = [
+            SiteOutcome {
+                url: "https://a.com".to_string(),
+                tags: vec!["news".to_string()],
+                result: Ok(result),
+                duration: Duration::from_millis(1200),
+                resumed: false,
+            },
+            SiteOutcome {
+                url: "https://b.com".to_string(),
+                tags: Vec::new(),
+                result: Err(SpiderError::NetworkError("connection refused".to_string())),
+                duration: Duration::from_millis(30),
+                resumed: false,
+            },
+        ];
+
+        let summary = BatchSummary::from_outcomes(&outcomes, Duration::from_secs(2));
+        assert_eq!(
+            summary.totals,
+            BatchTotals {
+                sites: 2,
+                completed: 1,
+                failed: 1,
+                found_urls: 2,
+                pages: 0,
+                skipped_urls: 1,
+                unreachable_urls: 0,
+                duration_ms: 2000,
+            }
+        );
+        assert_eq!(summary.sites[0].base_domain.as_deref(), Some("a.com"));
+        assert_eq!(summary.sites[1].status, SiteStatus::Failed);
+        assert!(summary.sites[1]
+            .error
+            .as_deref()
+            .unwrap()
+            .contains("connection refused"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let paths = summary.write(dir.path()).unwrap();
+        let csv = std::fs::read_to_string(&paths[1]).unwrap();
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!(rows.len(), 4);
+        assert!(rows[1].starts_with("https://a.com,news,completed,,1200,false,a.com,"));
+        assert!(rows[3].starts_with("total,,\"1 completed, 1 failed\""));
+    }
+}
@@ -0,0 +1,434 @@
+use crate::spider::error::SpiderError;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use url::Url;
+
+/// Minimum number of distinct values at a path position before it is learned as `{slug}`
+pub const MIN_SLUG_VARIANTS: usize = 5;
+
+/// Number of example URLs reported per template
+pub const MAX_TEMPLATE_EXAMPLES: usize = 3;
+
+static HEX_ID_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"(?i)^([0-9a-f]{16,}|[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12})$",
+    )
+    .unwrap()
+});
+static PLACEHOLDER_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\{[^}/]*\}").unwrap());
+
+/// Reduce a URL to a template by replacing variable parts with placeholders
+///
+/// Numeric path segments become `{n}`, hex and UUID identifiers become `{id}`,
+/// and query parameters are sorted by name with their values replaced by `{v}`.
+pub fn url_template(url: &Url) -> String {
+    let path = url
+        .path()
+        .split('/')
+        .map(|segment| {
+            if !segment.is_empty() && segment.chars().all(|c| c.is_ascii_digit()) {
+                "{n}"
+            } else if HEX_ID_RE.is_match(segment) {
+                "{id}"
+            } else {
+                segment
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/");
+
+    let port = url.port().map(|port| format!(":{}", port)).unwrap_or_default();
+    let mut template = format!(
+        "{}://{}{}{}",
+        url.scheme(),
+        url.host_str().unwrap_or(""),
+        port,
+        path
+    );
+
+    let mut keys: Vec<String> = url.query_pairs().map(|(key, _)| key.into_owned()).collect();
+    keys.sort();
+    keys.dedup();
+    if !keys.is_empty() {
+        let query: Vec<String> = keys.iter().map(|key| format!("{}={{v}}", key)).collect();
+        template.push('?');
+        template.push_str(&query.join("&"));
+    }
+
+    template
+}
+
+/// Learn templates for a set of URLs
+///
+/// Starts from [`url_template`] and additionally replaces a path segment with
+/// `{slug}` when at least [`MIN_SLUG_VARIANTS`] URLs differ only in that segment,
+/// as long as the template keeps at least one literal segment. Returns one
+/// template per input URL, in order.
+pub fn learn_templates(urls: &[&str]) -> Vec<String> {
+    let mut templates: Vec<(String, Vec<String>, String)> = urls
+        .iter()
+        .map(|url| match Url::parse(url) {
+            Ok(url) => split_template(&url_template(&url)),
+            Err(_) => (url.to_string(), Vec::new(), String::new()),
+        })
+        .collect();
+
+    let max_segments = templates.iter().map(|(_, s, _)| s.len()).max().unwrap_or(0);
+    for position in 0..max_segments {
+        let key = |(origin, segments, query): &(String, Vec<String>, String)| {
+            let mut masked = segments.clone();
+            masked[position] = String::new();
+            format!("{}|{}|{}", origin, masked.join("/"), query)
+        };
+
+        let mut variants: HashMap<String, HashSet<String>> = HashMap::new();
+        for template in templates.iter().filter(|t| is_maskable(&t.1, position)) {
+            variants
+                .entry(key(template))
+                .or_default()
+                .insert(template.1[position].clone());
+        }
+
+        for template in templates.iter_mut() {
+            if is_maskable(&template.1, position)
+                && variants[&key(template)].len() >= MIN_SLUG_VARIANTS
+            {
+                template.1[position] = "{slug}".to_string();
+            }
+        }
+    }
+
+    templates
+        .into_iter()
+        .map(|(origin, segments, query)| {
+            let mut template = origin;
+            if !segments.is_empty() {
+                template.push('/');
+                template.push_str(&segments.join("/"));
+            }
+            template.push_str(&query);
+            template
+        })
+        .collect()
+}
+
+/// Split a template into its origin, path segments and query part
+fn split_template(template: &str) -> (String, Vec<String>, String) {
+    let (rest, query) = match template.find('?') {
+        Some(index) => template.split_at(index),
+        None => (template, ""),
+    };
+    let path_start = rest
+        .find("://")
+        .and_then(|scheme_end| rest[scheme_end + 3..].find('/').map(|i| scheme_end + 3 + i))
+        .unwrap_or(rest.len());
+
+    let segments = rest[path_start..]
+        .split('/')
+        .skip(1)
+        .map(|s| s.to_string())
+        .collect();
+    (rest[..path_start].to_string(), segments, query.to_string())
+}
+
+/// Check whether a segment may be replaced with `{slug}`
+///
+/// The segment must be a literal, and another literal segment must remain.
+fn is_maskable(segments: &[String], position: usize) -> bool {
+    let is_literal = |s: &String| !s.is_empty() && !s.starts_with('{');
+    segments.get(position).is_some_and(is_literal)
+        && segments
+            .iter()
+            .enumerate()
+            .any(|(i, s)| i != position && is_literal(s))
+}
+
+/// A limit on how many URLs matching a template are crawled
+#[derive(Debug, Clone)]
+pub struct TemplateQuota {
+    /// The template as given, e.g. `/product/{id}` or `/category/{slug}?page={n}`
+    pub pattern: String,
+
+    /// Maximum number of matching URLs added to the frontier
+    pub max_urls: usize,
+
+    /// Compiled path part of the pattern
+    path: Regex,
+
+    /// Required query parameters with an optional value pattern
+    query: Vec<(String, Option<Regex>)>,
+}
+
+impl TemplateQuota {
+    /// Create a quota from a path template
+    ///
+    /// `{n}` matches digits and any other `{name}` matches a single path segment or
+    /// query value. Query parameters in the pattern must be present in the URL, in
+    /// any order; other parameters are ignored.
+    pub fn new(pattern: &str, max_urls: usize) -> Result<Self, SpiderError> {
+        if !pattern.starts_with('/') {
+            return Err(SpiderError::Other(format!(
+                "Template quota pattern must start with '/': {}",
+                pattern
+            )));
+        }
+
+        let (path, query) = pattern.split_once('?').unwrap_or((pattern, ""));
+        let path_regex = format!("^{}/?$", placeholder_regex(path.trim_end_matches('/')));
+        let path = Regex::new(&path_regex).map_err(|e| {
+            SpiderError::Other(format!("Invalid template quota {}: {}", pattern, e))
+        })?;
+
+        let mut params = Vec::new();
+        for param in query.split('&').filter(|p| !p.is_empty()) {
+            let (key, value) = param.split_once('=').unwrap_or((param, ""));
+            let value = match value {
+                "" => None,
+                value if value.starts_with('{') && value != "{n}" => None,
+                value => Some(
+                    Regex::new(&format!("^{}$", placeholder_regex(value))).map_err(|e| {
+                        SpiderError::Other(format!("Invalid template quota {}: {}", pattern, e))
+                    })?,
+                ),
+            };
+            params.push((key.to_string(), value));
+        }
+
+        Ok(Self {
+            pattern: pattern.to_string(),
+            max_urls,
+            path,
+            query: params,
+        })
+    }
+
+    /// Check whether a URL matches the template
+    pub fn matches(&self, url: &Url) -> bool {
+        if !self.path.is_match(url.path()) {
+            return false;
+        }
+
+        self.query.iter().all(|(key, value)| {
+            url.query_pairs().any(|(k, v)| {
+                k == key.as_str() && value.as_ref().is_none_or(|re| re.is_match(&v))
+            })
+        })
+    }
+}
+
+/// Turn a template with `{...}` placeholders into a regular expression
+fn placeholder_regex(template: &str) -> String {
+    let mut regex = String::new();
+    let mut last = 0;
+    for placeholder in PLACEHOLDER_RE.find_iter(template) {
+        regex.push_str(&regex::escape(&template[last..placeholder.start()]));
+        regex.push_str(if placeholder.as_str() == "{n}" {
+            "[0-9]+"
+        } else {
+            "[^/]+"
+        });
+        last = placeholder.end();
+    }
+    regex.push_str(&regex::escape(&template[last..]));
+    regex
+}
+
+/// A learned URL template with crawl counts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateReport {
+    /// The learned template
+    pub template: String,
+
+    /// Number of URLs discovered, including those over a quota
+    pub discovered: usize,
+
+    /// Number of URLs that were fetched
+    pub crawled: usize,
+
+    /// Number of URLs skipped because a quota was exhausted
+    pub skipped_by_quota: usize,
+
+    /// A few URLs of this template
+    pub examples: Vec<String>,
+}
+
+/// Applies template quotas and records URLs for the template report
+#[derive(Debug, Default)]
+pub struct TemplateTracker {
+    /// Configured quotas
+    quotas: Vec<TemplateQuota>,
+
+    /// Number of URLs admitted per quota
+    quota_counts: Vec<usize>,
+
+    /// URLs admitted to the frontier, in discovery order
+    admitted: Vec<String>,
+
+    /// URLs rejected because a quota was exhausted
+    over_quota: Vec<String>,
+}
+
+impl TemplateTracker {
+    /// Create a tracker enforcing the given quotas
+    pub fn new(quotas: Vec<TemplateQuota>) -> Self {
+        Self {
+            quota_counts: vec![0; quotas.len()],
+            quotas,
+            ..Default::default()
+        }
+    }
+
+    /// Check a URL about to be added to the frontier against the quotas
+    ///
+    /// Returns the pattern of the exhausted quota if the URL should be skipped,
+    /// recording it as over quota. Otherwise nothing is counted until the URL
+    /// is passed to `admit`.
+    pub fn check(&mut self, url_str: &str) -> Option<String> {
+        let full = self
+            .matching(url_str)
+            .into_iter()
+            .find(|&i| self.quota_counts[i] >= self.quotas[i].max_urls)?;
+        self.over_quota.push(url_str.to_string());
+        Some(self.quotas[full].pattern.clone())
+    }
+
+    /// Record a URL added to the frontier, counting it towards every quota it matches
+    pub fn admit(&mut self, url_str: &str) {
+        for i in self.matching(url_str) {
+            self.quota_counts[i] += 1;
+        }
+        self.admitted.push(url_str.to_string());
+    }
+
+    /// Indexes of the quotas matching a URL
+    fn matching(&self, url_str: &str) -> Vec<usize> {
+        match Url::parse(url_str) {
+            Ok(url) => (0..self.quotas.len())
+                .filter(|&i| self.quotas[i].matches(&url))
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Build the template report, most common templates first
+    pub fn report(&self, crawled: &HashSet<&str>) -> Vec<TemplateReport> {
+        let urls: Vec<&str> = self
+            .admitted
+            .iter()
+            .chain(&self.over_quota)
+            .map(|url| url.as_str())
+            .collect();
+        let templates = learn_templates(&urls);
+
+        let mut reports: BTreeMap<&str, TemplateReport> = BTreeMap::new();
+        for (index, (url, template)) in urls.iter().zip(&templates).enumerate() {
+            let report = reports
+                .entry(template.as_str())
+                .or_insert_with(|| TemplateReport {
+                    template: template.clone(),
+                    discovered: 0,
+                    crawled: 0,
+                    skipped_by_quota: 0,
+                    examples: Vec::new(),
+                });
+            report.discovered += 1;
+            if crawled.contains(url) {
+                report.crawled += 1;
+            }
+            if index >= self.admitted.len() {
+                report.skipped_by_quota += 1;
+            }
+            if report.examples.len() < MAX_TEMPLATE_EXAMPLES {
+                report.examples.push(url.to_string());
+            }
+        }
+
+        let mut reports: Vec<TemplateReport> = reports.into_values().collect();
+        reports.sort_by_key(|report| std::cmp::Reverse(report.discovered));
+        reports
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_url_template() {
+        let url = Url::parse("https://example.com/product/123/reviews?sort=asc&page=2").unwrap();
+        assert_eq!(
+            url_template(&url),
+            "https://example.com/product/{n}/reviews?page={v}&sort={v}"
+        );
+        let url = Url::parse("http://localhost:8080/a").unwrap();
+        assert_eq!(url_template(&url), "http://localhost:8080/a");
+    }
+
+    #[test]
+    fn test_learn_slug_templates() {
+        let mut urls: Vec<String> = ["shoes", "hats", "bags", "belts", "socks"]
+            .iter()
+            .map(|slug| format!("https://example.com/category/{}?page=2", slug))
+            .collect();
+        urls.extend(
+            ["about", "contact", "blog", "faq", "jobs"]
+                .iter()
+                .map(|page| format!("https://example.com/{}", page)),
+        );
+        urls.push("https://example.com/category/shoes".to_string());
+        let urls: Vec<&str> = urls.iter().map(|s| s.as_str()).collect();
+
+        let templates = learn_templates(&urls);
+        assert_eq!(templates[0], "https://example.com/category/{slug}?page={v}");
+        assert_eq!(templates[5], "https://example.com/about");
+        assert_eq!(templates[10], "https://example.com/category/shoes");
+    }
+
+    #[test]
+    fn test_quota_matching() {
+        let quota = TemplateQuota::new("/category/{slug}?page={n}", 1).unwrap();
+        let matches = |url: &str| quota.matches(&Url::parse(url).unwrap());
+        assert!(matches(
+            "https://example.com/category/shoes?sort=asc&page=3"
+        ));
+        assert!(matches("https://example.com/category/shoes/?page=3"));
+        assert!(!matches("https://example.com/category/shoes?page=last"));
+        assert!(!matches("https://example.com/category/shoes"));
+        assert!(!matches("https://example.com/category/shoes/red?page=3"));
+        assert!(TemplateQuota::new("product/{id}", 1).is_err());
+    }
+
+    #[test]
+    fn test_tracker_quota_and_report() {
+        let mut tracker =
+            TemplateTracker::new(vec![TemplateQuota::new("/product/{id}", 2).unwrap()]);
+        tracker.admit("https://example.com/");
+        for id in 1..=4 {
+            let url = format!("https://example.com/product/{}", id);
+            let result = tracker.check(&url);
+            if id <= 2 {
+                assert_eq!(result, None);
+                tracker.admit(&url);
+            } else {
+                assert_eq!(result.as_deref(), Some("/product/{id}"));
+            }
+        }
+
+        // Checked but never admitted URLs do not use up the quota
+        let mut unused =
+            TemplateTracker::new(vec![TemplateQuota::new("/product/{id}", 1).unwrap()]);
+        assert_eq!(unused.check("https://example.com/product/1"), None);
+        assert_eq!(unused.check("https://example.com/product/2"), None);
+
+        let crawled = HashSet::from(["https://example.com/", "https://example.com/product/1"]);
+        let report = tracker.report(&crawled);
+        assert_eq!(report[0].template, "https://example.com/product/{n}");
+        assert_eq!(report[0].discovered, 4);
+        assert_eq!(report[0].crawled, 1);
+        assert_eq!(report[0].skipped_by_quota, 2);
+        assert_eq!(report[0].examples.len(), MAX_TEMPLATE_EXAMPLES);
+        assert_eq!(report[1].template, "https://example.com/");
+    }
+}
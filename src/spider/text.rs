@@ -0,0 +1,394 @@
+use scraper::node::Element;
+use scraper::{ElementRef, Html, Node, Selector};
+use serde::{Deserialize, Serialize};
+
+/// Elements never part of the main content
+const REMOVED_ELEMENTS: &[&str] = &[
+    "script", "style", "noscript", "template", "nav", "header", "footer", "aside", "form",
+    "iframe", "svg", "button", "select", "head",
+];
+
+/// Elements that start a new line of text
+const BLOCK_ELEMENTS: &[&str] = &[
+    "p",
+    "div",
+    "section",
+    "article",
+    "main",
+    "li",
+    "ul",
+    "ol",
+    "dl",
+    "dt",
+    "dd",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "blockquote",
+    "pre",
+    "table",
+    "tr",
+    "td",
+    "th",
+    "figure",
+    "figcaption",
+    "br",
+    "hr",
+];
+
+/// ARIA roles of boilerplate regions
+const BOILERPLATE_ROLES: &[&str] = &[
+    "navigation",
+    "banner",
+    "contentinfo",
+    "complementary",
+    "search",
+    "dialog",
+];
+
+/// Class names and ids of boilerplate regions, matched against whole class names
+const BOILERPLATE_CLASSES: &[&str] = &[
+    "nav",
+    "navbar",
+    "navigation",
+    "menu",
+    "main-menu",
+    "header",
+    "site-header",
+    "footer",
+    "site-footer",
+    "sidebar",
+    "cookie",
+    "cookies",
+    "cookie-banner",
+    "cookie-notice",
+    "banner",
+    "breadcrumb",
+    "breadcrumbs",
+    "share",
+    "share-buttons",
+    "sharing",
+    "social",
+    "social-links",
+    "comments",
+    "comment-list",
+    "advert",
+    "ads",
+    "popup",
+    "modal",
+    "newsletter",
+    "related",
+    "related-posts",
+];
+
+/// Elements that are kept inside `<main>` and `<article>`, where they hold the title or byline
+const CONTENT_CHROME_ELEMENTS: &[&str] = &["header", "footer"];
+
+/// Elements marking the main content
+const CONTENT_ELEMENTS: &[&str] = &["main", "article"];
+
+/// Number of bytes of text used for language detection
+const LANGUAGE_SAMPLE_BYTES: usize = 20_000;
+
+/// A heading in the page outline
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Heading {
+    /// Heading level, 1 for `<h1>` to 6 for `<h6>`
+    pub level: u8,
+
+    /// Text of the heading
+    pub text: String,
+}
+
+/// Readable main content of a page
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PageText {
+    /// Main content text, one block per line, cut at the configured size
+    pub text: String,
+
+    /// Whether `text` was cut
+    pub truncated: bool,
+
+    /// Number of words in the full main content
+    pub word_count: usize,
+
+    /// Headings in the main content, in document order
+    pub headings: Vec<Heading>,
+
+    /// Detected language as an ISO 639-3 code, e.g. `eng`
+    pub language: Option<String>,
+
+    /// Confidence of the language detection, from 0 to 1
+    pub language_confidence: Option<f64>,
+
+    /// Language declared in `<html lang>`
+    pub declared_language: Option<String>,
+}
+
+/// Extract the main content text of a page
+///
+/// The content root is the largest `<main>`, `<article>` or `role="main"`
+/// element, or `<body>` if there is none. Navigation, headers, footers, forms,
+/// scripts and elements with a boilerplate class name or id are dropped, except
+/// that headers and footers inside `<main>` or `<article>` are kept. The stored
+/// text is cut to at most `max_bytes` bytes.
+pub fn extract_page_text(document: &Html, max_bytes: usize) -> PageText {
+    let mut text = String::new();
+    let mut headings = Vec::new();
+    if let Some(root) = content_root(document) {
+        let in_content = is_content(root.value());
+        collect_text(root, in_content, &mut text, &mut headings);
+    }
+
+    let text = text
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let word_count = text.split_whitespace().count();
+    let detected = whatlang::detect(truncate(&text, LANGUAGE_SAMPLE_BYTES));
+    let declared_language = Selector::parse("html[lang]")
+        .ok()
+        .and_then(|selector| document.select(&selector).next())
+        .and_then(|html| html.value().attr("lang"))
+        .map(|lang| lang.trim().to_string())
+        .filter(|lang| !lang.is_empty());
+
+    let stored = truncate(&text, max_bytes);
+    PageText {
+        truncated: stored.len() < text.len(),
+        text: stored.to_string(),
+        word_count,
+        headings,
+        language: detected.as_ref().map(|info| info.lang().code().to_string()),
+        language_confidence: detected.as_ref().map(|info| info.confidence()),
+        declared_language,
+    }
+}
+
+/// Pick the element holding the main content
+fn content_root(document: &Html) -> Option<ElementRef<'_>> {
+    let candidates = Selector::parse(r#"main, article, [role="main"]"#).unwrap();
+    let text_len = |element: &ElementRef| element.text().map(|t| t.trim().len()).sum::<usize>();
+
+    document
+        .select(&candidates)
+        .max_by_key(text_len)
+        .or_else(|| {
+            let body = Selector::parse("body").unwrap();
+            document.select(&body).next()
+        })
+        .or_else(|| Some(document.root_element()))
+}
+
+/// Append the text below a node, one block element per line
+///
+/// `in_content` tells whether the node is inside `<main>` or `<article>`.
+fn collect_text(
+    element: ElementRef,
+    in_content: bool,
+    text: &mut String,
+    headings: &mut Vec<Heading>,
+) {
+    for child in element.children() {
+        match child.value() {
+            Node::Text(content) => text.push_str(content),
+            Node::Element(child_element) => {
+                let Some(child) = ElementRef::wrap(child) else {
+                    continue;
+                };
+                if is_boilerplate(child_element, in_content) {
+                    continue;
+                }
+
+                let name = child_element.name();
+                if let Some(level) = heading_level(name) {
+                    let heading = child
+                        .text()
+                        .flat_map(|t| t.split_whitespace())
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                    if !heading.is_empty() {
+                        headings.push(Heading {
+                            level,
+                            text: heading,
+                        });
+                    }
+                }
+
+                let is_block = BLOCK_ELEMENTS.contains(&name);
+                if is_block {
+                    text.push('\n');
+                } else {
+                    text.push(' ');
+                }
+                let in_content = in_content || is_content(child_element);
+                collect_text(child, in_content, text, headings);
+                if is_block {
+                    text.push('\n');
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Check whether an element marks the main content
+fn is_content(element: &Element) -> bool {
+    CONTENT_ELEMENTS.contains(&element.name())
+        || element
+            .attr("role")
+            .is_some_and(|role| role.trim().eq_ignore_ascii_case("main"))
+}
+
+/// Check whether an element is navigation, chrome or otherwise not content
+///
+/// `in_content` tells whether the element is inside `<main>` or `<article>`.
+fn is_boilerplate(element: &Element, in_content: bool) -> bool {
+    let name = element.name();
+    let removed = REMOVED_ELEMENTS.contains(&name)
+        && !(in_content && CONTENT_CHROME_ELEMENTS.contains(&name));
+    if removed || element.attr("hidden").is_some() || element.attr("aria-hidden") == Some("true") {
+        return true;
+    }
+
+    if element
+        .attr("role")
+        .is_some_and(|role| BOILERPLATE_ROLES.contains(&role.trim().to_ascii_lowercase().as_str()))
+    {
+        return true;
+    }
+
+    element
+        .attr("class")
+        .into_iter()
+        .flat_map(str::split_whitespace)
+        .chain(element.attr("id").map(str::trim))
+        .any(|name| {
+            BOILERPLATE_CLASSES
+                .iter()
+                .any(|boilerplate| name.eq_ignore_ascii_case(boilerplate))
+        })
+}
+
+/// Level of a heading element
+fn heading_level(name: &str) -> Option<u8> {
+    match name {
+        "h1" => Some(1),
+        "h2" => Some(2),
+        "h3" => Some(3),
+        "h4" => Some(4),
+        "h5" => Some(5),
+        "h6" => Some(6),
+        _ => None,
+    }
+}
+
+/// Cut a string to at most `max_bytes` bytes on a character boundary
+fn truncate(text: &str, max_bytes: usize) -> &str {
+    if text.len() <= max_bytes {
+        return text;
+    }
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: &str = r#"<html lang="en"><body>
+        <header><h1>Site name</h1></header>
+        <nav><a href="/">Home</a></nav>
+        <main>
+            <h1>Rust crawler guide</h1>
+            <p>This guide explains how a web crawler discovers pages by following links.</p>
+            <div class="share-buttons">Share on social media</div>
+            <h2>Getting   started</h2>
+            <p>Install the tool and point it at the site you want to explore.<br>Then wait.</p>
+            <script>track()</script>
+        </main>
+        <footer>Copyright</footer>
+    </body></html>"#;
+
+    #[test]
+    fn test_extract_main_content() {
+        let page = extract_page_text(&Html::parse_document(PAGE), 10_000);
+        assert_eq!(
+            page.text,
+            "Rust crawler guide\n\
+             This guide explains how a web crawler discovers pages by following links.\n\
+             Getting started\n\
+             Install the tool and point it at the site you want to explore.\n\
+             Then wait."
+        );
+        assert_eq!(page.word_count, 32);
+        assert_eq!(
+            page.headings,
+            vec![
+                Heading {
+                    level: 1,
+                    text: "Rust crawler guide".to_string()
+                },
+                Heading {
+                    level: 2,
+                    text: "Getting started".to_string()
+                },
+            ]
+        );
+        assert_eq!(page.language.as_deref(), Some("eng"));
+        assert_eq!(page.declared_language.as_deref(), Some("en"));
+        assert!(!page.truncated);
+    }
+
+    #[test]
+    fn test_boilerplate_class_names() {
+        let page = extract_page_text(
+            &Html::parse_document(
+                r#"<body>
+                <div id="site-header">Site name</div>
+                <div class="content with-sidebar">
+                    <p>Main text.</p>
+                    <div class="post has-comments">Text of a post with comments.</div>
+                    <div class="widget sidebar">Recent posts</div>
+                </div>
+            </body>"#,
+            ),
+            10_000,
+        );
+        assert_eq!(page.text, "Main text.\nText of a post with comments.");
+
+        let page = extract_page_text(
+            &Html::parse_document(
+                r#"<body><header class="site-header">Blog</header>
+                <article class="post">
+                    <header class="entry-header"><h1>Post title</h1></header>
+                    <p>Post body.</p>
+                    <nav class="post-navigation">Next post</nav>
+                    <footer class="entry-footer">Filed under news</footer>
+                </article>
+            </body>"#,
+            ),
+            10_000,
+        );
+        assert_eq!(page.text, "Post title\nPost body.\nFiled under news");
+        assert_eq!(page.headings[0].text, "Post title");
+    }
+
+    #[test]
+    fn test_text_cap() {
+        let page = extract_page_text(&Html::parse_document(PAGE), 10);
+        assert_eq!(page.text, "Rust crawl");
+        assert!(page.truncated);
+        assert_eq!(page.word_count, 32);
+        assert_eq!(truncate("caf\u{e9}", 4), "caf");
+    }
+}
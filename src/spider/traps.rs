@@ -0,0 +1,373 @@
+use crate::spider::config::defaults;
+use crate::spider::templates::url_template;
+use chrono::Datelike;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use url::Url;
+
+/// Settings for crawler trap detection
+#[derive(Debug, Clone)]
+pub struct TrapConfig {
+    /// URLs longer than this are treated as traps
+    pub max_url_length: usize,
+
+    /// URLs with more query parameters than this are treated as traps
+    pub max_query_params: usize,
+
+    /// Maximum number of distinct query parameter combinations per path
+    pub max_query_combinations: usize,
+
+    /// How many times a sequence of path segments may repeat back to back
+    pub max_path_repeats: usize,
+
+    /// Calendar pages more than this many years in the past are traps
+    pub calendar_years_back: i32,
+
+    /// Calendar pages more than this many years in the future are traps
+    pub calendar_years_ahead: i32,
+}
+
+impl Default for TrapConfig {
+    fn default() -> Self {
+        Self {
+            max_url_length: 1024,
+            max_query_params: 6,
+            max_query_combinations: 32,
+            max_path_repeats: 2,
+            calendar_years_back: 5,
+            calendar_years_ahead: 1,
+        }
+    }
+}
+
+/// Why a URL was classified as a trap
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrapKind {
+    /// The URL is longer than `max_url_length`
+    LongUrl,
+
+    /// The URL contains a session identifier
+    SessionId,
+
+    /// The path repeats the same segments over and over
+    RepeatingPath,
+
+    /// The URL is a calendar page far from the current date
+    Calendar,
+
+    /// The URL has more than `max_query_params` parameters
+    TooManyParams,
+
+    /// The path has more than `max_query_combinations` parameter combinations
+    QueryCombinations,
+
+    /// The URL's template already has the maximum number of URLs
+    TemplateCap(String),
+}
+
+impl TrapKind {
+    /// Skip reason recorded in the crawl result
+    pub fn reason(&self) -> &'static str {
+        match self {
+            TrapKind::LongUrl => "trap:long_url",
+            TrapKind::SessionId => "trap:session_id",
+            TrapKind::RepeatingPath => "trap:repeating_path",
+            TrapKind::Calendar => "trap:calendar",
+            TrapKind::TooManyParams => "trap:too_many_params",
+            TrapKind::QueryCombinations => "trap:query_combinations",
+            TrapKind::TemplateCap(_) => "trap:template_cap",
+        }
+    }
+}
+
+static SESSION_QUERY_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)^(phpsessid|jsessionid|sessionid|session_id|sessid|sid|aspsessionid\w*|cfid|cftoken)$")
+        .unwrap()
+});
+static SESSION_PATH_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)(;jsessionid=|;sessionid=|/\([a-z]\([a-z0-9]{16,}\)\))").unwrap()
+});
+static DATE_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^((?:19|20)\d{2})(?:-(0?[1-9]|1[0-2]))?(?:-\d{1,2})?$").unwrap());
+static CALENDAR_KEY_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?i)^(date|day|month|year|cal|calendar|ym|week)$").unwrap());
+
+/// Detects crawler traps across every URL admitted to the frontier
+pub struct TrapDetector {
+    /// Detection settings
+    config: TrapConfig,
+
+    /// Maximum number of URLs admitted per template
+    max_urls_per_template: usize,
+
+    /// Number of URLs admitted per template
+    template_counts: HashMap<String, usize>,
+
+    /// Distinct query parameter name sets seen per path
+    query_combinations: HashMap<String, HashSet<String>>,
+
+    /// Year used as reference for calendar detection
+    current_year: i32,
+}
+
+impl Default for TrapDetector {
+    fn default() -> Self {
+        Self::new(TrapConfig::default(), defaults::MAX_URLS_PER_TEMPLATE)
+    }
+}
+
+impl TrapDetector {
+    /// Create a detector; `max_urls_per_template` caps how many URLs of one template are crawled
+    pub fn new(config: TrapConfig, max_urls_per_template: usize) -> Self {
+        Self {
+            config,
+            max_urls_per_template,
+            template_counts: HashMap::new(),
+            query_combinations: HashMap::new(),
+            current_year: chrono::Utc::now().year(),
+        }
+    }
+
+    /// Check a URL about to be added to the frontier
+    ///
+    /// Returns the trap kind if the URL should be skipped; otherwise the URL is
+    /// counted towards its template and parameter combination limits.
+    pub fn check(&mut self, url_str: &str) -> Option<TrapKind> {
+        if url_str.len() > self.config.max_url_length {
+            return Some(TrapKind::LongUrl);
+        }
+
+        let url = Url::parse(url_str).ok()?;
+
+        if has_session_id(&url) {
+            return Some(TrapKind::SessionId);
+        }
+
+        if has_repeating_path(&url, self.config.max_path_repeats) {
+            return Some(TrapKind::RepeatingPath);
+        }
+
+        if let Some(year) = calendar_year(&url) {
+            let min = self.current_year - self.config.calendar_years_back;
+            let max = self.current_year + self.config.calendar_years_ahead;
+            if year < min || year > max {
+                return Some(TrapKind::Calendar);
+            }
+        }
+
+        let mut keys: Vec<String> = url.query_pairs().map(|(key, _)| key.into_owned()).collect();
+        if keys.len() > self.config.max_query_params {
+            return Some(TrapKind::TooManyParams);
+        }
+
+        let template = url_template(&url);
+        if self.template_counts.get(&template).copied().unwrap_or(0) >= self.max_urls_per_template {
+            return Some(TrapKind::TemplateCap(template));
+        }
+
+        if !keys.is_empty() {
+            keys.sort();
+            keys.dedup();
+            let path = format!("{}{}", url.host_str().unwrap_or(""), url.path());
+            let combinations = self.query_combinations.entry(path).or_default();
+            let combination = keys.join("&");
+            if !combinations.contains(&combination) {
+                if combinations.len() >= self.config.max_query_combinations {
+                    return Some(TrapKind::QueryCombinations);
+                }
+                combinations.insert(combination);
+            }
+        }
+
+        *self.template_counts.entry(template).or_insert(0) += 1;
+        None
+    }
+}
+
+/// Check for session identifiers in the path or query
+fn has_session_id(url: &Url) -> bool {
+    SESSION_PATH_RE.is_match(url.path())
+        || url
+            .query_pairs()
+            .any(|(key, value)| !value.is_empty() && SESSION_QUERY_RE.is_match(&key))
+}
+
+/// Check whether a sequence of path segments repeats back to back
+///
+/// Single segments may repeat up to `max_repeats` times (`/a/a`), longer
+/// sequences only up to `max_repeats - 1` times, so `/a/b/a/b` is a trap with the
+/// default of 2.
+fn has_repeating_path(url: &Url, max_repeats: usize) -> bool {
+    let segments: Vec<&str> = url.path().split('/').filter(|s| !s.is_empty()).collect();
+
+    for len in 1..=segments.len() / 2 {
+        let allowed = if len == 1 {
+            max_repeats
+        } else {
+            max_repeats.saturating_sub(1).max(1)
+        };
+        for start in 0..segments.len() {
+            let mut repeats = 1;
+            let mut next = start + len;
+            while next + len <= segments.len()
+                && segments[next..next + len] == segments[start..start + len]
+            {
+                repeats += 1;
+                next += len;
+            }
+            if repeats > allowed {
+                return true;
+            }
+        }
+    }
+
+    false
+}
+
+/// Find the year of a calendar-style URL
+///
+/// A URL is a calendar page when its path ends in date segments (`/2024/05`,
+/// `/2024-05-12`) or a calendar-like query parameter holds a date.
+fn calendar_year(url: &Url) -> Option<i32> {
+    for (key, value) in url.query_pairs() {
+        if CALENDAR_KEY_RE.is_match(&key) {
+            if let Some(captures) = DATE_RE.captures(&value) {
+                return captures[1].parse().ok();
+            }
+        }
+    }
+
+    let segments: Vec<&str> = url.path().split('/').filter(|s| !s.is_empty()).collect();
+    let last = *segments.last()?;
+    if let Some(captures) = DATE_RE.captures(last) {
+        if last.contains('-') {
+            return captures[1].parse().ok();
+        }
+    }
+
+    // Trailing /YYYY, /YYYY/MM or /YYYY/MM/DD segments
+    let numeric_tail = segments
+        .iter()
+        .rev()
+        .take_while(|segment| segment.len() <= 4 && segment.chars().all(|c| c.is_ascii_digit()))
+        .count();
+    if (1..=3).contains(&numeric_tail) {
+        let year_segment = segments[segments.len() - numeric_tail];
+        if year_segment.len() == 4
+            && DATE_RE.is_match(year_segment)
+            && (numeric_tail > 1 || segments.len() == 1)
+        {
+            return year_segment.parse().ok();
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detector() -> TrapDetector {
+        let mut detector = TrapDetector::new(TrapConfig::default(), 3);
+        detector.current_year = 2024;
+        detector
+    }
+
+    #[test]
+    fn test_template_cap() {
+        let mut detector = detector();
+        for id in 1..=3 {
+            assert_eq!(
+                detector.check(&format!("https://example.com/item/{}", id)),
+                None
+            );
+        }
+        assert_eq!(
+            detector.check("https://example.com/item/4"),
+            Some(TrapKind::TemplateCap(
+                "https://example.com/item/{n}".to_string()
+            ))
+        );
+        assert_eq!(detector.check("https://example.com/other/1"), None);
+    }
+
+    #[test]
+    fn test_session_ids() {
+        let mut detector = detector();
+        assert_eq!(
+            detector.check("https://example.com/a;jsessionid=ABC123"),
+            Some(TrapKind::SessionId)
+        );
+        assert_eq!(
+            detector.check("https://example.com/a?PHPSESSID=abc"),
+            Some(TrapKind::SessionId)
+        );
+        assert_eq!(detector.check("https://example.com/a?side=left"), None);
+    }
+
+    #[test]
+    fn test_repeating_path() {
+        let mut detector = detector();
+        assert_eq!(
+            detector.check("https://example.com/a/b/a/b/c"),
+            Some(TrapKind::RepeatingPath)
+        );
+        assert_eq!(
+            detector.check("https://example.com/x/x/x"),
+            Some(TrapKind::RepeatingPath)
+        );
+        assert_eq!(detector.check("https://example.com/news/news"), None);
+        assert_eq!(detector.check("https://example.com/a/b/c/a"), None);
+    }
+
+    #[test]
+    fn test_calendar() {
+        let mut detector = detector();
+        assert_eq!(
+            detector.check("https://example.com/events/2031/02"),
+            Some(TrapKind::Calendar)
+        );
+        assert_eq!(
+            detector.check("https://example.com/calendar?month=1987-04"),
+            Some(TrapKind::Calendar)
+        );
+        assert_eq!(detector.check("https://example.com/events/2024/05"), None);
+        // Archive posts with a slug are not calendar pages
+        assert_eq!(
+            detector.check("https://example.com/2001/05/some-post"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_query_limits() {
+        let mut detector = TrapDetector::new(
+            TrapConfig {
+                max_query_params: 2,
+                max_query_combinations: 2,
+                ..TrapConfig::default()
+            },
+            100,
+        );
+        assert_eq!(
+            detector.check("https://example.com/s?a=1&b=2&c=3"),
+            Some(TrapKind::TooManyParams)
+        );
+        assert_eq!(detector.check("https://example.com/s?a=1"), None);
+        assert_eq!(detector.check("https://example.com/s?b=1"), None);
+        assert_eq!(detector.check("https://example.com/s?a=2"), None);
+        assert_eq!(
+            detector.check("https://example.com/s?a=1&b=1"),
+            Some(TrapKind::QueryCombinations)
+        );
+    }
+
+    #[test]
+    fn test_long_url() {
+        let mut detector = detector();
+        let url = format!("https://example.com/{}", "a".repeat(2000));
+        assert_eq!(detector.check(&url), Some(TrapKind::LongUrl));
+    }
+}
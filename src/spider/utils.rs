@@ -1,6 +1,8 @@
 use crate::spider::error::SpiderError;
 use anyhow::Result;
+use encoding_rs::{Encoding, UTF_8};
 use regex::Regex;
+use std::borrow::Cow;
 use std::path::Path;
 use url::Url;
 
@@ -52,6 +54,121 @@ pub fn is_same_domain(url_str: &str, base_domain: &str) -> Result<bool, SpiderEr
     Ok(false)
 }
 
+/// Which hosts count as the same site as the start URL
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DomainScope {
+    /// Only the start host (a leading `www.` is ignored)
+    ExactHost,
+
+    /// The start host and all of its subdomains
+    #[default]
+    AllSubdomains,
+
+    /// Every host sharing the start host's registrable domain per the Public Suffix List,
+    /// e.g. `shop.example.co.uk` and `www.example.co.uk` both belong to `example.co.uk`
+    RegistrableDomain,
+}
+
+impl DomainScope {
+    /// Parse a scope name such as `exact_host`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "exact_host" => Some(DomainScope::ExactHost),
+            "all_subdomains" => Some(DomainScope::AllSubdomains),
+            "registrable_domain" => Some(DomainScope::RegistrableDomain),
+            _ => None,
+        }
+    }
+}
+
+/// Get the registrable domain of a host according to the Public Suffix List
+///
+/// Falls back to the lowercased host for IP addresses and hosts without a known suffix.
+pub fn registrable_domain(host: &str) -> String {
+    let host = host.trim_end_matches('.').to_lowercase();
+    if host.parse::<std::net::IpAddr>().is_ok() || host.starts_with('[') {
+        return host;
+    }
+
+    psl::domain_str(&host)
+        .map(|domain| domain.to_string())
+        .unwrap_or(host)
+}
+
+/// Check if a URL is in scope for a crawl of `base_domain`
+///
+/// Hosts listed in `allowed_hosts` (and their `www.` variants) are always in scope.
+pub fn is_in_domain_scope(
+    url_str: &str,
+    base_domain: &str,
+    scope: DomainScope,
+    allowed_hosts: &[String],
+) -> Result<bool, SpiderError> {
+    let url = Url::parse(url_str).map_err(SpiderError::UrlParse)?;
+
+    let host = url
+        .host_str()
+        .ok_or_else(|| SpiderError::InvalidUrl(format!("No host in URL: {}", url_str)))?
+        .to_lowercase();
+    let normalized_host = host.strip_prefix("www.").unwrap_or(&host);
+
+    let allowed = allowed_hosts.iter().any(|allowed| {
+        let allowed = allowed.to_lowercase();
+        normalized_host == allowed.strip_prefix("www.").unwrap_or(&allowed)
+    });
+    if allowed {
+        return Ok(true);
+    }
+
+    match scope {
+        DomainScope::ExactHost => Ok(normalized_host == base_domain),
+        DomainScope::AllSubdomains => is_same_domain(url_str, base_domain),
+        DomainScope::RegistrableDomain => {
+            Ok(registrable_domain(&host) == registrable_domain(base_domain))
+        }
+    }
+}
+
+/// Normalize a start URL typed by a user, e.g. in a batch input file
+///
+/// A URL without a scheme gets `https://`. Only HTTP(S) URLs with a host are
+/// accepted. The host is lowercased, a default port and the fragment are
+/// dropped, and an empty path becomes `/`.
+pub fn normalize_input_url(input: &str) -> Result<String, SpiderError> {
+    let input = input.trim();
+    let with_scheme = if has_scheme(input) {
+        input.to_string()
+    } else {
+        format!("https://{}", input.trim_start_matches('/'))
+    };
+
+    let mut url = Url::parse(&with_scheme)?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(SpiderError::InvalidUrl(format!(
+            "Unsupported scheme: {}",
+            url.scheme()
+        )));
+    }
+    if url.host_str().is_none_or(str::is_empty) {
+        return Err(SpiderError::InvalidUrl(format!("No host in URL: {}", input)));
+    }
+    url.set_fragment(None);
+
+    Ok(url.to_string())
+}
+
+/// Check whether a URL starts with a scheme such as `https://`
+///
+/// `://` later in the URL, e.g. in a query string, does not count.
+fn has_scheme(input: &str) -> bool {
+    input.split_once("://").is_some_and(|(scheme, _)| {
+        scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    })
+}
+
 /// Normalize a URL by handling redirects
 pub fn normalize_url(url_str: &str) -> Result<String, SpiderError> {
     let url = Url::parse(url_str).map_err(SpiderError::UrlParse)?;
@@ -68,11 +185,209 @@ pub fn resolve_url(base_url: &str, relative_url: &str) -> Result<String, SpiderE
     Ok(absolute_url.to_string())
 }
 
+/// Resolve a relative URL found in a document with the given encoding
+///
+/// As in browsers, the query string is percent-encoded using the document's
+/// encoding rather than UTF-8.
+pub fn resolve_url_with_encoding(
+    base_url: &str,
+    relative_url: &str,
+    encoding: &'static Encoding,
+) -> Result<String, SpiderError> {
+    if encoding == UTF_8 {
+        return resolve_url(base_url, relative_url);
+    }
+
+    let base = Url::parse(base_url).map_err(SpiderError::UrlParse)?;
+    let encode: &dyn Fn(&str) -> Cow<'_, [u8]> =
+        &|input| encoding.output_encoding().encode(input).0;
+
+    let absolute_url = Url::options()
+        .base_url(Some(&base))
+        .encoding_override(Some(encode))
+        .parse(relative_url)
+        .map_err(SpiderError::UrlParse)?;
+
+    Ok(absolute_url.to_string())
+}
+
 /// Check if a URL contains any of the skip patterns
 pub fn should_skip_url(url: &str, skip_patterns: &[String]) -> bool {
     skip_patterns.iter().any(|pattern| url.contains(pattern))
 }
 
+/// Whether a scope rule includes or excludes matching URLs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScopeAction {
+    /// Crawl matching URLs
+    #[default]
+    Include,
+
+    /// Skip matching URLs
+    Exclude,
+}
+
+impl ScopeAction {
+    /// Parse an action name, `include` or `exclude`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "include" => Some(ScopeAction::Include),
+            "exclude" => Some(ScopeAction::Exclude),
+            _ => None,
+        }
+    }
+}
+
+/// The part of a URL a scope rule is matched against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScopeTarget {
+    /// The host name, e.g. `shop.example.com`
+    Host,
+
+    /// The path, e.g. `/products/1`
+    Path,
+
+    /// The query string without the leading `?`, empty if there is none
+    Query,
+}
+
+impl ScopeTarget {
+    /// Parse a target name, `host`, `path` or `query`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "host" => Some(ScopeTarget::Host),
+            "path" => Some(ScopeTarget::Path),
+            "query" => Some(ScopeTarget::Query),
+            _ => None,
+        }
+    }
+}
+
+/// How a scope rule pattern is matched
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScopePattern {
+    /// The target starts with the given string
+    Prefix(String),
+
+    /// Shell-style glob: `*` matches anything except `/`, `**` matches anything, `?` one character
+    Glob(String),
+
+    /// Regular expression searched anywhere in the target
+    Regex(String),
+}
+
+/// A named include or exclude rule
+#[derive(Debug, Clone)]
+pub struct ScopeRule {
+    /// Name recorded as the skip reason when the rule excludes a URL
+    pub name: String,
+
+    /// Whether matching URLs are included or excluded
+    pub action: ScopeAction,
+
+    /// The part of the URL to match
+    pub target: ScopeTarget,
+
+    /// The pattern as configured
+    pub pattern: ScopePattern,
+
+    /// Compiled form of glob and regex patterns
+    regex: Option<Regex>,
+}
+
+impl ScopeRule {
+    /// Create a rule, compiling its pattern
+    pub fn new(
+        name: &str,
+        action: ScopeAction,
+        target: ScopeTarget,
+        pattern: ScopePattern,
+    ) -> Result<Self, SpiderError> {
+        let regex = match &pattern {
+            ScopePattern::Prefix(_) => None,
+            ScopePattern::Glob(glob) => Some(glob_to_regex(glob)?),
+            ScopePattern::Regex(re) => Some(Regex::new(re).map_err(|e| {
+                SpiderError::Other(format!("Invalid regex in scope rule {}: {}", name, e))
+            })?),
+        };
+
+        Ok(Self {
+            name: name.to_string(),
+            action,
+            target,
+            pattern,
+            regex,
+        })
+    }
+
+    /// Check whether the rule matches a parsed URL
+    pub fn matches(&self, url: &Url) -> bool {
+        let value = match self.target {
+            ScopeTarget::Host => url.host_str().unwrap_or(""),
+            ScopeTarget::Path => url.path(),
+            ScopeTarget::Query => url.query().unwrap_or(""),
+        };
+
+        match (&self.pattern, &self.regex) {
+            (ScopePattern::Prefix(prefix), _) => value.starts_with(prefix.as_str()),
+            (_, Some(regex)) => regex.is_match(value),
+            _ => false,
+        }
+    }
+}
+
+/// Outcome of evaluating scope rules against a URL
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScopeDecision {
+    /// A rule included the URL
+    Included(String),
+
+    /// A rule excluded the URL
+    Excluded(String),
+
+    /// No rule matched; the default action applies
+    Default(ScopeAction),
+}
+
+/// Evaluate scope rules in order; the first matching rule wins
+pub fn evaluate_scope(
+    url_str: &str,
+    rules: &[ScopeRule],
+    default: ScopeAction,
+) -> Result<ScopeDecision, SpiderError> {
+    let url = Url::parse(url_str).map_err(SpiderError::UrlParse)?;
+
+    Ok(rules
+        .iter()
+        .find(|rule| rule.matches(&url))
+        .map(|rule| match rule.action {
+            ScopeAction::Include => ScopeDecision::Included(rule.name.clone()),
+            ScopeAction::Exclude => ScopeDecision::Excluded(rule.name.clone()),
+        })
+        .unwrap_or(ScopeDecision::Default(default)))
+}
+
+/// Translate a glob pattern into an anchored regex
+fn glob_to_regex(glob: &str) -> Result<Regex, SpiderError> {
+    let mut re = String::from("^");
+    let mut chars = glob.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                re.push_str(".*");
+            }
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            _ => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re.push('$');
+
+    Regex::new(&re).map_err(|e| SpiderError::Other(format!("Invalid glob {}: {}", glob, e)))
+}
+
 /// Check if a URL has a subdomain that matches any of the skip patterns
 pub fn should_skip_subdomain(
     url_str: &str,
@@ -101,40 +416,26 @@ pub fn is_priority_url(url: &str, priority_paths: &[String]) -> bool {
     priority_paths.iter().any(|path| url.contains(path))
 }
 
-/// Detect if a list of URLs contains a pattern that would indicate massive links
-pub fn detect_massive_links_pattern(urls: &[String], threshold: usize) -> Option<String> {
-    if urls.len() < threshold {
-        return None;
-    }
-
-    // Simple pattern detection: look for URLs that follow a numeric pattern
-    let re = Regex::new(r"(.*?)(\d+)(.*)").unwrap();
-
-    let mut patterns: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
-
-    for url in urls {
-        if let Some(captures) = re.captures(url) {
-            if captures.len() >= 4 {
-                let prefix = captures.get(1).unwrap().as_str();
-                let suffix = captures.get(3).unwrap().as_str();
-                let pattern = format!("{}*{}", prefix, suffix);
-
-                *patterns.entry(pattern).or_insert(0) += 1;
-            }
-        }
-    }
+/// Extract the robots.txt product token from a user agent
+///
+/// The token is the leading run of `[a-zA-Z_-]` characters, e.g. `TinyCrawler/0.1` becomes `TinyCrawler`.
+pub fn robots_product_token(user_agent: &str) -> &str {
+    let end = user_agent
+        .find(|c: char| !(c.is_ascii_alphabetic() || c == '-' || c == '_'))
+        .unwrap_or(user_agent.len());
+    &user_agent[..end]
+}
 
-    // Find patterns that exceed the threshold
-    patterns
-        .into_iter()
-        .filter(|(_, count)| *count >= threshold)
-        .max_by_key(|(_, count)| *count)
-        .map(|(pattern, _)| pattern)
+/// Name of the result file of a domain, without its directory
+///
+/// Crawls whose base domains give the same name write the same files.
+pub fn result_file_name(domain: &str) -> String {
+    domain.replace(".", "_").replace(":", "_") + ".json"
 }
 
 /// Generate a filename from a domain
 pub fn domain_to_filename(domain: &str) -> String {
-    let filename = domain.replace(".", "_").replace(":", "_") + ".json";
+    let filename = result_file_name(domain);
     
     // Create output/crawler directory if it doesn't exist
     let output_dir = Path::new("output").join("crawler");
@@ -150,6 +451,29 @@ pub fn domain_to_filename(domain: &str) -> String {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_normalize_input_url() {
+        for input in [
+            "example.com",
+            "https://Example.COM",
+            "https://example.com:443/",
+            " https://example.com/#top ",
+        ] {
+            assert_eq!(normalize_input_url(input).unwrap(), "https://example.com/");
+        }
+        assert_eq!(
+            normalize_input_url("http://www.example.com/shop?x=1").unwrap(),
+            "http://www.example.com/shop?x=1"
+        );
+        assert_eq!(
+            normalize_input_url("example.com/?next=http://x").unwrap(),
+            "https://example.com/?next=http://x"
+        );
+        assert!(normalize_input_url("ftp://example.com").is_err());
+        assert!(normalize_input_url("https://").is_err());
+        assert!(normalize_input_url("not a url").is_err());
+    }
+
     #[test]
     fn test_extract_base_domain_with_subdomain() {
         let url = "https://camps.example.com";
@@ -203,6 +527,53 @@ mod tests {
         assert!(result);
     }
 
+    #[test]
+    fn test_domain_scope_exact_host() {
+        let scope = DomainScope::ExactHost;
+        assert!(
+            is_in_domain_scope("https://www.example.com/a", "example.com", scope, &[]).unwrap()
+        );
+        assert!(
+            !is_in_domain_scope("https://shop.example.com/", "example.com", scope, &[]).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_domain_scope_registrable_domain() {
+        let scope = DomainScope::RegistrableDomain;
+        let base = "shop.example.co.uk";
+        assert!(is_in_domain_scope("https://www.example.co.uk/", base, scope, &[]).unwrap());
+        assert!(is_in_domain_scope("https://blog.example.co.uk/", base, scope, &[]).unwrap());
+        assert!(!is_in_domain_scope("https://other.co.uk/", base, scope, &[]).unwrap());
+        // Subdomain scope would not include the parent domain
+        assert!(!is_in_domain_scope(
+            "https://example.co.uk/",
+            base,
+            DomainScope::AllSubdomains,
+            &[]
+        )
+        .unwrap());
+    }
+
+    #[test]
+    fn test_domain_scope_allowed_hosts() {
+        let allowed = vec!["example.net".to_string()];
+        let scope = DomainScope::ExactHost;
+        assert!(
+            is_in_domain_scope("https://www.example.net/", "example.com", scope, &allowed).unwrap()
+        );
+        assert!(
+            !is_in_domain_scope("https://shop.example.net/", "example.com", scope, &allowed)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_registrable_domain() {
+        assert_eq!(registrable_domain("a.b.example.co.uk"), "example.co.uk");
+        assert_eq!(registrable_domain("127.0.0.1"), "127.0.0.1");
+    }
+
     #[test]
     fn test_resolve_url() {
         let base_url = "https://domain.com/faq";
@@ -211,6 +582,15 @@ mod tests {
         assert_eq!(result, "https://domain.com/q1");
     }
 
+    #[test]
+    fn test_resolve_url_with_encoding() {
+        let base_url = "https://domain.com/faq";
+        let relative_url = "/\u{5546}\u{54c1}?q=\u{5546}\u{54c1}";
+        let result =
+            resolve_url_with_encoding(base_url, relative_url, encoding_rs::SHIFT_JIS).unwrap();
+        assert_eq!(result, "https://domain.com/%E5%95%86%E5%93%81?q=%8F%A4%95i");
+    }
+
     #[test]
     fn test_should_skip_url() {
         let url = "https://example.com/docs/1";
@@ -218,6 +598,85 @@ mod tests {
         assert!(should_skip_url(url, &skip_patterns));
     }
 
+    #[test]
+    fn test_scope_rules_first_match_wins() {
+        let rules = vec![
+            ScopeRule::new(
+                "products",
+                ScopeAction::Include,
+                ScopeTarget::Path,
+                ScopePattern::Prefix("/products/".to_string()),
+            )
+            .unwrap(),
+            ScopeRule::new(
+                "blog",
+                ScopeAction::Exclude,
+                ScopeTarget::Path,
+                ScopePattern::Prefix("/blog/".to_string()),
+            )
+            .unwrap(),
+        ];
+
+        let decide = |url| evaluate_scope(url, &rules, ScopeAction::Exclude).unwrap();
+        assert_eq!(
+            decide("https://example.com/products/1"),
+            ScopeDecision::Included("products".to_string())
+        );
+        assert_eq!(
+            decide("https://example.com/blog/post"),
+            ScopeDecision::Excluded("blog".to_string())
+        );
+        assert_eq!(
+            decide("https://example.com/catalog/blog/post"),
+            ScopeDecision::Default(ScopeAction::Exclude)
+        );
+    }
+
+    #[test]
+    fn test_scope_rule_glob_and_regex() {
+        let glob = ScopeRule::new(
+            "category pages",
+            ScopeAction::Exclude,
+            ScopeTarget::Path,
+            ScopePattern::Glob("/category/*/page/**".to_string()),
+        )
+        .unwrap();
+        let url = Url::parse("https://example.com/category/shoes/page/2/x").unwrap();
+        assert!(glob.matches(&url));
+        let url = Url::parse("https://example.com/category/a/b/page/2").unwrap();
+        assert!(!glob.matches(&url));
+
+        let host = ScopeRule::new(
+            "shop host",
+            ScopeAction::Include,
+            ScopeTarget::Host,
+            ScopePattern::Glob("shop.*.com".to_string()),
+        )
+        .unwrap();
+        assert!(host.matches(&Url::parse("https://shop.example.com/").unwrap()));
+
+        let query = ScopeRule::new(
+            "sorting",
+            ScopeAction::Exclude,
+            ScopeTarget::Query,
+            ScopePattern::Regex(r"(^|&)sort=".to_string()),
+        )
+        .unwrap();
+        assert!(query.matches(&Url::parse("https://example.com/?page=2&sort=asc").unwrap()));
+        assert!(!query.matches(&Url::parse("https://example.com/?presort=1").unwrap()));
+    }
+
+    #[test]
+    fn test_scope_rule_invalid_regex() {
+        let rule = ScopeRule::new(
+            "broken",
+            ScopeAction::Exclude,
+            ScopeTarget::Path,
+            ScopePattern::Regex("(".to_string()),
+        );
+        assert!(rule.is_err());
+    }
+
     #[test]
     fn test_should_skip_subdomain() {
         let url = "https://docs.example.com";
@@ -247,19 +706,9 @@ mod tests {
     }
 
     #[test]
-    fn test_detect_massive_links_pattern() {
-        let urls = vec![
-            "domain.com/a/pattern/1".to_string(),
-            "domain.com/a/pattern/2".to_string(),
-            "domain.com/a/pattern/3".to_string(),
-            "domain.com/a/pattern/4".to_string(),
-            "domain.com/a/pattern/5".to_string(),
-            "domain.com/other/url".to_string(),
-        ];
-
-        let pattern = detect_massive_links_pattern(&urls, 5);
-        assert!(pattern.is_some());
-        assert_eq!(pattern.unwrap(), "domain.com/a/pattern/*");
+    fn test_robots_product_token() {
+        let user_agent = "TinyCrawler/0.1 (+https://example.com/bot)";
+        assert_eq!(robots_product_token(user_agent), "TinyCrawler");
     }
 
     #[test]
@@ -0,0 +1,665 @@
+use crate::spider::config::defaults;
+use crate::spider::error::SpiderError;
+use bytes::Bytes;
+use chrono::{DateTime, SecondsFormat, Utc};
+use data_encoding::BASE32;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use log::warn;
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use url::{Host, Position, Url};
+
+/// Profile of revisit records whose payload matches an earlier response
+const REVISIT_PROFILE: &str = "http://netpreserve.org/warc/1.1/revisit/identical-payload-digest";
+
+/// Header line of the CDX index, in the 11-field format
+const CDX_HEADER: &str = " CDX N b a m s k r M S V g";
+
+/// Number of exchanges waiting to be written before fetches wait for the writer
+const RECORD_QUEUE_SIZE: usize = 64;
+
+/// Response headers rewritten in records, since bodies are stored decoded
+const DECODED_HEADERS: &[&str] = &["content-encoding", "content-length", "transfer-encoding"];
+
+/// Settings for WARC output
+#[derive(Debug, Clone)]
+pub struct WarcConfig {
+    /// Directory the WARC files and CDX index are written to
+    pub directory: PathBuf,
+
+    /// Start a new WARC file once the current one reaches this size, in bytes
+    pub max_file_size: u64,
+}
+
+impl Default for WarcConfig {
+    fn default() -> Self {
+        Self {
+            directory: PathBuf::from("output").join("warc"),
+            max_file_size: defaults::MAX_WARC_FILE_SIZE,
+        }
+    }
+}
+
+/// A request and its response, as recorded in a WARC file
+///
+/// Redirects are recorded hop by hop, so the response is always for the
+/// requested URL.
+pub struct HttpExchange {
+    /// The HTTP method
+    pub method: String,
+
+    /// The requested URL
+    pub url: String,
+
+    /// The URL whose redirect led to this request, if any
+    pub redirected_from: Option<String>,
+
+    /// The page the URL was linked from, if any
+    pub referrer: Option<String>,
+
+    /// The request headers sent
+    pub request_headers: HeaderMap,
+
+    /// The HTTP version of the response, e.g. `HTTP/1.1`
+    pub version: String,
+
+    /// The HTTP status code
+    pub status: u16,
+
+    /// The response headers
+    pub response_headers: HeaderMap,
+
+    /// The decoded response body
+    pub body: Bytes,
+
+    /// When the request was sent
+    pub fetched_at: DateTime<Utc>,
+
+    /// Time taken to send the request and read the body
+    pub response_time: Duration,
+}
+
+/// Files written by a `WarcWriter`
+#[derive(Debug, Clone, Default)]
+pub struct WarcOutput {
+    /// Paths of the WARC files, in the order they were written
+    pub files: Vec<String>,
+
+    /// Path of the CDX index, if any record was written
+    pub cdx_file: Option<String>,
+}
+
+/// The WARC file currently being written
+struct WarcFile {
+    /// File name, without the directory
+    name: String,
+
+    /// Buffered writer for the file
+    writer: BufWriter<File>,
+
+    /// Bytes written so far
+    size: u64,
+
+    /// Record ID of the file's warcinfo record
+    warcinfo_id: String,
+}
+
+/// Writes fetches as gzipped WARC/1.1 records, with a CDX index
+///
+/// Each exchange becomes a request, a response and a metadata record. A response
+/// whose payload was already archived is written as a revisit record without
+/// the body. Every record is compressed separately, so the files can be read
+/// from any record offset.
+pub struct WarcWriter {
+    /// WARC settings
+    config: WarcConfig,
+
+    /// Start of every file name: the prefix and the time the writer was created
+    base_name: String,
+
+    /// Number of files opened so far
+    serial: usize,
+
+    /// The file being written
+    current: Option<WarcFile>,
+
+    /// Paths of the files written so far
+    files: Vec<String>,
+
+    /// Target URI and date of the first response per payload digest
+    payloads: HashMap<String, (String, String)>,
+
+    /// CDX lines of the response and revisit records
+    cdx: Vec<String>,
+}
+
+impl WarcWriter {
+    /// Create a writer for files named after `prefix`, such as the crawled domain
+    pub fn new(config: WarcConfig, prefix: &str) -> Result<Self, SpiderError> {
+        std::fs::create_dir_all(&config.directory)?;
+        let base_name = format!(
+            "{}-{}",
+            prefix.replace(['.', ':', '/'], "_"),
+            Utc::now().format("%Y%m%d%H%M%S")
+        );
+
+        Ok(Self {
+            config,
+            base_name,
+            serial: 0,
+            current: None,
+            files: Vec::new(),
+            payloads: HashMap::new(),
+            cdx: Vec::new(),
+        })
+    }
+
+    /// Write the request, response or revisit, and metadata records of an exchange
+    pub fn record(&mut self, exchange: &HttpExchange) -> Result<(), SpiderError> {
+        if self
+            .current
+            .as_ref()
+            .is_some_and(|file| file.size >= self.config.max_file_size)
+        {
+            self.close_file()?;
+        }
+        if self.current.is_none() {
+            self.open_file()?;
+        }
+
+        let date = exchange
+            .fetched_at
+            .to_rfc3339_opts(SecondsFormat::Secs, true);
+        let payload_digest = sha1_digest(&exchange.body);
+        let response_id = record_id();
+        let mut response_head = response_head(exchange);
+        let mut headers = vec![
+            ("WARC-Target-URI", exchange.url.clone()),
+            ("WARC-Payload-Digest", payload_digest.clone()),
+        ];
+
+        let revisit_of = match exchange.body.is_empty() {
+            true => None,
+            false => self.payloads.get(&payload_digest).cloned(),
+        };
+        let (kind, block) = match revisit_of {
+            Some((target, refers_date)) => {
+                headers.push(("WARC-Profile", REVISIT_PROFILE.to_string()));
+                headers.push(("WARC-Refers-To-Target-URI", target));
+                headers.push(("WARC-Refers-To-Date", refers_date));
+                ("revisit", response_head)
+            }
+            None => {
+                self.payloads
+                    .insert(payload_digest.clone(), (exchange.url.clone(), date.clone()));
+                response_head.extend_from_slice(&exchange.body);
+                ("response", response_head)
+            }
+        };
+        headers.push((
+            "Content-Type",
+            "application/http;msgtype=response".to_string(),
+        ));
+        let (offset, length) = self.write_record(kind, &response_id, &date, &headers, &block)?;
+
+        let mime = match kind {
+            "revisit" => "warc/revisit".to_string(),
+            _ => header_value(&exchange.response_headers, "content-type")
+                .and_then(|value| value.split(';').next().map(|m| m.trim().to_lowercase()))
+                .filter(|mime| !mime.is_empty())
+                .unwrap_or_else(|| "-".to_string()),
+        };
+        let redirect = header_value(&exchange.response_headers, "location")
+            .filter(|_| (300..400).contains(&exchange.status))
+            .unwrap_or_else(|| "-".to_string());
+        let file_name = self
+            .current
+            .as_ref()
+            .map(|f| f.name.clone())
+            .unwrap_or_default();
+        self.cdx.push(format!(
+            "{} {} {} {} {} {} {} - {} {} {}",
+            surt(&exchange.url),
+            exchange.fetched_at.format("%Y%m%d%H%M%S"),
+            exchange.url,
+            mime,
+            exchange.status,
+            payload_digest.trim_start_matches("sha1:"),
+            redirect.replace(' ', "%20"),
+            length,
+            offset,
+            file_name
+        ));
+
+        let concurrent = [
+            ("WARC-Target-URI", exchange.url.clone()),
+            ("WARC-Concurrent-To", response_id),
+        ];
+        let mut request_headers = concurrent.to_vec();
+        request_headers.push((
+            "Content-Type",
+            "application/http;msgtype=request".to_string(),
+        ));
+        self.write_record(
+            "request",
+            &record_id(),
+            &date,
+            &request_headers,
+            &request_head(exchange),
+        )?;
+
+        let mut fields = Vec::new();
+        if let Some(referrer) = &exchange.referrer {
+            fields.push(("via", referrer.clone()));
+        }
+        if let Some(redirected_from) = &exchange.redirected_from {
+            fields.push(("redirectedFrom", redirected_from.clone()));
+        }
+        fields.push((
+            "fetchTimeMs",
+            exchange.response_time.as_millis().to_string(),
+        ));
+        let mut metadata_headers = concurrent.to_vec();
+        metadata_headers.push(("Content-Type", "application/warc-fields".to_string()));
+        self.write_record(
+            "metadata",
+            &record_id(),
+            &date,
+            &metadata_headers,
+            &warc_fields(&fields),
+        )?;
+
+        Ok(())
+    }
+
+    /// Close the last file and write the sorted CDX index
+    pub fn finish(mut self) -> Result<WarcOutput, SpiderError> {
+        self.close_file()?;
+        if self.files.is_empty() {
+            return Ok(WarcOutput::default());
+        }
+
+        self.cdx.sort();
+        let cdx_path = self
+            .config
+            .directory
+            .join(format!("{}.cdx", self.base_name));
+        let mut cdx = BufWriter::new(File::create(&cdx_path)?);
+        writeln!(cdx, "{}", CDX_HEADER)?;
+        for line in &self.cdx {
+            writeln!(cdx, "{}", line)?;
+        }
+        cdx.flush()?;
+
+        Ok(WarcOutput {
+            files: self.files,
+            cdx_file: Some(cdx_path.to_string_lossy().to_string()),
+        })
+    }
+
+    /// Open the next WARC file and write its warcinfo record
+    fn open_file(&mut self) -> Result<(), SpiderError> {
+        self.serial += 1;
+        let name = format!("{}-{:05}.warc.gz", self.base_name, self.serial);
+        let path = self.config.directory.join(&name);
+        let warcinfo_id = record_id();
+
+        self.current = Some(WarcFile {
+            name: name.clone(),
+            writer: BufWriter::new(File::create(&path)?),
+            size: 0,
+            warcinfo_id: warcinfo_id.clone(),
+        });
+        self.files.push(path.to_string_lossy().to_string());
+
+        let info = warc_fields(&[
+            (
+                "software",
+                format!("tiny_crawler/{}", env!("CARGO_PKG_VERSION")),
+            ),
+            ("format", "WARC File Format 1.1".to_string()),
+            (
+                "conformsTo",
+                "https://iipc.github.io/warc-specifications/specifications/warc-format/warc-1.1/"
+                    .to_string(),
+            ),
+            ("isPartOf", self.base_name.clone()),
+        ]);
+        let headers = [
+            ("WARC-Filename", name),
+            ("Content-Type", "application/warc-fields".to_string()),
+        ];
+        let date = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+        self.write_record("warcinfo", &warcinfo_id, &date, &headers, &info)?;
+        Ok(())
+    }
+
+    /// Flush and close the current file, if any
+    fn close_file(&mut self) -> Result<(), SpiderError> {
+        if let Some(mut file) = self.current.take() {
+            file.writer.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Append a gzipped record to the current file, returning its offset and length
+    fn write_record(
+        &mut self,
+        kind: &str,
+        id: &str,
+        date: &str,
+        headers: &[(&str, String)],
+        block: &[u8],
+    ) -> Result<(u64, u64), SpiderError> {
+        let file = self
+            .current
+            .as_mut()
+            .ok_or_else(|| SpiderError::Other("No open WARC file".to_string()))?;
+
+        let mut record = Vec::with_capacity(block.len() + 512);
+        write!(
+            record,
+            "WARC/1.1\r\nWARC-Type: {}\r\nWARC-Record-ID: {}\r\nWARC-Date: {}\r\n",
+            kind, id, date
+        )?;
+        if kind != "warcinfo" {
+            write!(record, "WARC-Warcinfo-ID: {}\r\n", file.warcinfo_id)?;
+        }
+        for (name, value) in headers {
+            write!(record, "{}: {}\r\n", name, value)?;
+        }
+        write!(
+            record,
+            "WARC-Block-Digest: {}\r\nContent-Length: {}\r\n\r\n",
+            sha1_digest(block),
+            block.len()
+        )?;
+        record.extend_from_slice(block);
+        record.extend_from_slice(b"\r\n\r\n");
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&record)?;
+        let compressed = encoder.finish()?;
+
+        let offset = file.size;
+        file.writer.write_all(&compressed)?;
+        file.size += compressed.len() as u64;
+        Ok((offset, compressed.len() as u64))
+    }
+}
+
+/// Runs a `WarcWriter` on a blocking thread, so fetches never wait on disk IO
+///
+/// Exchanges are written in the order they are recorded. Write failures are
+/// logged rather than failing the fetch.
+pub struct WarcRecorder {
+    /// Queue of exchanges to write
+    sender: mpsc::Sender<HttpExchange>,
+
+    /// The thread writing the records, returning the files written
+    task: JoinHandle<Result<WarcOutput, SpiderError>>,
+}
+
+impl WarcRecorder {
+    /// Start writing records with a writer
+    pub fn spawn(mut writer: WarcWriter) -> Self {
+        let (sender, mut receiver) = mpsc::channel::<HttpExchange>(RECORD_QUEUE_SIZE);
+        let task = tokio::task::spawn_blocking(move || {
+            while let Some(exchange) = receiver.blocking_recv() {
+                if let Err(e) = writer.record(&exchange) {
+                    warn!("Failed to write WARC records for {}: {}", exchange.url, e);
+                }
+            }
+            writer.finish()
+        });
+
+        Self { sender, task }
+    }
+
+    /// Queue an exchange to be written, waiting if the writer is behind
+    pub async fn record(&self, exchange: HttpExchange) {
+        if self.sender.send(exchange).await.is_err() {
+            warn!("WARC writer stopped, dropping record");
+        }
+    }
+
+    /// Write the queued exchanges, close the last file and write the CDX index
+    pub async fn finish(self) -> Result<WarcOutput, SpiderError> {
+        drop(self.sender);
+        self.task
+            .await
+            .map_err(|e| SpiderError::Other(format!("WARC writer failed: {}", e)))?
+    }
+}
+
+/// Sort-friendly URL key used by CDX indexes, e.g. `com,example)/path?a=1`
+///
+/// Domain labels are reversed and a leading `www.` is dropped; query
+/// parameters are sorted.
+pub fn surt(url: &str) -> String {
+    let Ok(parsed) = Url::parse(url) else {
+        return url.to_lowercase();
+    };
+
+    let mut key = match parsed.host() {
+        Some(Host::Domain(domain)) => {
+            let domain = domain.to_lowercase();
+            let domain = domain.strip_prefix("www.").unwrap_or(&domain);
+            domain.split('.').rev().collect::<Vec<_>>().join(",")
+        }
+        Some(host) => host.to_string(),
+        None => String::new(),
+    };
+    if let Some(port) = parsed.port() {
+        key.push_str(&format!(":{}", port));
+    }
+    key.push(')');
+    key.push_str(&parsed.path().to_lowercase());
+    if let Some(query) = parsed.query().filter(|query| !query.is_empty()) {
+        let mut params: Vec<&str> = query.split('&').collect();
+        params.sort_unstable();
+        key.push('?');
+        key.push_str(&params.join("&").to_lowercase());
+    }
+    key
+}
+
+/// Request line and headers of an exchange
+fn request_head(exchange: &HttpExchange) -> Vec<u8> {
+    let parsed = Url::parse(&exchange.url).ok();
+    let target = parsed
+        .as_ref()
+        .map(|url| &url[Position::BeforePath..Position::AfterQuery])
+        .unwrap_or("/");
+
+    let mut head = format!("{} {} HTTP/1.1\r\n", exchange.method, target).into_bytes();
+    if !exchange.request_headers.contains_key("host") {
+        if let Some(url) = &parsed {
+            let host = &url[Position::BeforeHost..Position::AfterPort];
+            head.extend_from_slice(format!("Host: {}\r\n", host).as_bytes());
+        }
+    }
+    write_headers(&mut head, &exchange.request_headers, &[]);
+    head.extend_from_slice(b"\r\n");
+    head
+}
+
+/// Status line and headers of an exchange's response
+///
+/// The body is stored decoded, so encoding headers are dropped and the
+/// `Content-Length` is set to the decoded size.
+fn response_head(exchange: &HttpExchange) -> Vec<u8> {
+    let reason = StatusCode::from_u16(exchange.status)
+        .ok()
+        .and_then(|status| status.canonical_reason())
+        .unwrap_or("");
+    let mut head = format!("{} {} {}\r\n", exchange.version, exchange.status, reason).into_bytes();
+    write_headers(&mut head, &exchange.response_headers, DECODED_HEADERS);
+    head.extend_from_slice(format!("Content-Length: {}\r\n\r\n", exchange.body.len()).as_bytes());
+    head
+}
+
+/// Append `name: value` lines for the headers not in `skip`
+fn write_headers(head: &mut Vec<u8>, headers: &HeaderMap, skip: &[&str]) {
+    for (name, value) in headers {
+        if skip.contains(&name.as_str()) {
+            continue;
+        }
+        head.extend_from_slice(name.as_str().as_bytes());
+        head.extend_from_slice(b": ");
+        head.extend_from_slice(value.as_bytes());
+        head.extend_from_slice(b"\r\n");
+    }
+}
+
+/// Format `name: value` lines as an `application/warc-fields` block
+fn warc_fields(fields: &[(&str, String)]) -> Vec<u8> {
+    fields
+        .iter()
+        .map(|(name, value)| format!("{}: {}\r\n", name, value))
+        .collect::<String>()
+        .into_bytes()
+}
+
+/// Value of a header as text, if present
+fn header_value(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get(name)
+        .map(|value| String::from_utf8_lossy(value.as_bytes()).to_string())
+}
+
+/// SHA-1 digest in the `sha1:<base32>` form used by WARC and CDX
+fn sha1_digest(bytes: &[u8]) -> String {
+    format!("sha1:{}", BASE32.encode(&Sha1::digest(bytes)))
+}
+
+/// A new random record ID
+fn record_id() -> String {
+    let mut bytes: [u8; 16] = rand::random();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "<urn:uuid:{}-{}-{}-{}-{}>",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::MultiGzDecoder;
+    use reqwest::header::HeaderValue;
+    use std::io::Read;
+
+    fn exchange(url: &str, headers: &HeaderMap, body: &[u8]) -> HttpExchange {
+        HttpExchange {
+            method: "GET".to_string(),
+            url: url.to_string(),
+            redirected_from: None,
+            referrer: Some("https://example.com/".to_string()),
+            request_headers: headers.clone(),
+            version: "HTTP/1.1".to_string(),
+            status: 200,
+            response_headers: headers.clone(),
+            body: Bytes::copy_from_slice(body),
+            fetched_at: Utc::now(),
+            response_time: Duration::from_millis(12),
+        }
+    }
+
+    fn read_warc(path: &str) -> String {
+        let mut text = String::new();
+        MultiGzDecoder::new(File::open(path).unwrap())
+            .read_to_string(&mut text)
+            .unwrap();
+        text
+    }
+
+    #[test]
+    fn test_surt() {
+        assert_eq!(
+            surt("https://www.Example.com/Path?b=2&a=1"),
+            "com,example)/path?a=1&b=2"
+        );
+        assert_eq!(surt("http://127.0.0.1:8080/"), "127.0.0.1:8080)/");
+    }
+
+    #[test]
+    fn test_records_revisits_and_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = WarcConfig {
+            directory: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "content-type",
+            HeaderValue::from_static("text/html; charset=utf-8"),
+        );
+        headers.insert("content-encoding", HeaderValue::from_static("gzip"));
+
+        let mut writer = WarcWriter::new(config, "example.com").unwrap();
+        writer
+            .record(&exchange("https://example.com/a", &headers, b"<p>same</p>"))
+            .unwrap();
+        writer
+            .record(&exchange("https://example.com/b", &headers, b"<p>same</p>"))
+            .unwrap();
+        let output = writer.finish().unwrap();
+
+        assert_eq!(output.files.len(), 1);
+        let warc = read_warc(&output.files[0]);
+        assert_eq!(warc.matches("WARC/1.1\r\n").count(), 7);
+        assert_eq!(warc.matches("WARC-Type: response").count(), 1);
+        assert_eq!(warc.matches("WARC-Type: revisit").count(), 1);
+        assert!(warc.contains("WARC-Refers-To-Target-URI: https://example.com/a"));
+        assert!(warc.contains("GET /b HTTP/1.1\r\nHost: example.com\r\n"));
+        assert!(warc.contains(
+            "HTTP/1.1 200 OK\r\ncontent-type: text/html; charset=utf-8\r\n\
+             Content-Length: 11\r\n\r\n<p>same</p>"
+        ));
+
+        let cdx = std::fs::read_to_string(output.cdx_file.unwrap()).unwrap();
+        let lines: Vec<&str> = cdx.lines().collect();
+        assert_eq!(lines[0], CDX_HEADER);
+        assert!(lines[1].starts_with("com,example)/a "));
+        assert!(lines[1].contains(" text/html 200 "));
+        assert!(lines[2].contains(" warc/revisit 200 "));
+    }
+
+    #[test]
+    fn test_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = WarcConfig {
+            directory: dir.path().to_path_buf(),
+            max_file_size: 1,
+        };
+        let headers = HeaderMap::new();
+
+        let mut writer = WarcWriter::new(config, "example.com").unwrap();
+        for url in ["https://example.com/1", "https://example.com/2"] {
+            writer
+                .record(&exchange(url, &headers, url.as_bytes()))
+                .unwrap();
+        }
+        let output = writer.finish().unwrap();
+
+        assert_eq!(output.files.len(), 2);
+        assert!(output.files[1].ends_with("-00002.warc.gz"));
+        assert!(read_warc(&output.files[1]).starts_with("WARC/1.1\r\nWARC-Type: warcinfo"));
+    }
+}